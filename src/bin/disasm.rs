@@ -0,0 +1,55 @@
+// Lists the instructions in a ROM file without running it.
+//
+//   disasm <rom> [START [COUNT]]
+//
+// START is a hex address into the file (default 0x100, the entry point) and
+// COUNT how many instructions to show (default 32). Addresses past 0x3FFF are
+// file offsets, banks aren't mapped.
+
+use std::fs;
+use std::process;
+
+use doma_emu::cpu::disasm;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(path) = args.first() else {
+        eprintln!("usage: disasm <rom> [START [COUNT]]");
+        process::exit(2);
+    };
+    let start = match args.get(1) {
+        Some(start) => usize::from_str_radix(start.trim_start_matches("0x"), 16).unwrap_or_else(|_| {
+            eprintln!("bad address: {}", start);
+            process::exit(2);
+        }),
+        None => 0x100,
+    };
+    let count = match args.get(2) {
+        Some(count) => count.parse().unwrap_or_else(|_| {
+            eprintln!("bad count: {}", count);
+            process::exit(2);
+        }),
+        None => 32,
+    };
+
+    let rom = fs::read(path).unwrap_or_else(|error| {
+        eprintln!("{}: {}", path, error);
+        process::exit(2);
+    });
+    let mut offset = start;
+    for _ in 0..count {
+        if offset >= rom.len() {
+            break;
+        }
+        let instruction = disasm::disassemble(&rom[offset..], offset as u16);
+        let end = (offset + instruction.length as usize).min(rom.len());
+        let bytes: Vec<String> = rom[offset..end].iter().map(|byte| format!("{:02X}", byte)).collect();
+        let cycles = if instruction.cycles == instruction.cycles_taken {
+            instruction.cycles.to_string()
+        } else {
+            format!("{}/{}", instruction.cycles, instruction.cycles_taken)
+        };
+        println!("{:06X}  {:<9} {:<20} ; {}", offset, bytes.join(" "), instruction.text, cycles);
+        offset = end;
+    }
+}
@@ -0,0 +1,118 @@
+// Runs a ROM with no windows and reports how it went, for CI.
+//
+//   headless <rom> [--frames N] [--until-serial TEXT] [--fail-serial TEXT]
+//            [--until-pc ADDR] [--until-loop] [--until-breakpoint]
+//            [--screen FILE] [--serial FILE] [--compare FILE] [--diff-dir DIR]
+//
+// Without any --until/--fail it just runs the frames and passes. Otherwise it
+// passes when an --until is met, and fails on a --fail or when the frames run
+// out first. A CPU lock-up always fails. --compare also fails when the screen
+// doesn't match a reference PNG, leaving the actual screen and a diff in
+// --diff-dir. Exit status is 0 for pass, 1 for fail and 2 for bad arguments.
+
+use std::fs;
+use std::process;
+
+use doma_emu::runner::{Runner, RunResult, StopCondition};
+
+const DEFAULT_FRAMES: u32 = 60 * 60;
+
+struct Options {
+    rom: String,
+    frames: u32,
+    until: Vec<StopCondition>,
+    fail: Vec<StopCondition>,
+    screen: Option<String>,
+    serial: Option<String>,
+    compare: Option<String>,
+    diff_dir: String,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        rom: String::new(),
+        frames: DEFAULT_FRAMES,
+        until: Vec::new(),
+        fail: Vec::new(),
+        screen: None,
+        serial: None,
+        compare: None,
+        diff_dir: ".".to_string(),
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--frames" => options.frames = value()?.parse().map_err(|_| "--frames needs a number".to_string())?,
+            "--until-serial" => options.until.push(StopCondition::Serial(value()?)),
+            "--fail-serial" => options.fail.push(StopCondition::Serial(value()?)),
+            "--until-pc" => {
+                let pc = value()?;
+                let pc = u16::from_str_radix(pc.trim_start_matches("0x"), 16)
+                    .map_err(|_| format!("bad address: {}", pc))?;
+                options.until.push(StopCondition::Pc(pc));
+            }
+            "--until-loop" => options.until.push(StopCondition::InfiniteLoop),
+            "--until-breakpoint" => options.until.push(StopCondition::Breakpoint),
+            "--screen" => options.screen = Some(value()?),
+            "--serial" => options.serial = Some(value()?),
+            "--compare" => options.compare = Some(value()?),
+            "--diff-dir" => options.diff_dir = value()?,
+            _ if arg.starts_with("--") => return Err(format!("unknown option: {}", arg)),
+            _ => options.rom = arg.clone(),
+        }
+    }
+    if options.rom.is_empty() {
+        return Err("no ROM given".to_string());
+    }
+    Ok(options)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = parse_args(&args).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        eprintln!("usage: headless <rom> [--frames N] [--until-serial TEXT] [--fail-serial TEXT] \
+            [--until-pc ADDR] [--until-loop] [--until-breakpoint] [--screen FILE] [--serial FILE] \
+            [--compare FILE] [--diff-dir DIR]");
+        process::exit(2);
+    });
+
+    let mut runner = Runner::from_file(&options.rom).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(2);
+    });
+
+    let conditions: Vec<StopCondition> = options.fail.iter().chain(options.until.iter()).cloned().collect();
+    // nothing comes after a lock-up, no point running on
+    let result = runner.run(options.frames, &[conditions.as_slice(), &[StopCondition::Lockup]].concat());
+    let mut passed = match &result {
+        RunResult::Stopped(StopCondition::Lockup) => false,
+        RunResult::Stopped(condition) => !options.fail.contains(condition),
+        RunResult::OutOfFrames => conditions.is_empty(),
+    };
+
+    if let Some(reference) = &options.compare {
+        if let Err(error) = runner.compare_screen(reference, &options.diff_dir) {
+            println!("screen: {}", error);
+            passed = false;
+        }
+    }
+
+    if let Some(path) = &options.screen {
+        if let Err(error) = runner.save_screen(path) {
+            eprintln!("screen dump failed: {}", error);
+        }
+    }
+    if let Some(path) = &options.serial {
+        if let Err(error) = fs::write(path, runner.mmu.serial.output()) {
+            eprintln!("{}: {}", path, error);
+        }
+    }
+
+    println!("serial: {}", runner.serial_text());
+    println!("{}", runner.registers());
+    println!("{:?} after {} frames: {}", result, runner.frame(), if passed {"pass"} else {"fail"});
+    process::exit(if passed {0} else {1});
+}
@@ -0,0 +1,123 @@
+use crate::mmu::interrupts::InterruptType;
+
+// Everything the CPU sees of the rest of the machine. MMU is the real one,
+// TestBus below is plain RAM for CPU tests and tools that don't want a whole
+// Game Boy behind them.
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+    // the rest of the machine moves on by this many M-cycles
+    fn tick(&mut self, m_cycles: u8);
+
+    // IE and IF
+    fn interrupt_enable(&self) -> u8;
+    fn interrupt_flags(&self) -> u8;
+    // the CPU took it, clear its IF bit
+    fn acknowledge_interrupt(&mut self, interrupt: InterruptType);
+
+    // VRAM DMA, STOP and the speed switch keep the CPU off the bus
+    fn cpu_stalled(&self) -> bool {
+        false
+    }
+    // a selected joypad line is low
+    fn button_held(&self) -> bool {
+        false
+    }
+    // STOP ran: low power until a button goes down, or the CGB speed switch
+    fn stop(&mut self) {}
+
+    // highest priority interrupt that's both enabled and requested
+    fn pending_interrupt(&self) -> Option<InterruptType> {
+        let pending = self.interrupt_enable() & self.interrupt_flags() & 0x1F;
+        if pending == 0 {
+            return None;
+        }
+        InterruptType::try_from(pending.trailing_zeros() as u8).ok()
+    }
+}
+
+// one read or write the CPU did, see TestBus::start_log
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BusAccess {
+    // M-cycle it happened in, counting from 0 when the log started
+    pub cycle: usize,
+    pub address: u16,
+    pub value: u8,
+    pub write: bool,
+}
+
+// 64KiB of RAM with nothing mapped in. IE and IF are just the bytes at
+// 0xFFFF and 0xFF0F, ticking only counts the M-cycles.
+pub struct TestBus {
+    ram: Vec<u8>,
+    log: Option<Vec<BusAccess>>,
+    log_start: usize,
+    cycles: usize,
+}
+
+impl Default for TestBus {
+    fn default() -> Self {
+        Self {
+            ram: vec![0; 0x10000],
+            log: None,
+            log_start: 0,
+            cycles: 0,
+        }
+    }
+}
+
+impl TestBus {
+    pub fn new() -> TestBus {
+        TestBus::default()
+    }
+
+    // records every read and write from here on
+    pub fn start_log(&mut self) {
+        self.log = Some(Vec::new());
+        self.log_start = self.cycles;
+    }
+    pub fn take_log(&mut self) -> Vec<BusAccess> {
+        self.log.take().unwrap_or_default()
+    }
+
+    // M-cycles ticked so far
+    pub fn cycles(&self) -> usize {
+        self.cycles
+    }
+
+    // the CPU ticks the cycle before it accesses memory, so an access
+    // belongs to the cycle that just went by
+    fn log_access(&mut self, address: u16, value: u8, write: bool) {
+        let cycle = self.cycles.saturating_sub(self.log_start + 1);
+        if let Some(log) = &mut self.log {
+            log.push(BusAccess {cycle, address, value, write});
+        }
+    }
+}
+
+impl Bus for TestBus {
+    fn read(&mut self, address: u16) -> u8 {
+        let value = self.ram[address as usize];
+        self.log_access(address, value, false);
+        value
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        self.log_access(address, value, true);
+        self.ram[address as usize] = value;
+    }
+
+    fn tick(&mut self, m_cycles: u8) {
+        self.cycles += m_cycles as usize;
+    }
+
+    fn interrupt_enable(&self) -> u8 {
+        self.ram[0xFFFF]
+    }
+    fn interrupt_flags(&self) -> u8 {
+        self.ram[0xFF0F]
+    }
+    fn acknowledge_interrupt(&mut self, interrupt: InterruptType) {
+        self.ram[0xFF0F] &= !(1 << interrupt as u8);
+    }
+}
@@ -170,6 +170,39 @@ impl Cartridge {
         
     }
 
+    // 0x143 is the last byte of the title area: 0x80 = works on CGB and DMG, 0xC0 = CGB only
+    pub fn cgb_flag(&self) -> u8 {
+        self.header.title[15]
+    }
+
+    pub fn is_cgb(&self) -> bool {
+        self.cgb_flag() & 0x80 != 0
+    }
+
+    // the SGB ignores packets unless 0x146 is 0x03 and the old licensee is 0x33
+    pub fn supports_sgb(&self) -> bool {
+        self.header.sgb_flag == 0x03 && self.header.old_lic_code == 0x33
+    }
+
+    // the CGB boot rom only colourises games published by Nintendo
+    pub fn is_nintendo(&self) -> bool {
+        match self.header.old_lic_code {
+            0x01 => true,
+            0x33 => self.header.new_lic_code == u16::from_be_bytes(*b"01"),
+            _ => false,
+        }
+    }
+
+    // sum of 0x134-0x143, the key of the boot rom palette lookup
+    pub fn title_checksum(&self) -> u8 {
+        self.header.title.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+    }
+
+    // 0x137, used to tell apart titles that share a checksum
+    pub fn title_fourth_letter(&self) -> u8 {
+        self.header.title[3]
+    }
+
     pub fn rom_size(&self) -> usize {
         32 * (1 << self.header.rom_size)
     }
@@ -0,0 +1,51 @@
+use std::fmt;
+
+use crate::cpu::opcodes;
+
+// Turns raw bytes into SM83 mnemonics without running anything, for traces
+// and for looking at ROMs. It's the same decoding the CPU runs on, the text
+// is Instruction's Display.
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Disassembly {
+    pub address: u16,
+    pub length: u8,
+    pub text: String,
+    // M-cycles, cycles_taken is only different for conditional branches
+    pub cycles: u8,
+    pub cycles_taken: u8,
+    // where a jump, call or RST goes, when it's known without running it
+    pub target: Option<u16>,
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+// `bytes` starts at the opcode, missing operand bytes read as 0
+pub fn disassemble(bytes: &[u8], address: u16) -> Disassembly {
+    let byte = |i: usize| bytes.get(i).copied().unwrap_or(0);
+    let instruction = opcodes::decode(byte(0), u16::from_le_bytes([byte(1), byte(2)]));
+    Disassembly {
+        address,
+        length: instruction.length(),
+        text: instruction.to_string(),
+        cycles: instruction.cycles(),
+        cycles_taken: instruction.cycles_taken(),
+        target: instruction.target(address),
+    }
+}
+
+// everything from `address` on, `bytes[0]` being at `address`
+pub fn disassemble_all(bytes: &[u8], address: u16) -> Vec<Disassembly> {
+    let mut out = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let instruction = disassemble(&bytes[offset..], address.wrapping_add(offset as u16));
+        offset += instruction.length as usize;
+        out.push(instruction);
+    }
+    out
+}
@@ -1,566 +1,154 @@
-
 pub use crate::cpu::CPU;
-pub use crate::instruction::Instruction;
-pub use crate::mmu::MMU;
-pub fn executer(cpu: &mut CPU, instruction: &Instruction, mmu: &mut MMU) -> u8 {
+pub use crate::instruction::{Instruction, R8, R16, R16Stack, R16Indirect, Condition, Alu, Shift};
+pub use crate::bus::Bus;
+
+/*
+    Runs an instruction that's already been fetched, PC is past its last
+    byte. Every register and memory access happens here, the timed ones
+    through cpu.read/write so they land on the right M-cycle; the cycles
+    left over are ticked by run_instruction. Returns whether a conditional
+    branch was taken, which is what decides between cycles and
+    cycles_taken.
+*/
+pub fn execute<B: Bus>(cpu: &mut CPU, instruction: Instruction, bus: &mut B) -> bool {
     match instruction {
-        Instruction::LdN(n, v) => {
-            match n {
-                0 => {cpu.b = *v;},
-                1 => {cpu.c = *v;},
-                2 => {cpu.d = *v;},
-                3 => {cpu.e = *v;},
-                4 => {cpu.h = *v;},
-                5 => {cpu.l = *v;},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
-            }
-            cpu.pc += 2;
-            2
-        },
+        Instruction::Nop => {},
 
-        Instruction::LdAR2(n, r2) => {
-            cpu.a = *r2 as u8;
-            cpu.pc += 1;
-            match n {
-                0 => {1},
-                1 => {2},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
+        Instruction::Stop => {
+            // with a button already down it never stops, it's a 1 byte
+            // opcode if an interrupt is waiting and otherwise a HALT
+            if bus.button_held() {
+                if bus.pending_interrupt().is_none() {
+                    cpu.set_halted(true);
+                    cpu.pc = cpu.pc.wrapping_add(1);
+                }
+            } else {
+                bus.stop();
+                cpu.pc = cpu.pc.wrapping_add(1);
             }
         },
-        Instruction::LdAnn(r2) => {
-            cpu.a = *r2 as u8;
-            cpu.pc += 3;
-            4
-        },
-        Instruction::LdAd8(r2) => {
-            cpu.a = *r2 as u8;
-            cpu.pc += 2;
-            2
-        },
-        Instruction::LdBR2(r2, b) => {
-            cpu.b = *r2 as u8;
-            cpu.pc += 1;
-            if *b {2} else {1}
-        },
-        Instruction::LdCR2(r2, b) => {
-            cpu.c = *r2 as u8;
-            cpu.pc += 1;
-            if *b {2} else {1}
-        },
-        Instruction::LdDR2(r2, b) => {
-            cpu.d = *r2 as u8;
-            cpu.pc += 1;
-            if *b {2} else {1}
-        },
-        Instruction::LdER2(r2, b) => {
-            cpu.e = *r2 as u8;
-            cpu.pc += 1;
-            if *b {2} else {1}
-        },
-        Instruction::LdHR2(r2, b) => {
-            cpu.h = *r2 as u8;
-            cpu.pc += 1;
-            if *b {2} else {1}
-        },
-        Instruction::LdLR2(r2, b) => {
-            cpu.l = *r2 as u8;
-            cpu.pc += 1;
-            if *b {2} else {1}
-        },
-        Instruction::LdHlR2(r2, b) => {
-            mmu.write_byte(cpu.get_hl(), *r2);
-            if *b {cpu.pc += 2; 3} else {cpu.pc += 1; 2}
-        },
 
-        Instruction::LdnA(n, a) => {
-            match n {
-                0 => {cpu.b = *a;},
-                1 => {cpu.c = *a;},
-                2 => {cpu.d = *a;},
-                3 => {cpu.e = *a;},
-                4 => {cpu.h = *a;},
-                5 => {cpu.l = *a;},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
+        Instruction::Halt => {
+            // With IME on, or nothing pending, it halts until IE & IF has
+            // something and then carries on (serving it if IME is on).
+            // With IME off and an interrupt already waiting it doesn't halt
+            // at all, and the next opcode fetch doesn't move PC: the halt bug.
+            if !cpu.ime && bus.pending_interrupt().is_some() {
+                cpu.halt_bug = true;
+            } else {
+                cpu.set_halted(true);
             }
-            cpu.pc += 1;
-            1
         },
-        Instruction::Ldn16A(n, a) => {
-            mmu.write_byte(*n, *a);
-            cpu.pc += 1;
-            2
-        },
-        Instruction::Lda16A(n, a) => {
-            mmu.write_byte(*n, *a);
-            cpu.pc += 3;
-            4
-        },
-        Instruction::LdAc(c) => {
-            cpu.a = mmu.read_byte(0xFF00 | *c as u16);
-            cpu.pc += 2;
-            2
-        },
-        Instruction::LdCa(a) => {
-            mmu.write_byte(0xFF00 | cpu.c as u16, *a);
-            cpu.pc += 2;
-            2
-        },
-        
-
-        Instruction::LddAHl(hl16) => {
-            cpu.a = mmu.read_byte(*hl16);
-            let mut hl = *hl16 as u16;
-            hl = hl.wrapping_sub(1);
-            cpu.set_hl(hl);
-            cpu.pc += 1;
-            2
-        },
-        Instruction::LddHlA(hl16) => {
-            let mut hl = *hl16 as u16;
-            mmu.write_byte(hl, cpu.a);
-            hl = hl.wrapping_sub(1);
-            cpu.set_hl(hl);
-            cpu.pc += 1;
-            // maybe implement DECHl here
-
-            //println!("CPU STATE: {:?}", cpu);
-            2
-        },
-        Instruction::LdIAHl(hl16) => {
-            cpu.a = mmu.read_byte(*hl16);
-            let mut hl = *hl16 as u16;
-            hl = hl.wrapping_add(1);
-            cpu.set_hl(hl);
-            cpu.pc += 1;
-            2
-        },
-        Instruction::LdIHlA(hl16) => {
-            let mut hl = *hl16 as u16;
-            mmu.write_byte(hl, cpu.a);
-            hl = hl.wrapping_add(1);
-            cpu.set_hl(hl);
-            cpu.pc += 1;
-            2
-        },
-
 
+        Instruction::Di => cpu.ime = false,
+        Instruction::Ei => cpu.ei = true,
 
-        Instruction::LdHnA(n) => {
-            mmu.write_byte(0xFF00 | *n as u16, cpu.a);
-            cpu.pc += 2;
-            3
+        // a real one hangs until it's switched off. PC goes back to the
+        // opcode so it's easy to find
+        Instruction::Illegal(_) => {
+            cpu.pc = cpu.pc.wrapping_sub(1);
+            cpu.locked = true;
         },
-        Instruction::LdHAn(n) => {
-            cpu.a = mmu.read_byte(0xFF00 | *n as u16);
-            //cpu.a = 0x90;
-            cpu.pc += 2;
-            3
-        },
-
 
-        Instruction::LdBc(d16) => {
-            cpu.b = (d16 >> 8) as u8;
-            cpu.c = (d16 & 0x00FF) as u8;
-            cpu.pc += 3;
-            3
-        },
-        Instruction::LdDe(d16) => {
-            cpu.d = (d16 >> 8) as u8;
-            cpu.e = (d16 & 0x00FF) as u8;
-            cpu.pc += 3;
-            3
+        // 8 bit loads
+        Instruction::Ld(to, from) => {
+            let v = read_r8(cpu, bus, from);
+            write_r8(cpu, bus, to, v);
         },
-        Instruction::LdHl(d16) => {
-            cpu.h = (d16 >> 8) as u8;
-            cpu.l = (d16 & 0x00FF) as u8;
-            cpu.pc += 3;
-            3
+        Instruction::LdImm(r, n) => write_r8(cpu, bus, r, n),
+        Instruction::StoreA(rr) => {
+            let address = indirect_address(cpu, rr);
+            cpu.write(bus, address, cpu.a);
         },
-        Instruction::LdSp(d16) => {
-            cpu.sp = *d16;
-            cpu.pc += 3;
-            3
+        Instruction::LoadA(rr) => {
+            let address = indirect_address(cpu, rr);
+            cpu.a = cpu.read(bus, address);
         },
-        Instruction::LdSpHl(hl) => {
-            cpu.sp = *hl;
-            cpu.pc += 1;
-            3
-        },
-        Instruction::LdHlSp(s8) => {
-            let sp = cpu.get_sp();
-            cpu.set_flag_z(false);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h((sp & 0x000F) + (*s8 as u16 & 0x000F) > 0x000F);
-            cpu.set_flag_c((sp & 0x00FF) + (*s8 as u16 & 0x00FF) > 0x00FF);
-            cpu.set_hl(cpu.sp.wrapping_add(*s8 as u16));
-            cpu.pc += 2;
-            3
+        Instruction::StoreAAbsolute(nn) => cpu.write(bus, nn, cpu.a),
+        Instruction::LoadAAbsolute(nn) => cpu.a = cpu.read(bus, nn),
+        Instruction::StoreAHigh(n) => cpu.write(bus, 0xFF00 | n as u16, cpu.a),
+        Instruction::LoadAHigh(n) => cpu.a = cpu.read(bus, 0xFF00 | n as u16),
+        Instruction::StoreAHighC => cpu.write(bus, 0xFF00 | cpu.c as u16, cpu.a),
+        Instruction::LoadAHighC => cpu.a = cpu.read(bus, 0xFF00 | cpu.c as u16),
+
+        // 16 bit loads
+        Instruction::LdImm16(rr, nn) => set_r16(cpu, rr, nn),
+        Instruction::StoreSp(nn) => {
+            cpu.write(bus, nn, cpu.sp as u8);
+            cpu.write(bus, nn.wrapping_add(1), (cpu.sp >> 8) as u8);
         },
-        Instruction::LdnnSp(d16) => {
-            mmu.write_byte(*d16, (cpu.sp & 0x00FF) as u8);
-            mmu.write_byte(d16.wrapping_add(1), (cpu.sp >> 8) as u8);
-            cpu.pc += 3;
-            5
+        Instruction::LdSpHl => cpu.sp = cpu.get_hl(),
+        Instruction::LdHlSpOffset(e) => {
+            let r = add_sp(cpu, e);
+            cpu.set_hl(r);
         },
-
-        Instruction::Pushnn(n16) => {
-            let addr1: u8 = ((n16 & 0xFF00) >> 8) as u8;
-            let addr2: u8 = (n16 & 0x00FF) as u8;
+        Instruction::Push(rr) => {
+            let value = match rr {
+                R16Stack::BC => cpu.get_bc(),
+                R16Stack::DE => cpu.get_de(),
+                R16Stack::HL => cpu.get_hl(),
+                R16Stack::AF => cpu.get_af(),
+            };
+            cpu.idle(bus);
             cpu.sp = cpu.sp.wrapping_sub(1);
-            mmu.write_byte(cpu.sp, addr1);
+            cpu.write(bus, cpu.sp, (value >> 8) as u8);
             cpu.sp = cpu.sp.wrapping_sub(1);
-            mmu.write_byte(cpu.sp, addr2);
-            cpu.pc += 1;
-            4
-        },
-        Instruction::Popnn(n) => {
-            let value1 = mmu.read_byte(cpu.sp);
-            cpu.sp = cpu.sp.wrapping_add(1);
-            let value2 = mmu.read_byte(cpu.sp);
-            cpu.sp = cpu.sp.wrapping_add(1);
-            match n {
-                0 => {cpu.a = value2; cpu.f = value1 & 0xF0;},
-                1 => {cpu.b = value2; cpu.c = value1;},
-                2 => {cpu.d = value2; cpu.e = value1;},
-                3 => {cpu.h = value2; cpu.l = value1;},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
+            cpu.write(bus, cpu.sp, value as u8);
+        },
+        Instruction::Pop(rr) => {
+            let value = cpu.pop_stack(bus);
+            let (high, low) = ((value >> 8) as u8, value as u8);
+            match rr {
+                R16Stack::BC => {cpu.b = high; cpu.c = low;},
+                R16Stack::DE => {cpu.d = high; cpu.e = low;},
+                R16Stack::HL => {cpu.h = high; cpu.l = low;},
+                // the low nibble of F doesn't exist
+                R16Stack::AF => {cpu.a = high; cpu.f = low & 0xF0;},
             }
-            cpu.pc += 1;
-            3
-        },
-
-        // 8bit alu
-        Instruction::AddN(n) => {
-            cpu.a = cpu.alu_add(n, false);
-            cpu.pc += 1;
-            1
-        },
-        Instruction::AddHl(n) => {
-            cpu.a = cpu.alu_add(n, false);
-            cpu.pc += 1;
-            2
-        },
-        Instruction::AddD8(n) => {
-            cpu.a = cpu.alu_add(n, false);
-            cpu.pc += 2;
-            2
-        },
-        Instruction::AdcN(n) => {
-            cpu.a = cpu.alu_add(n, true);
-            cpu.pc += 1;
-            1
-        },
-        Instruction::AdcHl(n) => {
-            cpu.a = cpu.alu_add(n, true);
-            cpu.pc += 1;
-            2
-        },
-        Instruction::AdcD8(n) => {
-            cpu.a = cpu.alu_add(n, true);
-            cpu.pc += 2;
-            2
         },
 
-        Instruction::SubN(n) => {
-            cpu.a = cpu.alu_sub(n, false);
-            cpu.pc += 1;
-            1
-        },
-        Instruction::SubHl(n) => {
-            cpu.a = cpu.alu_sub(n, false);
-            cpu.pc += 1;
-            2
-        },
-        Instruction::SubD8(n) => {
-            cpu.a = cpu.alu_sub(n, false);
-            cpu.pc += 2;
-            2
-        },
-
-        Instruction::SbcN(n) => {
-            cpu.a = cpu.alu_sub(n, true);
-            cpu.pc += 1;
-            1
-        },
-        Instruction::SbcHl(n) => {
-            cpu.a = cpu.alu_sub(n, true);
-            cpu.pc += 1;
-            2
-        },
-        Instruction::SbcD8(n) => {
-            cpu.a = cpu.alu_sub(n, true);
-            cpu.pc += 2;
-            2
-        },
-        Instruction::Andn(n) => {
-            let result = cpu.a & *n;
-            cpu.a = result;
-            cpu.set_flag_z(result == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(true);
-            cpu.set_flag_c(false);
-            cpu.pc += 1;
-            1
-        },
-        Instruction::AndHl(n) => {
-            let result = cpu.a & *n;
-            cpu.a = result;
-            cpu.set_flag_z(result == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(true);
-            cpu.set_flag_c(false);
-            cpu.pc += 1;
-            2
-        },
-        Instruction::AndD8(n) => {
-            let result = cpu.a & *n;
-            cpu.a = result;
-            cpu.set_flag_z(result == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(true);
-            cpu.set_flag_c(false);
-            cpu.pc += 2;
-            2
-        },
-        Instruction::OrN(n) => {
-            let result = cpu.a | *n;
-            cpu.set_flag_z(result == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(false);
-            cpu.set_flag_c(false);
-            cpu.a = result;
-            cpu.pc += 1;
-            1
-        },
-        Instruction::OrHl(n) => {
-            let result = cpu.a | *n;
-            cpu.set_flag_z(result == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(false);
-            cpu.set_flag_c(false);
-            cpu.a = result;
-            cpu.pc += 1;
-            2
-        },
-        Instruction::OrD8(n) => {
-            let result = cpu.a | *n;
-            cpu.set_flag_z(result == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(false);
-            cpu.set_flag_c(false);
-            cpu.a = result;
-            cpu.pc += 2;
-            2
+        // 8 bit alu
+        Instruction::Alu(op, r) => {
+            let n = read_r8(cpu, bus, r);
+            alu(cpu, op, n);
         },
+        Instruction::AluImm(op, n) => alu(cpu, op, n),
 
-        Instruction::Xor(r) => {
-            let result = cpu.a ^ *r;
-            cpu.a = result;
+        Instruction::Inc(r) => {
+            let v = read_r8(cpu, bus, r);
+            let result = v.wrapping_add(1);
             cpu.set_flag_z(result == 0);
             cpu.set_flag_n(false);
-            cpu.set_flag_h(false);
-            cpu.set_flag_c(false);
-            cpu.pc += 1;
-            1
-        },
-        Instruction::XorHl(r) => {
-            let result = cpu.a ^ *r;
-            cpu.a = result;
-            cpu.set_flag_z(result == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(false);
-            cpu.set_flag_c(false);
-            cpu.pc += 1;
-            2
+            cpu.set_flag_h(v & 0x0F == 0x0F);
+            write_r8(cpu, bus, r, result);
         },
-        Instruction::XorD8(n) => {
-            let result = cpu.a ^ *n;
-            cpu.a = result;
+        Instruction::Dec(r) => {
+            let v = read_r8(cpu, bus, r);
+            let result = v.wrapping_sub(1);
             cpu.set_flag_z(result == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(false);
-            cpu.set_flag_c(false);
-            cpu.pc += 2;
-            2
-        },
-
-        Instruction::Cp(n) => {
-            cpu.alu_sub(n, false);
-            cpu.pc += 1;
-            1
-        },
-        Instruction::CpHl(n) => {
-            cpu.alu_sub(n, false);
-            cpu.pc += 1;
-            2
-        },
-        Instruction::CpD8(n) => {
-            cpu.alu_sub(n, false);
-            cpu.pc += 2;
-            2
-        },
-
-        Instruction::IncN(v, n) => {
-            let r: u8;
-            match v {
-                0 => {r = cpu.a.wrapping_add(1); cpu.a = r;},
-                1 => {r = cpu.b.wrapping_add(1); cpu.b = r;},
-                2 => {r = cpu.c.wrapping_add(1); cpu.c = r;},
-                3 => {r = cpu.d.wrapping_add(1); cpu.d = r;},
-                4 => {r = cpu.e.wrapping_add(1); cpu.e = r;},
-                5 => {r = cpu.h.wrapping_add(1); cpu.h = r;},
-                6 => {r = cpu.l.wrapping_add(1); cpu.l = r;},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
-            }
-            cpu.set_flag_z(r == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h((n & 0x0F) + 1 > 0x0F);
-            //H - Set if carry from bit 3.
-            cpu.pc += 1;
-            1
-        },
-        Instruction::IncHl(hl) => {
-            let n = mmu.read_byte(*hl);
-            let r = n.wrapping_add(1);
-            mmu.write_byte(*hl, r);
-            
-            cpu.set_flag_z(r == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h((n & 0x0F) + 1 > 0x0F);
-
-            cpu.pc += 1;
-            3
-        },
-        Instruction::DecN(v, n) => {
-            let r: u8;
-            match v {
-                0 => {r = cpu.a.wrapping_sub(1); cpu.a = r;},
-                1 => {r = cpu.b.wrapping_sub(1); cpu.b = r;},
-                2 => {r = cpu.c.wrapping_sub(1); cpu.c = r;},
-                3 => {r = cpu.d.wrapping_sub(1); cpu.d = r;},
-                4 => {r = cpu.e.wrapping_sub(1); cpu.e = r;},
-                5 => {r = cpu.h.wrapping_sub(1); cpu.h = r;},
-                6 => {r = cpu.l.wrapping_sub(1); cpu.l = r;},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
-            }
-            cpu.set_flag_z(r == 0);
             cpu.set_flag_n(true);
-            cpu.set_flag_h((n & 0x0F) == 0);
-
-            cpu.pc += 1;
-            1
+            cpu.set_flag_h(v & 0x0F == 0);
+            write_r8(cpu, bus, r, result);
         },
-        // DEC Hl
-        Instruction::DecHl(hl) => {
-            let n = mmu.read_byte(*hl);
-            let r = n.wrapping_sub(1);
-            mmu.write_byte(*hl, r);
-            
-            cpu.set_flag_z(r == 0);
-            cpu.set_flag_n(true);
-            cpu.set_flag_h((n & 0x0F) == 0);
 
-            cpu.pc += 1;
-            3
+        // 16 bit arithmetic
+        Instruction::Inc16(rr) => {
+            let r = get_r16(cpu, rr).wrapping_add(1);
+            set_r16(cpu, rr, r);
         },
-
-        // 16bit arithmetic
-
-        Instruction::AddHlN(nn) => {
+        Instruction::Dec16(rr) => {
+            let r = get_r16(cpu, rr).wrapping_sub(1);
+            set_r16(cpu, rr, r);
+        },
+        Instruction::AddHl(rr) => {
             let hl = cpu.get_hl();
-            let r = hl.wrapping_add(*nn);
-            cpu.set_flag_h((hl & 0x07FF) + (nn & 0x07FF) > 0x07FF);
+            let nn = get_r16(cpu, rr);
+            cpu.set_flag_h((hl & 0x0FFF) + (nn & 0x0FFF) > 0x0FFF);
             cpu.set_flag_n(false);
             cpu.set_flag_c(hl > (0xFFFF - nn));
-
-            cpu.set_hl(r);
-            cpu.pc += 1;
-            2
-        },
-
-        Instruction::AddSpN(n) => {
-            let sp = cpu.get_sp();
-            cpu.set_flag_z(false);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h((sp & 0x000F) + (*n as u16 & 0x000F) > 0x000F);
-            cpu.set_flag_c((sp & 0x00FF) + (*n as u16 & 0x00FF) > 0x00FF);
-            cpu.sp = cpu.sp.wrapping_add(*n as u16);
-            cpu.pc += 2;
-            4
-        },
-
-        Instruction::IncNN(n, nn) => {
-            let r = nn.wrapping_add(1);
-            match n {
-                0 => {cpu.b = (r >> 8) as u8; cpu.c = (r & 0x00FF) as u8;},
-                1 => {cpu.d = (r >> 8) as u8; cpu.e = (r & 0x00FF) as u8;},
-                2 => {cpu.h = (r >> 8) as u8; cpu.l = (r & 0x00FF) as u8;},
-                3 => {cpu.sp = r;},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
-            }
-            cpu.pc += 1;
-            2
-        },
-        Instruction::DecNN(n, nn) => {
-            let r = nn.wrapping_sub(1);
-            match n {
-                0 => {cpu.b = (r >> 8) as u8; cpu.c = (r & 0x00FF) as u8;},
-                1 => {cpu.d = (r >> 8) as u8; cpu.e = (r & 0x00FF) as u8;},
-                2 => {cpu.h = (r >> 8) as u8; cpu.l = (r & 0x00FF) as u8;},
-                3 => {cpu.sp = r;},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
-            }
-            cpu.pc += 1;
-            2
-        },
-
-        // miscellaneous
-        Instruction::SwapN(n, v) => {
-            match n {
-                0 => {cpu.a = cpu.swap(v);},
-                1 => {cpu.b = cpu.swap(v);},
-                2 => {cpu.c = cpu.swap(v);},
-                3 => {cpu.d = cpu.swap(v);},
-                4 => {cpu.e = cpu.swap(v);},
-                5 => {cpu.h = cpu.swap(v);},
-                6 => {cpu.l = cpu.swap(v);},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
-            }
-            cpu.pc += 2;
-            2
-        },
-        Instruction::SwapHl(v) => {
-            mmu.write_byte(cpu.get_hl(), cpu.swap(v));
-            cpu.pc += 2;
-            4
+            cpu.set_hl(hl.wrapping_add(nn));
         },
+        Instruction::AddSp(e) => cpu.sp = add_sp(cpu, e),
 
         Instruction::Daa => {
             let mut carry = false;
@@ -582,455 +170,236 @@ pub fn executer(cpu: &mut CPU, instruction: &Instruction, mmu: &mut MMU) -> u8 {
             cpu.set_flag_z(cpu.a == 0);
             cpu.set_flag_h(false);
             cpu.set_flag_c(carry);
-            cpu.pc += 1;
-            1
         },
-
-        Instruction::Cpl(a) => {
-            cpu.a = !a; 
-            cpu.set_flag_n(true); 
-            cpu.set_flag_h(true); 
-            cpu.pc += 1;
-            1
+        Instruction::Cpl => {
+            cpu.a = !cpu.a;
+            cpu.set_flag_n(true);
+            cpu.set_flag_h(true);
         },
         Instruction::Ccf => {
             cpu.set_flag_c(!cpu.get_flag_c());
             cpu.set_flag_h(false);
             cpu.set_flag_n(false);
-            cpu.pc += 1;
-            1
         },
         Instruction::Scf => {
-            cpu.set_flag_n(false); 
-            cpu.set_flag_h(false); 
-            cpu.set_flag_c(true); 
-            cpu.pc += 1;
-            1
-        },
-
-        Instruction::Nop => {cpu.pc += 1; 1},
-
-        Instruction::Halt => {
-            // When halt instruction is executed several outcomes might occur:
-                // - When IME = 1:
-                //      In this case, the halt instruction works normally. It will
-                //      stop exection and wait until an interrupt occure (`IF & IE & 0x1F != 0`),
-                //      then it will exit halt mode and execute the interrupt normally.
-                // - When IME = 0:
-                //      - If an interrupt is waiting (`IF & IE & 0x1F != 0`), it
-                //        will enter a `Halt bug` state, in this state, the halt
-                //        mode is not entered and the PC register is not incremented
-                //        on the next instruction.
-                //      - If an interrupt is not waiting (`IF & IE & 0x1F == 0`),
-                //        the cpu will enter halt mode normally and wait for an interrupt
-                //        to occur like in *IME = 1* case but if an interrupt is
-                //        requested it will not just to the interrupt vector
-                //        and it will continue executing normally, we can think
-                //        of it as being stuck in a large array of NOP instructions
-                //        until an interrupt is requested.
-            cpu.set_halted(true);
-            cpu.pc += 1;
-            1
-        },
-        Instruction::Stop => {
-            cpu.pc += 2;
-            1
-        },
-
-        Instruction::Di => {cpu.ime = false; cpu.pc += 1; 1},
-
-        Instruction::Ei => {cpu.ei = true; cpu.pc += 1; 1},
-
-
-        // rotate and shift
-        Instruction::Rlca(a) => {
-            let c = a & 0x80 == 0x80;
-            
-            cpu.a = (a << 1) | (if c {1} else {0});
-
-            cpu.set_flag_z(false);
             cpu.set_flag_n(false);
             cpu.set_flag_h(false);
-            cpu.set_flag_c(c);
-            cpu.pc += 1;
-            1
+            cpu.set_flag_c(true);
         },
-        Instruction::Rla(a) => {
-            let c = a & 0x80 == 0x80;
-            
-            cpu.a = (a << 1) | (if cpu.get_flag_c() {1} else {0});
 
+        // rotate and shift, the A ones always clear Z
+        Instruction::Rlca => {
+            cpu.a = shift(cpu, Shift::Rlc, cpu.a);
             cpu.set_flag_z(false);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(false);
-            cpu.set_flag_c(c);
-            cpu.pc += 1;
-            1
         },
-        Instruction::Rrca(a) => {
-            let c = a & 0x01 == 0x01;
-            
-            cpu.a = (a >> 1) | (if c {0x80} else {0});
-
+        Instruction::Rrca => {
+            cpu.a = shift(cpu, Shift::Rrc, cpu.a);
             cpu.set_flag_z(false);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(false);
-            cpu.set_flag_c(c);
-            cpu.pc += 1;
-            1
         },
-        Instruction::Rra(a) => {
-            let c = a & 0x01 == 0x01;
-            
-            cpu.a = (a >> 1) | (if cpu.get_flag_c() {0x80} else {0});
-
+        Instruction::Rla => {
+            cpu.a = shift(cpu, Shift::Rl, cpu.a);
             cpu.set_flag_z(false);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(false);
-            cpu.set_flag_c(c);
-            cpu.pc += 1;
-            1
         },
-
-        Instruction::RlcN(n, v) => {
-            let c = v & 0x80 == 0x80;
-            let r = (v << 1) | (if c {1} else {0});
-
-            cpu.set_flag_z(r == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(false);
-            cpu.set_flag_c(c);
-            cpu.pc += 2;
-            match n {
-                0 => {cpu.a = r; 2},
-                1 => {cpu.b = r; 2},
-                2 => {cpu.c = r; 2},
-                3 => {cpu.d = r; 2},
-                4 => {cpu.e = r; 2},
-                5 => {cpu.h = r; 2},
-                6 => {cpu.l = r; 2},
-                7 => {mmu.write_byte(cpu.get_hl(), r); 4},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
-            }
+        Instruction::Rra => {
+            cpu.a = shift(cpu, Shift::Rr, cpu.a);
+            cpu.set_flag_z(false);
         },
-
-        Instruction::RlN(n, v) => {
-            let c = v & 0x80 == 0x80;
-            let r = (v << 1) | (if cpu.get_flag_c() {1} else {0});
-
-            cpu.set_flag_z(r == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(false);
-            cpu.set_flag_c(c);
-            cpu.pc += 2;
-            match n {
-                0 => {cpu.a = r; 2},
-                1 => {cpu.b = r; 2},
-                2 => {cpu.c = r; 2},
-                3 => {cpu.d = r; 2},
-                4 => {cpu.e = r; 2},
-                5 => {cpu.h = r; 2},
-                6 => {cpu.l = r; 2},
-                7 => {mmu.write_byte(cpu.get_hl(), r); 4},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
-            }
+        Instruction::Shift(op, r) => {
+            let v = read_r8(cpu, bus, r);
+            let result = shift(cpu, op, v);
+            write_r8(cpu, bus, r, result);
         },
 
-        Instruction::RrcN(n, v) => {
-            let c = v & 0x01 == 0x01;
-            
-            let r = (v >> 1) | (if c {0x80} else {0});
-
-            cpu.set_flag_z(r == 0);
+        // bit opcodes
+        Instruction::Bit(bit, r) => {
+            // only reads (HL), no write back like RES/SET
+            let v = read_r8(cpu, bus, r);
+            cpu.set_flag_z(v & (1 << bit) == 0);
             cpu.set_flag_n(false);
-            cpu.set_flag_h(false);
-            cpu.set_flag_c(c);
-            cpu.pc += 2;
-            match n {
-                0 => {cpu.a = r; 2},
-                1 => {cpu.b = r; 2},
-                2 => {cpu.c = r; 2},
-                3 => {cpu.d = r; 2},
-                4 => {cpu.e = r; 2},
-                5 => {cpu.h = r; 2},
-                6 => {cpu.l = r; 2},
-                7 => {mmu.write_byte(cpu.get_hl(), r); 4},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
-            }
+            cpu.set_flag_h(true);
         },
-
-        Instruction::RrN(n, v) => {
-            let c = v & 0x01 == 0x01;
-            
-            let r = (v >> 1) | (if cpu.get_flag_c() {0x80} else {0});
-
-            cpu.set_flag_z(r == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(false);
-            cpu.set_flag_c(c);
-            cpu.pc += 2;
-            match n {
-                0 => {cpu.a = r; 2},
-                1 => {cpu.b = r; 2},
-                2 => {cpu.c = r; 2},
-                3 => {cpu.d = r; 2},
-                4 => {cpu.e = r; 2},
-                5 => {cpu.h = r; 2},
-                6 => {cpu.l = r; 2},
-                7 => {mmu.write_byte(cpu.get_hl(), r); 4},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
-            }
+        Instruction::Res(bit, r) => {
+            let v = read_r8(cpu, bus, r);
+            write_r8(cpu, bus, r, v & !(1 << bit));
         },
-        
-        Instruction::SlaN(n, v) => {
-            let c = v & 0x80 == 0x80;
-            let r = v << 1;
-            cpu.set_flag_z(r == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(false);
-            cpu.set_flag_c(c);
-            cpu.pc += 2;
-            match n {
-                0 => {cpu.a = r; 2},
-                1 => {cpu.b = r; 2},
-                2 => {cpu.c = r; 2},
-                3 => {cpu.d = r; 2},
-                4 => {cpu.e = r; 2},
-                5 => {cpu.h = r; 2},
-                6 => {cpu.l = r; 2},
-                7 => {mmu.write_byte(cpu.get_hl(), r); 4},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
-            }
-        },
-        Instruction::SraN(n, v) => {
-            let c = v & 0x01 == 0x01;
-            let r = v >> 1 | (v & 0x80);
-            cpu.set_flag_z(r == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(false);
-            cpu.set_flag_c(c);
-            cpu.pc += 2;
-            match n {
-                0 => {cpu.a = r; 2},
-                1 => {cpu.b = r; 2},
-                2 => {cpu.c = r; 2},
-                3 => {cpu.d = r; 2},
-                4 => {cpu.e = r; 2},
-                5 => {cpu.h = r; 2},
-                6 => {cpu.l = r; 2},
-                7 => {mmu.write_byte(cpu.get_hl(), r); 4},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
-            }
+        Instruction::Set(bit, r) => {
+            let v = read_r8(cpu, bus, r);
+            write_r8(cpu, bus, r, v | (1 << bit));
         },
 
-        Instruction::SrlN(n, v) => {
-            let c = v & 0x01 == 0x01;
-            let r = v >> 1;
-            cpu.set_flag_z(r == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(false);
-            cpu.set_flag_c(c);
-            cpu.pc += 2;
-            match n {
-                0 => {cpu.a = r; 2},
-                1 => {cpu.b = r; 2},
-                2 => {cpu.c = r; 2},
-                3 => {cpu.d = r; 2},
-                4 => {cpu.e = r; 2},
-                5 => {cpu.h = r; 2},
-                6 => {cpu.l = r; 2},
-                7 => {mmu.write_byte(cpu.get_hl(), r); 4},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
+        // jumps
+        Instruction::Jp(nn) => cpu.pc = nn,
+        Instruction::JpIf(cc, nn) => {
+            if condition(cpu, cc) {
+                cpu.pc = nn;
+                return true;
             }
         },
-
-        Instruction::BitbR(pos, r) => {
-            let result: u8 = r & pos;
-            cpu.set_flag_z(result == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(true);
-            cpu.pc += 2;
-            2
-        },
-        Instruction::BitbHl(pos, r) => {
-            let result: u8 = r & pos;
-            cpu.set_flag_z(result == 0);
-            cpu.set_flag_n(false);
-            cpu.set_flag_h(true);
-            cpu.pc += 2;
-            4
-        },
-
-        Instruction::SetbR(pos, r, n) => {
-            cpu.pc += 2;
-            match n {
-                0 => {cpu.a = r | pos; 2},
-                1 => {cpu.b = r | pos; 2},
-                2 => {cpu.c = r | pos; 2},
-                3 => {cpu.d = r | pos; 2},
-                4 => {cpu.e = r | pos; 2},
-                5 => {cpu.h = r | pos; 2},
-                6 => {cpu.l = r | pos; 2},
-                7 => {mmu.write_byte(cpu.get_hl(), r | pos); 4},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
+        Instruction::JpHl => cpu.pc = cpu.get_hl(),
+        Instruction::Jr(e) => cpu.pc = cpu.pc.wrapping_add(e as u16),
+        Instruction::JrIf(cc, e) => {
+            if condition(cpu, cc) {
+                cpu.pc = cpu.pc.wrapping_add(e as u16);
+                return true;
             }
         },
 
-        Instruction::ResbR(pos, r, n) => {
-            cpu.pc += 2;
-            match n {
-                0 => {cpu.a = r & !pos; 2},
-                1 => {cpu.b = r & ! pos; 2},
-                2 => {cpu.c = r & ! pos; 2},
-                3 => {cpu.d = r & ! pos; 2},
-                4 => {cpu.e = r & ! pos; 2},
-                5 => {cpu.h = r & ! pos; 2},
-                6 => {cpu.l = r & ! pos; 2},
-                7 => {mmu.write_byte(cpu.get_hl(), r & ! pos); 4},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
-            }
+        // calls, restarts and returns
+        Instruction::Call(nn) => {
+            cpu.push_stack(bus);
+            cpu.pc = nn;
         },
-
-        Instruction::Jpnn(nn) => {
-            cpu.pc = *nn;
-            //cpu.pc += 3;
-            4
+        Instruction::CallIf(cc, nn) => {
+            if condition(cpu, cc) {
+                cpu.push_stack(bus);
+                cpu.pc = nn;
+                return true;
+            }
         },
-
-        Instruction::Jpcc(nn, m) => {
-            //cpu.pc += 3;
-            match m {
-                0 => {if !cpu.get_flag_z() {cpu.pc = *nn; 4} else {cpu.pc += 3; 3}},
-                1 => {if cpu.get_flag_z() {cpu.pc = *nn; 4} else {cpu.pc += 3; 3}},
-                2 => {if !cpu.get_flag_c() {cpu.pc = *nn; 4} else {cpu.pc += 3; 3}},
-                3 => {if cpu.get_flag_c() {cpu.pc = *nn; 4} else {cpu.pc += 3; 3}},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    m, instruction,
-                ),
+        Instruction::Rst(vector) => {
+            cpu.push_stack(bus);
+            cpu.pc = vector as u16;
+        },
+        Instruction::Ret => cpu.pc = cpu.pop_stack(bus),
+        Instruction::RetIf(cc) => {
+            // checking the condition takes a cycle, taken or not
+            cpu.idle(bus);
+            if condition(cpu, cc) {
+                cpu.pc = cpu.pop_stack(bus);
+                return true;
             }
         },
-
-        Instruction::JpHl(hl) => {
-            cpu.pc += 1;
-            cpu.pc = *hl;
-            1
+        Instruction::Reti => {
+            cpu.pc = cpu.pop_stack(bus);
+            cpu.ime = true;
         },
+    }
+    false
+}
 
+// (HL) costs a memory read
+fn read_r8<B: Bus>(cpu: &mut CPU, bus: &mut B, r: R8) -> u8 {
+    match r {
+        R8::B => cpu.b,
+        R8::C => cpu.c,
+        R8::D => cpu.d,
+        R8::E => cpu.e,
+        R8::H => cpu.h,
+        R8::L => cpu.l,
+        R8::HlIndirect => cpu.read(bus, cpu.get_hl()),
+        R8::A => cpu.a,
+    }
+}
 
-        Instruction::JrN(n) => {
-            cpu.pc = cpu.pc.wrapping_add(*n as u16);
-            cpu.pc += 2;
-            3
-        },
-
-        Instruction::Jrcc(n, m) => {
-            cpu.pc += 2;
-            match m {
-                0 => {if !cpu.get_flag_z() {cpu.pc = cpu.pc.wrapping_add(*n as u16); 3} else {2}},
-                1 => {if cpu.get_flag_z() {cpu.pc = cpu.pc.wrapping_add(*n as u16); 3} else {2}},
-                2 => {if !cpu.get_flag_c() {cpu.pc = cpu.pc.wrapping_add(*n as u16); 3} else {2}},
-                3 => {if cpu.get_flag_c() {cpu.pc = cpu.pc.wrapping_add(*n as u16); 3} else {2}},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    m, instruction,
-                ),
-            }
-        },
+fn write_r8<B: Bus>(cpu: &mut CPU, bus: &mut B, r: R8, value: u8) {
+    match r {
+        R8::B => cpu.b = value,
+        R8::C => cpu.c = value,
+        R8::D => cpu.d = value,
+        R8::E => cpu.e = value,
+        R8::H => cpu.h = value,
+        R8::L => cpu.l = value,
+        R8::HlIndirect => cpu.write(bus, cpu.get_hl(), value),
+        R8::A => cpu.a = value,
+    }
+}
 
-        Instruction::Callnn(d16) => {
-            cpu.pc += 3;
-            cpu.push_stack(mmu);
-            cpu.pc = *d16;
+fn get_r16(cpu: &CPU, rr: R16) -> u16 {
+    match rr {
+        R16::BC => cpu.get_bc(),
+        R16::DE => cpu.get_de(),
+        R16::HL => cpu.get_hl(),
+        R16::SP => cpu.sp,
+    }
+}
 
-            6
-        },
+fn set_r16(cpu: &mut CPU, rr: R16, value: u16) {
+    let (high, low) = ((value >> 8) as u8, value as u8);
+    match rr {
+        R16::BC => {cpu.b = high; cpu.c = low;},
+        R16::DE => {cpu.d = high; cpu.e = low;},
+        R16::HL => {cpu.h = high; cpu.l = low;},
+        R16::SP => cpu.sp = value,
+    }
+}
 
-        Instruction::Callcc(d16, n) => {
-            cpu.pc += 3;
-            let v;
-            match n {
-                0 => {if !cpu.get_flag_z() {cpu.push_stack(mmu); cpu.pc = *d16; v = 6;} else {v = 3;}},
-                1 => {if cpu.get_flag_z() {cpu.push_stack(mmu); cpu.pc = *d16; v = 6;} else {v = 3;}},
-                2 => {if !cpu.get_flag_c() {cpu.push_stack(mmu); cpu.pc = *d16; v = 6;} else {v = 3;}},
-                3 => {if cpu.get_flag_c() {cpu.push_stack(mmu); cpu.pc = *d16; v = 6;} else {v = 3;}},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
-            }
-            v
+// HL+ and HL- move HL once the address is out
+fn indirect_address(cpu: &mut CPU, rr: R16Indirect) -> u16 {
+    match rr {
+        R16Indirect::BC => cpu.get_bc(),
+        R16Indirect::DE => cpu.get_de(),
+        R16Indirect::HlInc => {
+            let hl = cpu.get_hl();
+            cpu.set_hl(hl.wrapping_add(1));
+            hl
         },
-
-        Instruction::Rst(n) => {
-            cpu.pc += 1;
-            cpu.push_stack(mmu);
-            cpu.pc = *n as u16;
-            4
+        R16Indirect::HlDec => {
+            let hl = cpu.get_hl();
+            cpu.set_hl(hl.wrapping_sub(1));
+            hl
         },
+    }
+}
 
-        Instruction::Ret => {
-            //println!("CPU STATE: {:?}", cpu);
-            //println!("pop: {:#X}", cpu.pop_stack(mmu));
-            cpu.pc += 1;
-            cpu.pc = cpu.pop_stack(mmu);
-            4
-        },
+fn condition(cpu: &CPU, cc: Condition) -> bool {
+    match cc {
+        Condition::NZ => !cpu.get_flag_z(),
+        Condition::Z => cpu.get_flag_z(),
+        Condition::NC => !cpu.get_flag_c(),
+        Condition::C => cpu.get_flag_c(),
+    }
+}
 
-        Instruction::Retcc(n) => {
-            cpu.pc += 1;
-            let v;
-            match n {
-                0 => {if !cpu.get_flag_z() {cpu.pc = cpu.pop_stack(mmu); v = 5;} else {v = 2;}},
-                1 => {if cpu.get_flag_z() {cpu.pc = cpu.pop_stack(mmu); v = 5;} else {v = 2;}},
-                2 => {if !cpu.get_flag_c() {cpu.pc = cpu.pop_stack(mmu); v = 5;} else {v = 2;}},
-                3 => {if cpu.get_flag_c() {cpu.pc = cpu.pop_stack(mmu); v = 5;} else {v = 2;}},
-                _ => panic!(
-                    "Can't find {:?} for instruction {:#?}",
-                    n, instruction,
-                ),
-            }
-            v
+fn alu(cpu: &mut CPU, op: Alu, n: u8) {
+    match op {
+        Alu::Add => cpu.a = cpu.alu_add(&n, false),
+        Alu::Adc => cpu.a = cpu.alu_add(&n, true),
+        Alu::Sub => cpu.a = cpu.alu_sub(&n, false),
+        Alu::Sbc => cpu.a = cpu.alu_sub(&n, true),
+        // CP is a SUB that only keeps the flags
+        Alu::Cp => {cpu.alu_sub(&n, false);},
+        Alu::And | Alu::Xor | Alu::Or => {
+            cpu.a = match op {
+                Alu::And => cpu.a & n,
+                Alu::Xor => cpu.a ^ n,
+                _ => cpu.a | n,
+            };
+            cpu.set_flag_z(cpu.a == 0);
+            cpu.set_flag_n(false);
+            cpu.set_flag_h(op == Alu::And);
+            cpu.set_flag_c(false);
         },
+    }
+}
 
-        Instruction::Reti => {
-            cpu.pc += 1;
-            cpu.pc = cpu.pop_stack(mmu);
-            cpu.ime = true;
-            4
-        },
+// SP plus a signed offset, flags from the unsigned add of the low byte
+fn add_sp(cpu: &mut CPU, e: i8) -> u16 {
+    let sp = cpu.sp;
+    let n = e as u16;
+    cpu.set_flag_z(false);
+    cpu.set_flag_n(false);
+    cpu.set_flag_h((sp & 0x000F) + (n & 0x000F) > 0x000F);
+    cpu.set_flag_c((sp & 0x00FF) + (n & 0x00FF) > 0x00FF);
+    sp.wrapping_add(n)
+}
 
-        _ => panic!(
-            "Unreconized instruction {:?} on pc {:#X}\n CPU STATE: {:?}",
-            instruction, cpu.pc, cpu
-        ),
+fn shift(cpu: &mut CPU, op: Shift, v: u8) -> u8 {
+    if op == Shift::Swap {
+        return cpu.swap(&v);
     }
+    let carry_in = cpu.get_flag_c() as u8;
+    let (result, carry) = match op {
+        Shift::Rlc => (v.rotate_left(1), v & 0x80 != 0),
+        Shift::Rrc => (v.rotate_right(1), v & 0x01 != 0),
+        Shift::Rl => ((v << 1) | carry_in, v & 0x80 != 0),
+        Shift::Rr => ((v >> 1) | (carry_in << 7), v & 0x01 != 0),
+        Shift::Sla => (v << 1, v & 0x80 != 0),
+        Shift::Sra => ((v >> 1) | (v & 0x80), v & 0x01 != 0),
+        _ => (v >> 1, v & 0x01 != 0),
+    };
+    cpu.set_flag_z(result == 0);
+    cpu.set_flag_n(false);
+    cpu.set_flag_h(false);
+    cpu.set_flag_c(carry);
+    result
 }
-
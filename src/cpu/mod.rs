@@ -1,11 +1,13 @@
 pub use crate::mmu::{MMU, interrupts, dma, DmaTransfer};
 pub use crate::instruction::Instruction;
 pub use crate::dbg::DBG;
+pub use crate::bus::Bus;
 
 use std::fmt;
 
 pub mod execute;
 pub mod opcodes;
+pub mod disasm;
 //mod executor;
 
 
@@ -21,10 +23,50 @@ pub struct CPU {
     sp: u16, // Stack Pointer
     pc: u16, // Program Counter
     halted: bool,
+    halt_bug: bool, // next opcode fetch doesn't move PC
+    locked: bool, // ran an illegal opcode, nothing gets it going again
+    event: Option<CpuEvent>,
     ime: bool, // Interrupt Master Enable Flag
     ei: bool, // enable_interrupt_next
     ticks: usize,
+    breakpoint: bool, // ran a LD B,B
+    instruction_cycles: u8, // M-cycles the running instruction has ticked so far
 }
+// things the CPU ran into that the host should hear about, see take_event
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CpuEvent {
+    // the CPU is locked up for good, it's up to the host to carry on or not
+    IllegalOpcode {opcode: u8, pc: u16},
+}
+
+impl fmt::Display for CpuEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuEvent::IllegalOpcode {opcode, pc} => {
+                write!(f, "illegal opcode {:#04X} at {:#06X}, the CPU is locked up", opcode, pc)
+            }
+        }
+    }
+}
+
+// everything a test needs to set up or check a CPU
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Registers {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    pub ime: bool,
+    // EI ran, IME goes on after the next instruction
+    pub ei: bool,
+}
+
 impl fmt::Debug for CPU {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -63,14 +105,86 @@ impl Default for CPU {
             sp: 0xFFFE, // Stack Pointer
             pc: 0x0100, // Program Counter
             halted: false,
+            halt_bug: false,
+            locked: false,
+            event: None,
             ime: false,
             ei: false,
             ticks: 0,
+            breakpoint: false,
+            instruction_cycles: 0,
         }
     }
 }
 
 impl CPU {
+    // register values the CGB boot rom leaves behind. Games look at A == 0x11
+    // to know they are running on a CGB.
+    pub fn cgb_default() -> CPU {
+        CPU {
+            a: 0x11,
+            f: 0x80,
+            b: 0x00,
+            c: 0x00,
+            d: 0xFF,
+            e: 0x56,
+            h: 0x00,
+            l: 0x0D,
+            ..CPU::default()
+        }
+    }
+
+    // what the SGB leaves behind, A == 0x01 like on a DMG
+    pub fn sgb_default() -> CPU {
+        CPU {
+            a: 0x01,
+            f: 0x00,
+            b: 0x00,
+            c: 0x14,
+            d: 0x00,
+            e: 0x00,
+            h: 0xC0,
+            l: 0x60,
+            ..CPU::default()
+        }
+    }
+
+    pub fn from_registers(registers: Registers) -> CPU {
+        CPU {
+            a: registers.a,
+            // the low nibble of F doesn't exist
+            f: registers.f & 0xF0,
+            b: registers.b,
+            c: registers.c,
+            d: registers.d,
+            e: registers.e,
+            h: registers.h,
+            l: registers.l,
+            sp: registers.sp,
+            pc: registers.pc,
+            ime: registers.ime,
+            ei: registers.ei,
+            ..CPU::new()
+        }
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers {
+            a: self.a,
+            f: self.f,
+            b: self.b,
+            c: self.c,
+            d: self.d,
+            e: self.e,
+            h: self.h,
+            l: self.l,
+            sp: self.sp,
+            pc: self.pc,
+            ime: self.ime,
+            ei: self.ei,
+        }
+    }
+
     pub fn new() -> CPU {
         CPU {
             a: 0,
@@ -84,9 +198,14 @@ impl CPU {
             sp: 0, // Stack Pointer
             pc: 0, // Program Counter
             halted: false,
+            halt_bug: false,
+            locked: false,
+            event: None,
             ime: false,
             ei: false,
             ticks: 0,
+            breakpoint: false,
+            instruction_cycles: 0,
         }
     }
 
@@ -118,32 +237,32 @@ impl CPU {
         self.f = if bit {self.f | 0b00010000} else {self.f & 0b11101111};
     }
 
-    pub fn get_a(&mut self) -> u8 {
+    pub fn get_a(&self) -> u8 {
         self.a
     }
-    pub fn get_b(&mut self) -> u8 {
+    pub fn get_b(&self) -> u8 {
         self.b
     }
-    pub fn get_c(&mut self) -> u8 {
+    pub fn get_c(&self) -> u8 {
         self.c
     }
-    pub fn get_d(&mut self) -> u8 {
+    pub fn get_d(&self) -> u8 {
         self.d
     }
-    pub fn get_e(&mut self) -> u8 {
+    pub fn get_e(&self) -> u8 {
         self.e
     }
-    pub fn get_h(&mut self) -> u8 {
+    pub fn get_h(&self) -> u8 {
         self.h
     }
-    pub fn get_l(&mut self) -> u8 {
+    pub fn get_l(&self) -> u8 {
         self.l
     }
 
-    pub fn get_pc(&mut self) -> u16 {
+    pub fn get_pc(&self) -> u16 {
         self.pc
     }
-    pub fn get_sp(&mut self) -> u16 {
+    pub fn get_sp(&self) -> u16 {
         self.sp
     }
 
@@ -154,19 +273,19 @@ impl CPU {
         self.sp = sp;
     }
 
-    pub fn get_af(&mut self) -> u16 {
+    pub fn get_af(&self) -> u16 {
         let a: u16 = (self.a as u16) << 8;
         a | (self.f as u16)
     }
-    pub fn get_bc(&mut self) -> u16 {
+    pub fn get_bc(&self) -> u16 {
         let b: u16 = (self.b as u16) << 8;
         b | (self.c as u16)
     }
-    pub fn get_de(&mut self) -> u16 {
+    pub fn get_de(&self) -> u16 {
         let d: u16 = (self.d as u16) << 8;
         d | (self.e as u16)
     }
-    pub fn get_hl(&mut self) -> u16 {
+    pub fn get_hl(&self) -> u16 {
         let h: u16 = (self.h as u16) << 8;
         h | (self.l as u16)
     }
@@ -176,18 +295,33 @@ impl CPU {
         self.l = (hl & 0x00FF) as u8;
     }
 
-    pub fn get_halted(&mut self) -> bool {
+    pub fn get_halted(&self) -> bool {
         self.halted
     }
     pub fn set_halted(&mut self, b: bool){
         self.halted = b;
     }
 
+    pub fn get_ime(&self) -> bool {
+        self.ime
+    }
     pub fn set_ime(&mut self, b: bool){
         self.ime = b;
     }
 
-    pub fn get_ticks(&mut self) -> usize {
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
+    pub fn take_event(&mut self) -> Option<CpuEvent> {
+        self.event.take()
+    }
+
+    // true once after every LD B,B
+    pub fn take_breakpoint(&mut self) -> bool {
+        std::mem::take(&mut self.breakpoint)
+    }
+
+    pub fn get_ticks(&self) -> usize {
         self.ticks
     }
 
@@ -218,81 +352,122 @@ impl CPU {
         (n << 4) | (n >> 4)
     }
 
-    pub fn push_stack(&mut self, mmu: &mut MMU) {
+    /*
+        Every memory access gets an M-cycle of its own, and the timer, PPU
+        and DMA catch up before it happens. Cycles that don't touch memory
+        are ticked with idle where their position matters (before a push),
+        run_instruction ticks whatever is left at the end.
+    */
+    pub fn read<B: Bus>(&mut self, bus: &mut B, address: u16) -> u8 {
+        self.idle(bus);
+        bus.read(address)
+    }
+    pub fn write<B: Bus>(&mut self, bus: &mut B, address: u16, value: u8) {
+        self.idle(bus);
+        bus.write(address, value);
+    }
+    pub fn idle<B: Bus>(&mut self, bus: &mut B) {
+        self.cycle(bus, 1);
+        self.instruction_cycles += 1;
+    }
+
+    // the cycle before the writes is SP going down
+    pub fn push_stack<B: Bus>(&mut self, bus: &mut B) {
         let addr1: u8 = ((self.pc & 0xFF00) >> 8) as u8;
         let addr2: u8 = (self.pc & 0x00FF) as u8;
+        self.idle(bus);
         self.sp = self.sp.wrapping_sub(1);
-        mmu.write_byte(self.sp, addr1);
+        self.write(bus, self.sp, addr1);
         self.sp = self.sp.wrapping_sub(1);
-        mmu.write_byte(self.sp, addr2);
+        self.write(bus, self.sp, addr2);
     }
 
-    pub fn pop_stack(&mut self, mmu:&mut MMU) -> u16 {
-        let low = mmu.read_byte(self.sp) as u16;
+    pub fn pop_stack<B: Bus>(&mut self, bus: &mut B) -> u16 {
+        let low = self.read(bus, self.sp) as u16;
         self.sp = self.sp.wrapping_add(1);
-        let high = mmu.read_byte(self.sp) as u16;
+        let high = self.read(bus, self.sp) as u16;
         self.sp = self.sp.wrapping_add(1);
         (high << 8) | low
     }
 
-    // decode instructions
-    pub fn decode(&mut self, byte: u8, mmu: &MMU) -> Instruction {
-        let n1 = mmu.read_byte(self.pc + 1) as u16;
-        let n2 = mmu.read_byte(self.pc + 2) as u16;
-        // d16 is nn
-        let d16: u16 = (n2 << 8) | n1;
-        //println!("d16: {:#X} or n1 {:#X} and n2 {:#X}", d16, n1, n2);
-
-        opcodes::decoder(self, mmu, byte, n1, d16)
-    }
-
-    pub fn cb_decode(&mut self, byte: u8, mmu: &MMU) -> Instruction {
-        opcodes::cb_decoder(self, byte, mmu)
+    // the byte at PC, which moves past it unless the halt bug holds it back
+    // for this one fetch
+    fn fetch<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        let byte = self.read(bus, self.pc);
+        if self.halt_bug {
+            self.halt_bug = false;
+        } else {
+            self.pc = self.pc.wrapping_add(1);
+        }
+        byte
     }
 
-    pub fn excute(&mut self, instruction: &Instruction, mmu: &mut MMU) -> u8{
-        execute::executer(self, instruction, mmu)
+    // reads the opcode and only the operand bytes it has, the bus log
+    // should look like the real thing. Decoding itself doesn't touch the
+    // CPU or the bus, see opcodes::decode.
+    pub fn fetch_instruction<B: Bus>(&mut self, bus: &mut B) -> (u8, Instruction) {
+        let byte = self.fetch(bus);
+        let length = opcodes::instruction_length(byte);
+        let n1 = if length > 1 {self.fetch(bus) as u16} else {0};
+        let n2 = if length > 2 {self.fetch(bus) as u16} else {0};
+        (byte, opcodes::decode(byte, (n2 << 8) | n1))
     }
 
-    pub fn run_instruction(&mut self, mmu: &mut MMU) -> u8 {
-        let byte = mmu.read_byte(self.pc);
-
-        let instruction = self.decode(byte, mmu);
-        //190000
-        //0xFE00 < self.get_hl() && self.get_hl() <= 0xFE9F
-        //
-        if self.ticks >= 0x6B3F0 && self.ticks < 0x6C3F0 {
+    // runs one instruction with all its M-cycles ticked, returns how many
+    pub fn run_instruction<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        self.instruction_cycles = 0;
+        let (byte, instruction) = self.fetch_instruction(bus);
 
-            //println!("{:?} {:?} ({:#X} {:#X}) \n", self, instruction, byte, mmu.read_byte(self.pc + 1));
-            /* println!(
-                "Counter:{:#X} IE:{:#X} IF:{:#X} Interrupt:{} \n", 
-                mmu.read_byte(0xFF04), mmu.read_byte(0xFFFF), mmu.read_byte(0xFF0F), 
-                mmu.interrupts.peek_highest_interrupt().is_some()); */
+        // LD B,B does nothing, so test ROMs (Mooneye's among them) use it
+        // as a breakpoint
+        if byte == 0x40 {
+            self.breakpoint = true;
         }
 
-        let cycle = self.excute(&instruction, mmu);
-        if self.ticks < (4*100) {
-            //println!("byte:{:#X}, ticks:{}:{}, cycle:{}", byte, self.ticks, self.ticks/4, cycle);
+        let taken = execute::execute(self, instruction, bus);
+        if self.locked {
+            self.event = Some(CpuEvent::IllegalOpcode {opcode: byte, pc: self.pc});
         }
-        
+        let cycle = if taken {instruction.cycles_taken()} else {instruction.cycles()};
+        debug_assert!(self.instruction_cycles <= cycle,
+            "{:?} ticked {} M-cycles, the table says {}", instruction, self.instruction_cycles, cycle);
+        // the internal cycles at the end
+        self.cycle(bus, cycle.saturating_sub(self.instruction_cycles));
         cycle
     }
 
     pub fn do_cycle(&mut self, mmu: &mut MMU, dbg: &mut DBG) {
+        if !self.halted && !self.locked && !mmu.hdma.cpu_stalled() {
+            dbg.dbg_update(mmu);
+            dbg.dbg_print();
+        }
+        self.step(mmu);
+        if let Some(event) = self.take_event() {
+            dbg.raise(event);
+        }
+    }
+
+    // one instruction, or one M-cycle of being halted or stalled, then any
+    // interrupt that's due
+    pub fn step<B: Bus>(&mut self, bus: &mut B) {
+        // the CPU does nothing while VRAM DMA is copying a block, or ever
+        // again after an illegal opcode
+        if self.locked || bus.cpu_stalled() {
+            self.cycle(bus, 1);
+            return;
+        }
         if self.halted {
-            self.cycle(mmu, 1);
-            if mmu.interrupts.read_requested() != 0 {
+            self.cycle(bus, 1);
+            // wakes up even with IME off, it just carries on without the
+            // interrupt being served
+            if bus.pending_interrupt().is_some() {
                 self.halted = false;
             }
         } else {
-            dbg.dbg_update(mmu);
-            dbg.dbg_print();
-            let m = self.run_instruction(mmu);
-            //println!("c:{}", m);
-            self.cycle(mmu, m);
+            self.run_instruction(bus);
         }
-        if self.ime && mmu.interrupts.peek_highest_interrupt().is_some() {
-            self.interrupt_handle(mmu);
+        if self.ime && !self.locked && bus.pending_interrupt().is_some() {
+            self.interrupt_handle(bus);
         }
         if self.ei {
             self.ime = true;
@@ -300,39 +475,36 @@ impl CPU {
         }
         
     }
-    pub fn cycle(&mut self, mmu: &mut MMU, cycles: u8) {
-        //let n = cycles * 4;
-        for cycle in 0..cycles {
-            for tick in 0..4 {
-                self.ticks += 1;
-                mmu.timer.ticks(&mut mmu.interrupts);
-                mmu.ppu.ppu_ticks(&mut mmu.interrupts)
-            }
-            mmu.dma_tick();
-        }
-    }
-    pub fn interrupt_handle(&mut self, mmu:&mut MMU) {
-        // Push PC part 1
-        // trigger write oam bug because of the increment
-        
-        //bus.trigger_write_oam_bug(self.reg_sp);
+    pub fn cycle<B: Bus>(&mut self, bus: &mut B, cycles: u8) {
+        self.ticks += cycles as usize * 4;
+        bus.tick(cycles);
+    }
+    /*
+        Five M-cycles: two internal ones, the high byte of PC pushed, the
+        low byte pushed and the jump. Which interrupt gets served is only
+        decided after the high byte went out, so when that push lands on
+        IE (SP was 0x0000) it can pick another one or cancel the dispatch,
+        which leaves PC at 0x0000.
+    */
+    pub fn interrupt_handle<B: Bus>(&mut self, bus: &mut B) {
+        self.instruction_cycles = 0;
+        self.ime = false;
+        self.halted = false;
         let pc = self.pc;
 
+        self.idle(bus);
+        self.idle(bus);
         self.sp = self.sp.wrapping_sub(1);
-        //cpu.set_sp(sp);
-        mmu.write_byte(self.sp, (pc >> 8) as u8);
-
+        self.write(bus, self.sp, (pc >> 8) as u8);
 
-        if mmu.interrupts.peek_highest_interrupt().is_some() {
-            let highest_interrupt = mmu.interrupts.get_highest_interrupt(self).unwrap();
-            self.pc = mmu.interrupts.interrupt_addresses(highest_interrupt);
-        } else {
-            // Interrupt cancelled. Why would this happen??
-            self.pc = 0;
+        let interrupt = bus.pending_interrupt();
+        if let Some(interrupt) = interrupt {
+            bus.acknowledge_interrupt(interrupt);
         }
-        
-        // Push PC part 2
+
         self.sp = self.sp.wrapping_sub(1);
-        mmu.write_byte(self.sp, pc as u8);
+        self.write(bus, self.sp, pc as u8);
+        self.pc = interrupt.map_or(0, |interrupt| interrupt.vector());
+        self.idle(bus);
     }
 }
\ No newline at end of file
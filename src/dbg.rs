@@ -1,4 +1,5 @@
 pub use crate::mmu::MMU;
+use crate::cpu::CpuEvent;
 //use ascii::AsciiCast;
 
 #[derive(Debug)]
@@ -6,6 +7,10 @@ pub struct DBG {
     msg: [u8; 1024],
     counter: usize,
     word: String,
+    // still acks the transfers, just doesn't print them
+    silent: bool,
+    // CPU events waiting for the frontend
+    events: Vec<CpuEvent>,
 }
 
 impl Default for DBG {
@@ -14,11 +19,20 @@ impl Default for DBG {
             msg: [0; 1024],
             counter: 0,
             word: String::new(),
+            silent: false,
+            events: Vec::new(),
         }
     }
 }
 
 impl DBG {
+    pub fn silent() -> DBG {
+        DBG {
+            silent: true,
+            ..DBG::default()
+        }
+    }
+
     pub fn dbg_update(&mut self, mmu: &mut MMU) {
         //println!("{:?}", mmu.read_byte(0xFF01));
         if mmu.read_byte(0xFF02) == 0x81 {
@@ -40,7 +54,9 @@ impl DBG {
             //let mut word = String::new();
             
             
-            if letter.eq(&'\n') {
+            if self.silent {
+                // nothing to print
+            } else if letter.eq(&'\n') {
                 if self.word.is_empty() {
                     println!("empty");
                 } else {
@@ -65,6 +81,13 @@ impl DBG {
             mmu.write_byte(0xFF02, 0);
         }
     }
+    pub fn raise(&mut self, event: CpuEvent) {
+        self.events.push(event);
+    }
+    pub fn take_events(&mut self) -> Vec<CpuEvent> {
+        std::mem::take(&mut self.events)
+    }
+
     pub fn dbg_print(&self) {
 
         if self.msg[0] != 0 {
@@ -0,0 +1,215 @@
+// Post processing between the PPU and the window: frame blending first,
+// at the native resolution, then the upscaler, then the LCD grid on top of
+// the scaled image. Works on any 0xRRGGBB buffer, so the SGB frame with
+// its border goes through the same path as the plain screen.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Upscaler {
+    Nearest(usize),
+    Scale2x,
+    Scale3x,
+}
+
+impl Upscaler {
+    pub fn factor(&self) -> usize {
+        match self {
+            Upscaler::Nearest(scale) => (*scale).max(1),
+            Upscaler::Scale2x => 2,
+            Upscaler::Scale3x => 3,
+        }
+    }
+
+    // nearest 1x to 4x, then the two ScaleNx
+    pub fn next(&self) -> Upscaler {
+        match self {
+            Upscaler::Nearest(scale) if *scale < 4 => Upscaler::Nearest(scale + 1),
+            Upscaler::Nearest(_) => Upscaler::Scale2x,
+            Upscaler::Scale2x => Upscaler::Scale3x,
+            Upscaler::Scale3x => Upscaler::Nearest(1),
+        }
+    }
+
+    // nearest, nearest3, scale2x, scale3x
+    pub fn from_name(name: &str) -> Result<Upscaler, String> {
+        match name {
+            "scale2x" => Ok(Upscaler::Scale2x),
+            "scale3x" => Ok(Upscaler::Scale3x),
+            _ => match name.strip_prefix("nearest") {
+                Some("") => Ok(Upscaler::Nearest(1)),
+                Some(scale) => match scale.parse::<usize>() {
+                    Ok(scale) if scale > 0 => Ok(Upscaler::Nearest(scale)),
+                    _ => Err(format!("bad nearest scale: {}", scale)),
+                },
+                None => Err(format!("unknown filter: {}", name)),
+            },
+        }
+    }
+}
+
+pub struct Filter {
+    pub upscaler: Upscaler,
+    // darkens the gaps between the LCD's pixels
+    pub lcd_grid: bool,
+    // mixes in the last frame, like the slow DMG LCD does
+    pub blend: bool,
+    previous: Vec<u32>,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self::new(Upscaler::Nearest(1))
+    }
+}
+
+impl Filter {
+    pub fn new(upscaler: Upscaler) -> Filter {
+        Filter {
+            upscaler,
+            lcd_grid: false,
+            blend: false,
+            previous: Vec::new(),
+        }
+    }
+
+    pub fn output_size(&self, width: usize, height: usize) -> (usize, usize) {
+        (width * self.upscaler.factor(), height * self.upscaler.factor())
+    }
+
+    // blend_frame then scale, for a caller that shows every frame
+    pub fn apply(&mut self, width: usize, height: usize, pixels: &[u32]) -> Vec<u32> {
+        let blended = self.blend_frame(pixels);
+        self.scale(width, height, &blended)
+    }
+
+    // has to see every frame the PPU finishes, even the ones that never
+    // make it to the window, or it mixes in an older one
+    pub fn blend_frame(&mut self, pixels: &[u32]) -> Vec<u32> {
+        let blended = if self.blend && self.previous.len() == pixels.len() {
+            pixels.iter().zip(self.previous.iter()).map(|(&a, &b)| mix(a, b)).collect()
+        } else {
+            pixels.to_vec()
+        };
+        // blend against the unblended frame, otherwise the trail never fades
+        self.previous.clear();
+        self.previous.extend_from_slice(pixels);
+        blended
+    }
+
+    // the upscaler and the LCD grid
+    pub fn scale(&self, width: usize, height: usize, source: &[u32]) -> Vec<u32> {
+        assert_eq!(source.len(), width * height, "pixel buffer doesn't match {}x{}", width, height);
+
+        let mut out = match self.upscaler {
+            Upscaler::Nearest(_) => nearest(width, height, source, self.upscaler.factor()),
+            Upscaler::Scale2x => scale2x(width, height, source),
+            Upscaler::Scale3x => scale3x(width, height, source),
+        };
+
+        let scale = self.upscaler.factor();
+        if self.lcd_grid && scale > 1 {
+            let out_width = width * scale;
+            for (i, color) in out.iter_mut().enumerate() {
+                let (x, y) = (i % out_width, i / out_width);
+                if x % scale == scale - 1 || y % scale == scale - 1 {
+                    *color = darken(*color);
+                }
+            }
+        }
+        out
+    }
+}
+
+fn nearest(width: usize, height: usize, pixels: &[u32], scale: usize) -> Vec<u32> {
+    let out_width = width * scale;
+    let mut out = vec![0; out_width * height * scale];
+    for (i, color) in out.iter_mut().enumerate() {
+        let (x, y) = (i % out_width, i / out_width);
+        *color = pixels[(y / scale) * width + x / scale];
+    }
+    out
+}
+
+// neighbours clamp at the edges of the screen
+fn pixel_at(width: usize, height: usize, pixels: &[u32], x: isize, y: isize) -> u32 {
+    let x = x.clamp(0, width as isize - 1) as usize;
+    let y = y.clamp(0, height as isize - 1) as usize;
+    pixels[y * width + x]
+}
+
+/*
+    Scale2x (AdvMAME2x). Around E:
+
+        A B C        E0 E1
+        D E F  --->  E2 E3
+        G H I
+*/
+fn scale2x(width: usize, height: usize, pixels: &[u32]) -> Vec<u32> {
+    let mut out = vec![0; width * height * 4];
+    for y in 0..height {
+        for x in 0..width {
+            let at = |dx: isize, dy: isize| pixel_at(width, height, pixels, x as isize + dx, y as isize + dy);
+            let (b, d, e, f, h) = (at(0, -1), at(-1, 0), at(0, 0), at(1, 0), at(0, 1));
+
+            let mut block = [e; 4];
+            if b != h && d != f {
+                if d == b {block[0] = d;}
+                if b == f {block[1] = f;}
+                if d == h {block[2] = d;}
+                if h == f {block[3] = f;}
+            }
+
+            let (ox, oy) = (x * 2, y * 2);
+            for (i, color) in block.iter().enumerate() {
+                out[(oy + i / 2) * width * 2 + ox + i % 2] = *color;
+            }
+        }
+    }
+    out
+}
+
+/*
+    Scale3x (AdvMAME3x), same neighbourhood, 3x3 out:
+
+        E0 E1 E2
+        E3 E4 E5
+        E6 E7 E8
+*/
+fn scale3x(width: usize, height: usize, pixels: &[u32]) -> Vec<u32> {
+    let mut out = vec![0; width * height * 9];
+    for y in 0..height {
+        for x in 0..width {
+            let at = |dx: isize, dy: isize| pixel_at(width, height, pixels, x as isize + dx, y as isize + dy);
+            let (a, b, c) = (at(-1, -1), at(0, -1), at(1, -1));
+            let (d, e, f) = (at(-1, 0), at(0, 0), at(1, 0));
+            let (g, h, i) = (at(-1, 1), at(0, 1), at(1, 1));
+
+            let mut block = [e; 9];
+            if b != h && d != f {
+                if d == b {block[0] = d;}
+                if (d == b && e != c) || (b == f && e != a) {block[1] = b;}
+                if b == f {block[2] = f;}
+                if (d == b && e != g) || (d == h && e != a) {block[3] = d;}
+                if (b == f && e != i) || (h == f && e != c) {block[5] = f;}
+                if d == h {block[6] = d;}
+                if (d == h && e != i) || (h == f && e != g) {block[7] = h;}
+                if h == f {block[8] = f;}
+            }
+
+            let (ox, oy) = (x * 3, y * 3);
+            for (n, color) in block.iter().enumerate() {
+                out[(oy + n / 3) * width * 3 + ox + n % 3] = *color;
+            }
+        }
+    }
+    out
+}
+
+// 50/50 of two colours
+fn mix(a: u32, b: u32) -> u32 {
+    ((a >> 1) & 0x7F7F7F) + ((b >> 1) & 0x7F7F7F)
+}
+
+// 3/4 of the colour
+fn darken(color: u32) -> u32 {
+    color - ((color >> 2) & 0x3F3F3F)
+}
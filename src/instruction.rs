@@ -1,138 +1,421 @@
-pub use crate::cpu::CPU;
+use std::fmt;
 
-#[derive(Debug)]
-pub enum Instruction {
+// A decoded instruction: what to do and on which operands, with the
+// immediates it was fetched with. Decoding never looks at the CPU or the
+// bus, so the same bytes always give the same Instruction and it's fine to
+// cache or print one. Reading registers and memory is all up to execute.
 
-    //8 bit loads
-    LdN(u8, u8),
-
-    LdAR2(u8, u8),
-    LdAnn(u8),
-    LdAd8(u8),
-    LdBR2(u8, bool),
-    LdCR2(u8, bool),
-    LdDR2(u8, bool),
-    LdER2(u8, bool),
-    LdHR2(u8, bool),
-    LdLR2(u8, bool),
-    LdHlR2(u8, bool),
-
-    LdnA(u8, u8),
-    Ldn16A(u16, u8),
-    Lda16A(u16, u8),
-
-    LdAc(u8),
-    LdCa(u8),
-
-    LddAhl,
-    LddAHl(u16),
-    LddHlA(u16),
-    LdIAHl(u16),
-    LdIHlA(u16),
-
-    LdHnA(u8),
-    LdHAn(u8),
-
-    //16 bit loads
-    LdBc(u16),
-    LdDe(u16),
-    LdHl(u16),
-    LdSp(u16),
-    LdSpHl(u16),
-    LdHlSp(i8),
-    LdnnSp(u16),
-    Pushnn(u16),
-    Popnn(u8),
-
-    //8 bit ALU
-    AddN(u8),
-    AddHl(u8),
-    AddD8(u8),
-    AdcN(u8),
-    AdcHl(u8),
-    AdcD8(u8),
-    SubN(u8),
-    SubHl(u8),
-    SubD8(u8),
-    SbcN(u8),
-    SbcHl(u8),
-    SbcD8(u8),
-    Andn(u8),
-    AndHl(u8),
-    AndD8(u8),
-    OrN(u8),
-    OrHl(u8),
-    OrD8(u8),
-
-    Xor(u8),
-    XorHl(u8),
-    XorD8(u8),
-
-    Cp(u8),
-    CpHl(u8),
-    CpD8(u8),
-
-    IncN(u8, u8),
-    IncHl(u16),
-
-    DecN(u8, u8),
-    DecHl(u16),
-
-    // 16bit Arithmetic
-    AddHlN(u16),
-    AddSpN(i8),
-    IncNN(u8, u16),
-    DecNN(u8, u16),
-
-    // Miscellaneous
-    SwapN(u8, u8),
-    SwapHl(u8),
-    Daa,
-    Cpl(u8),
-    Ccf,
-    Scf,
+// 8 bit operands in opcode order, HlIndirect is the byte HL points at
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum R8 {
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    HlIndirect,
+    A,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum R16 {
+    BC,
+    DE,
+    HL,
+    SP,
+}
+
+// PUSH and POP use AF in place of SP
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum R16Stack {
+    BC,
+    DE,
+    HL,
+    AF,
+}
+
+// what LD (rr),A and LD A,(rr) go through, HL+ and HL- move HL after
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum R16Indirect {
+    BC,
+    DE,
+    HlInc,
+    HlDec,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Condition {
+    NZ,
+    Z,
+    NC,
+    C,
+}
+
+// the 8 operations of the 0x80-0xBF block and the d8 ones
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Alu {
+    Add,
+    Adc,
+    Sub,
+    Sbc,
+    And,
+    Xor,
+    Or,
+    Cp,
+}
+
+// CB 0x00-0x3F
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Shift {
+    Rlc,
+    Rrc,
+    Rl,
+    Rr,
+    Sla,
+    Sra,
+    Swap,
+    Srl,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Instruction {
     Nop,
-    Halt,
     Stop,
+    Halt,
     Di,
     Ei,
+    // one of the unused opcodes, locks the CPU up
+    Illegal(u8),
 
-    //Rotates and Shifts
-    Rlca(u8),
-    Rla(u8),
-    Rrca(u8),
-    Rra(u8),
-    RlcN(u8, u8),
-    RlN(u8, u8),
-    RrcN(u8, u8),
-    RrN(u8, u8),
-    SlaN(u8, u8),
-    SraN(u8, u8),
-    SrlN(u8, u8),
-
-    //bit opcodes
-    BitbR(u8, u8),
-    BitbHl(u8, u8),
-    SetbR(u8, u8, u8),
-    ResbR(u8, u8, u8),
-
-    //jumps
-    Jpnn(u16),
-    Jpcc(u16, u8),
-    JpHl(u16),
-    JrN(i8),
-    Jrcc(i8, u8),
-
-    //calls
-    Callnn(u16),
-    Callcc(u16, u8),
-
-    //restarts
-    Rst(u8),
+    // 8 bit loads
+    Ld(R8, R8),
+    LdImm(R8, u8),
+    // LD (rr),A and LD A,(rr)
+    StoreA(R16Indirect),
+    LoadA(R16Indirect),
+    // LD (a16),A and LD A,(a16)
+    StoreAAbsolute(u16),
+    LoadAAbsolute(u16),
+    // LDH (a8),A and LDH A,(a8)
+    StoreAHigh(u8),
+    LoadAHigh(u8),
+    // LD ($FF00+C),A and LD A,($FF00+C)
+    StoreAHighC,
+    LoadAHighC,
+
+    // 16 bit loads
+    LdImm16(R16, u16),
+    StoreSp(u16),
+    LdSpHl,
+    LdHlSpOffset(i8),
+    Push(R16Stack),
+    Pop(R16Stack),
+
+    // arithmetic
+    Alu(Alu, R8),
+    AluImm(Alu, u8),
+    Inc(R8),
+    Dec(R8),
+    Inc16(R16),
+    Dec16(R16),
+    AddHl(R16),
+    AddSp(i8),
+    Daa,
+    Cpl,
+    Scf,
+    Ccf,
+
+    // rotates, shifts and bits
+    Rlca,
+    Rrca,
+    Rla,
+    Rra,
+    Shift(Shift, R8),
+    Bit(u8, R8),
+    Res(u8, R8),
+    Set(u8, R8),
 
-    //returns
+    // jumps, calls and returns
+    Jp(u16),
+    JpIf(Condition, u16),
+    JpHl,
+    Jr(i8),
+    JrIf(Condition, i8),
+    Call(u16),
+    CallIf(Condition, u16),
+    Rst(u8),
     Ret,
-    Retcc(u8),
+    RetIf(Condition),
     Reti,
+}
+
+impl R8 {
+    pub fn from_index(index: u8) -> R8 {
+        [R8::B, R8::C, R8::D, R8::E, R8::H, R8::L, R8::HlIndirect, R8::A][index as usize & 7]
+    }
+}
+
+impl R16 {
+    pub fn from_index(index: u8) -> R16 {
+        [R16::BC, R16::DE, R16::HL, R16::SP][index as usize & 3]
+    }
+}
+
+impl R16Stack {
+    pub fn from_index(index: u8) -> R16Stack {
+        [R16Stack::BC, R16Stack::DE, R16Stack::HL, R16Stack::AF][index as usize & 3]
+    }
+}
+
+impl R16Indirect {
+    pub fn from_index(index: u8) -> R16Indirect {
+        [R16Indirect::BC, R16Indirect::DE, R16Indirect::HlInc, R16Indirect::HlDec][index as usize & 3]
+    }
+}
+
+impl Condition {
+    pub fn from_index(index: u8) -> Condition {
+        [Condition::NZ, Condition::Z, Condition::NC, Condition::C][index as usize & 3]
+    }
+}
+
+impl Alu {
+    pub fn from_index(index: u8) -> Alu {
+        [Alu::Add, Alu::Adc, Alu::Sub, Alu::Sbc, Alu::And, Alu::Xor, Alu::Or, Alu::Cp][index as usize & 7]
+    }
+}
+
+impl Shift {
+    pub fn from_index(index: u8) -> Shift {
+        [Shift::Rlc, Shift::Rrc, Shift::Rl, Shift::Rr, Shift::Sla, Shift::Sra, Shift::Swap, Shift::Srl][index as usize & 7]
+    }
+}
+
+impl Instruction {
+    // bytes including the opcode (and the CB prefix). STOP counts its
+    // padding byte even though it's never fetched.
+    pub fn length(&self) -> u8 {
+        use Instruction::*;
+        match self {
+            Stop | LdImm(..) | StoreAHigh(_) | LoadAHigh(_) | LdHlSpOffset(_) | AluImm(..)
+            | AddSp(_) | Jr(_) | JrIf(..) | Shift(..) | Bit(..) | Res(..) | Set(..) => 2,
+            StoreAAbsolute(_) | LoadAAbsolute(_) | LdImm16(..) | StoreSp(_)
+            | Jp(_) | JpIf(..) | Call(_) | CallIf(..) => 3,
+            _ => 1,
+        }
+    }
+
+    // M-cycles when a conditional branch isn't taken, and for everything else
+    pub fn cycles(&self) -> u8 {
+        use Instruction::*;
+        // (HL) operands cost an extra cycle or two
+        let hl = |r: &R8| *r == R8::HlIndirect;
+        match self {
+            Ld(to, from) if hl(to) || hl(from) => 2,
+            LdImm(r, _) if hl(r) => 3,
+            LdImm(..) => 2,
+            Inc(r) | Dec(r) if hl(r) => 3,
+            Alu(_, r) if hl(r) => 2,
+            // BIT only reads (HL), the others write it back too
+            Bit(_, r) if hl(r) => 3,
+            Shift(_, r) | Res(_, r) | Set(_, r) if hl(r) => 4,
+            Bit(..) | Shift(..) | Res(..) | Set(..) => 2,
+
+            StoreA(_) | LoadA(_) | StoreAHighC | LoadAHighC => 2,
+            StoreAHigh(_) | LoadAHigh(_) => 3,
+            StoreAAbsolute(_) | LoadAAbsolute(_) => 4,
+            LdImm16(..) => 3,
+            StoreSp(_) => 5,
+            LdSpHl => 2,
+            LdHlSpOffset(_) => 3,
+            Push(_) => 4,
+            Pop(_) => 3,
+            AluImm(..) => 2,
+            Inc16(_) | Dec16(_) | AddHl(_) => 2,
+            AddSp(_) => 4,
+
+            Jp(_) => 4,
+            JpIf(..) => 3,
+            JpHl => 1,
+            Jr(_) => 3,
+            JrIf(..) => 2,
+            Call(_) => 6,
+            CallIf(..) => 3,
+            Rst(_) => 4,
+            Ret | Reti => 4,
+            RetIf(_) => 2,
+
+            _ => 1,
+        }
+    }
+
+    // M-cycles when a conditional branch is taken
+    pub fn cycles_taken(&self) -> u8 {
+        match self {
+            Instruction::JpIf(..) => 4,
+            Instruction::JrIf(..) => 3,
+            Instruction::CallIf(..) => 6,
+            Instruction::RetIf(_) => 5,
+            _ => self.cycles(),
+        }
+    }
+
+    // where a jump, call or RST at `address` goes, when it's known without
+    // running it
+    pub fn target(&self, address: u16) -> Option<u16> {
+        match *self {
+            Instruction::Jr(e) | Instruction::JrIf(_, e) => Some(address.wrapping_add(2).wrapping_add(e as u16)),
+            Instruction::Jp(nn) | Instruction::JpIf(_, nn) | Instruction::Call(nn) | Instruction::CallIf(_, nn) => Some(nn),
+            Instruction::Rst(vector) => Some(vector as u16),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for R8 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            R8::B => "B",
+            R8::C => "C",
+            R8::D => "D",
+            R8::E => "E",
+            R8::H => "H",
+            R8::L => "L",
+            R8::HlIndirect => "(HL)",
+            R8::A => "A",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for R16 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl fmt::Display for R16Stack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl fmt::Display for R16Indirect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            R16Indirect::BC => "(BC)",
+            R16Indirect::DE => "(DE)",
+            R16Indirect::HlInc => "(HL+)",
+            R16Indirect::HlDec => "(HL-)",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl fmt::Display for Alu {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Alu::Add => "ADD A,",
+            Alu::Adc => "ADC A,",
+            Alu::Sub => "SUB ",
+            Alu::Sbc => "SBC A,",
+            Alu::And => "AND ",
+            Alu::Xor => "XOR ",
+            Alu::Or => "OR ",
+            Alu::Cp => "CP ",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Shift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format!("{:?}", self).to_uppercase())
+    }
+}
+
+// JR offsets are relative to the start of the JR itself, so a jump to
+// itself is `$`
+struct Relative(i8);
+
+impl fmt::Display for Relative {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 as i16 + 2 {
+            0 => write!(f, "$"),
+            offset => write!(f, "${:+}", offset),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Instruction::*;
+        match *self {
+            Nop => write!(f, "NOP"),
+            Stop => write!(f, "STOP"),
+            Halt => write!(f, "HALT"),
+            Di => write!(f, "DI"),
+            Ei => write!(f, "EI"),
+            Illegal(opcode) => write!(f, "DB ${:02X}", opcode),
+
+            Ld(to, from) => write!(f, "LD {},{}", to, from),
+            LdImm(r, n) => write!(f, "LD {},${:02X}", r, n),
+            StoreA(rr) => write!(f, "LD {},A", rr),
+            LoadA(rr) => write!(f, "LD A,{}", rr),
+            StoreAAbsolute(nn) => write!(f, "LD (${:04X}),A", nn),
+            LoadAAbsolute(nn) => write!(f, "LD A,(${:04X})", nn),
+            StoreAHigh(n) => write!(f, "LDH (${:04X}),A", 0xFF00 | n as u16),
+            LoadAHigh(n) => write!(f, "LDH A,(${:04X})", 0xFF00 | n as u16),
+            StoreAHighC => write!(f, "LD ($FF00+C),A"),
+            LoadAHighC => write!(f, "LD A,($FF00+C)"),
+
+            LdImm16(rr, nn) => write!(f, "LD {},${:04X}", rr, nn),
+            StoreSp(nn) => write!(f, "LD (${:04X}),SP", nn),
+            LdSpHl => write!(f, "LD SP,HL"),
+            LdHlSpOffset(e) => write!(f, "LD HL,SP{:+}", e),
+            Push(rr) => write!(f, "PUSH {}", rr),
+            Pop(rr) => write!(f, "POP {}", rr),
+
+            Alu(op, r) => write!(f, "{}{}", op, r),
+            AluImm(op, n) => write!(f, "{}${:02X}", op, n),
+            Inc(r) => write!(f, "INC {}", r),
+            Dec(r) => write!(f, "DEC {}", r),
+            Inc16(rr) => write!(f, "INC {}", rr),
+            Dec16(rr) => write!(f, "DEC {}", rr),
+            AddHl(rr) => write!(f, "ADD HL,{}", rr),
+            AddSp(e) => write!(f, "ADD SP,{}", e),
+            Daa => write!(f, "DAA"),
+            Cpl => write!(f, "CPL"),
+            Scf => write!(f, "SCF"),
+            Ccf => write!(f, "CCF"),
+
+            Rlca => write!(f, "RLCA"),
+            Rrca => write!(f, "RRCA"),
+            Rla => write!(f, "RLA"),
+            Rra => write!(f, "RRA"),
+            Shift(op, r) => write!(f, "{} {}", op, r),
+            Bit(bit, r) => write!(f, "BIT {},{}", bit, r),
+            Res(bit, r) => write!(f, "RES {},{}", bit, r),
+            Set(bit, r) => write!(f, "SET {},{}", bit, r),
 
-}
\ No newline at end of file
+            Jp(nn) => write!(f, "JP ${:04X}", nn),
+            JpIf(cc, nn) => write!(f, "JP {},${:04X}", cc, nn),
+            JpHl => write!(f, "JP HL"),
+            Jr(e) => write!(f, "JR {}", Relative(e)),
+            JrIf(cc, e) => write!(f, "JR {},{}", cc, Relative(e)),
+            Call(nn) => write!(f, "CALL ${:04X}", nn),
+            CallIf(cc, nn) => write!(f, "CALL {},${:04X}", cc, nn),
+            Rst(vector) => write!(f, "RST ${:02X}", vector),
+            Ret => write!(f, "RET"),
+            RetIf(cc) => write!(f, "RET {}", cc),
+            Reti => write!(f, "RETI"),
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use bitflags::bitflags;
 
+use crate::sgb::packet::{PacketReceiver, PACKET_SIZE};
+
 pub enum JoypadButtons {
     Start,
     Select,
@@ -44,6 +46,10 @@ pub struct JoyPad {
     button_select: bool,
     direction_select: bool,
     buttons: Buttons,
+    // SGB only: packets and the MLT_REQ player selection
+    packets: PacketReceiver,
+    players: u8,
+    player: u8,
 }
 
 impl Default for JoyPad {
@@ -52,6 +58,9 @@ impl Default for JoyPad {
             button_select: true,
             direction_select: true,
             buttons: Buttons::default(),
+            packets: PacketReceiver::default(),
+            players: 1,
+            player: 0,
         }
     }
 }
@@ -65,6 +74,15 @@ impl JoyPad {
     }
 
     pub fn read_joypad(&self) -> u8 {
+        // with both lines deselected a multiplayer SGB answers with 0xF - player
+        if self.players > 1 && !self.button_select && !self.direction_select {
+            return 0xFF - self.player;
+        }
+        // only player 1 is connected
+        if self.player != 0 {
+            return 0xC0 | (((!self.button_select) as u8) << 5) | ((!self.direction_select as u8) << 4) | 0x0F;
+        }
+
         let mut output = 0xCF;
         if self.button_select {
             output &= !self.buttons.bits() >> 4;
@@ -78,7 +96,14 @@ impl JoyPad {
     }
 
     pub fn write_joypad(&mut self, value: u8) {
-        self.button_select = ((value >> 5) & 1) == 0;
+        let button_select = ((value >> 5) & 1) == 0;
+        // MLT_REQ: P15 going high moves on to the next player
+        if self.players > 1 && self.button_select && !button_select {
+            self.player = (self.player + 1) % self.players;
+        }
+        self.packets.write(value);
+
+        self.button_select = button_select;
         self.direction_select = ((value >> 4) & 1) == 0;
         //println!("button:{}, direction:{}", self.button_select, self.direction_select);
     }
@@ -90,4 +115,29 @@ impl JoyPad {
     pub fn release_joypad(&mut self, button: JoypadButtons) {
         self.buttons.remove(button.into())
     }
+
+    pub fn take_sgb_packet(&mut self) -> Option<[u8; PACKET_SIZE]> {
+        self.packets.take_packet()
+    }
+
+    pub fn set_players(&mut self, players: u8) {
+        self.players = players;
+        self.player = 0;
+    }
+
+    // P10-P13 that a pressed button pulls low, a set bit is a low line
+    pub fn low_lines(&self) -> u8 {
+        let mut lines = 0;
+        if self.button_select {
+            lines |= self.buttons.bits() >> 4;
+        }
+        if self.direction_select {
+            lines |= self.buttons.bits() & 0x0F;
+        }
+        lines
+    }
+
+    pub fn is_pressed(&self, button: JoypadButtons) -> bool {
+        self.buttons.contains(button.into())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,16 @@
+#![allow(dead_code)]
+
+pub mod mmu;
+pub mod bus;
+pub mod cpu;
+pub mod instruction;
+pub mod ppu;
+pub mod timer;
+pub mod dbg;
+pub mod cartridge;
+pub mod joypad;
+pub mod sgb;
+pub mod png;
+pub mod recorder;
+pub mod filter;
+pub mod runner;
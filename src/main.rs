@@ -15,23 +15,23 @@ use std::time::Duration; */
 
 //mod mmu;
 //use mmu::MMU;
-use crate::mmu::{MMU, interrupts, serial};
-use crate::cpu::CPU;
-use crate::instruction::Instruction;
-use crate::ppu::{PPU, lcd::state_machine};
-use crate::timer::Timer;
-use crate::dbg::DBG; 
-use crate::cartridge::Cartridge; 
-use crate::joypad::JoypadButtons;
-
-mod mmu;
-mod cpu;
-mod instruction;
-mod ppu;
-mod timer;
-mod dbg;
-mod cartridge;
-mod joypad;
+use doma_emu::mmu::{MMU, interrupts, serial};
+use doma_emu::cpu::CPU;
+use doma_emu::instruction::Instruction;
+use doma_emu::ppu::{PPU, RenderMode, lcd::state_machine};
+use doma_emu::ppu::viewer::{PixelBuffer, TileMap};
+use doma_emu::ppu::layers::Layer;
+use doma_emu::ppu::palette::{PaletteConfig, PaletteLayer};
+use doma_emu::ppu::colorize::{self, ButtonCombo};
+use doma_emu::timer::Timer;
+use doma_emu::dbg::DBG; 
+use doma_emu::cartridge::Cartridge; 
+use doma_emu::joypad::JoypadButtons;
+use doma_emu::sgb::{SGB_WIDTH, SGB_HEIGHT};
+use doma_emu::png;
+use doma_emu::recorder::Recorder;
+use doma_emu::filter::{Filter, Upscaler};
+
 
 //160 x 144
 pub const SCALE: usize = 2;
@@ -45,7 +45,9 @@ pub const GAMEBOY_HEIGHT: usize = YRES * SCALE;
 pub const SCREEN_WIDTH: usize = 144 * SCALE;
 pub const SCREEN_HEIGHT: usize = 216 * SCALE;
 
-pub const TILE_COLORS: [u32; 4] = [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000];
+pub const PALETTE_CONFIG: &str = "palettes.cfg";
+// how long the CGB boot logo stays up, button combos are only read meanwhile
+pub const BOOT_LOGO_FRAMES: u32 = 120;
 
 pub struct Gameboy {
 
@@ -72,6 +74,7 @@ impl Rect {
 }
 
 fn display_tile(mmu: &MMU, dest: &mut [u32], start_address:u16, tile_num:u16, x:u32, y:u32) {
+    let tile_colors = mmu.ppu.dmg_colors().bg;
     for tile_y in 0..=7 {
         let b1:u8 = mmu.read_byte(start_address + (tile_num * 16) + tile_y * 2);
         let b2:u8 = mmu.read_byte(start_address + (tile_num * 16) + tile_y * 2 + 1);
@@ -96,7 +99,7 @@ fn display_tile(mmu: &MMU, dest: &mut [u32], start_address:u16, tile_num:u16, x:
             /* if color > 0 {
                 println!("b1:{:#X}, b2:{:#X}, rec_x:{}, rec_y:{}, w:{}, h:{}, color:{:#X}", b1, b2, rec_x, rec_y, rec_w, rec_h, color);
             } */
-            let rec = Rect::new(rec_x, rec_y, rec_w, rec_h, TILE_COLORS[color as usize]);
+            let rec = Rect::new(rec_x, rec_y, rec_w, rec_h, tile_colors[color as usize]);
             //println!("b1:{:#X}, b2:{:#X}, rec_x:{}, rec_y:{}, w:{}, h:{}, color:{:#X}", b1, b2, rec_x, rec_y, rec_w, rec_h, color);
             fill_rect(dest, &rec, false);
         }
@@ -137,18 +140,80 @@ fn update_dbg_window(mmu: &MMU, dest: &mut [u32]) {
     }
 }
 
-fn update_gameboy_window(mmu: &MMU, dest: &mut [u32]) {
-    for line_num in 0..YRES {
-        for x in 0..XRES {
-            let color = mmu.ppu.video_buffer[x + line_num * XRES];
-            let rect = Rect::new(x * SCALE, line_num * SCALE, SCALE, SCALE, color);
-            //if line_num * SCALE > 270 {println!("rect x:{}, y:{}, h:{}, w:{}, color:{:#X}", rect.x, rect.y, rect.height, rect.width, rect.color)};
-            //if recta.color != 0xFFFFFF {println!("rect_color1:{}, color:{}", recta.color, color)};
-            fill_rect(dest, &rect, true);
+// what the debug window shows, F5 moves to the next one
+#[derive(Clone, Copy, Debug)]
+enum DebugView {
+    Tiles,
+    Map9800,
+    Map9C00,
+    Oam,
+    Palettes,
+}
+
+impl DebugView {
+    fn next(self) -> DebugView {
+        match self {
+            DebugView::Tiles => DebugView::Map9800,
+            DebugView::Map9800 => DebugView::Map9C00,
+            DebugView::Map9C00 => DebugView::Oam,
+            DebugView::Oam => DebugView::Palettes,
+            DebugView::Palettes => DebugView::Tiles,
+        }
+    }
+}
+
+fn update_debug_view(mmu: &MMU, view: DebugView, dest: &mut [u32]) {
+    let buffer = match view {
+        DebugView::Tiles => return update_dbg_window(mmu, dest),
+        DebugView::Map9800 => mmu.ppu.tilemap_view(TileMap::Low),
+        DebugView::Map9C00 => mmu.ppu.tilemap_view(TileMap::High),
+        DebugView::Oam => mmu.ppu.oam_view(),
+        DebugView::Palettes => mmu.ppu.palette_view(),
+    };
+    draw_scaled(dest, &buffer);
+}
+
+// biggest integer scale that still fits the debug window
+fn draw_scaled(dest: &mut [u32], buffer: &PixelBuffer) {
+    let scale = (SCREEN_WIDTH / buffer.width).min(SCREEN_HEIGHT / buffer.height).max(1);
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            dest[y * SCREEN_WIDTH + x] = if x < buffer.width * scale && y < buffer.height * scale {
+                buffer.get(x / scale, y / scale)
+            } else {
+                0
+            };
         }
     }
 }
 
+fn print_sprite_table(mmu: &MMU) {
+    for info in mmu.ppu.sprite_table().iter().filter(|info| info.visible) {
+        println!("sprite {:2}: x:{:3} y:{:3} tile:{:#04X} behind_bg:{} x_flip:{} y_flip:{} obp:{} cgb_pal:{} bank:{}",
+            info.index, info.x, info.y, info.tile, info.behind_bg, info.x_flip, info.y_flip,
+            info.dmg_palette, info.cgb_palette, info.vram_bank);
+    }
+}
+
+// every finished frame goes through the frame blending, the SGB one with
+// its border
+fn blend_frame(mmu: &mut MMU, filter: &mut Filter, sgb_mode: bool, frame: &mut Vec<u32>) {
+    *frame = if sgb_mode {
+        let sgb_frame = mmu.sgb.render(mmu.ppu.shade_buffer());
+        filter.blend_frame(&sgb_frame)
+    } else {
+        filter.blend_frame(mmu.ppu.video_buffer())
+    };
+}
+
+// scales the last blended frame for the window. Returns the size it came
+// out at, the window stretches it to fit
+fn update_gameboy_window(frame: &[u32], filter: &Filter, sgb_mode: bool, dest: &mut Vec<u32>) -> (usize, usize) {
+    let (width, height) = if sgb_mode {(SGB_WIDTH, SGB_HEIGHT)} else {(XRES, YRES)};
+    *dest = filter.scale(width, height, frame);
+    filter.output_size(width, height)
+}
+
 fn press_keys(mmu: &mut MMU, k: Key, pressed: bool, released: bool) {
     if pressed {
         match k {
@@ -178,6 +243,91 @@ fn press_keys(mmu: &mut MMU, k: Key, pressed: bool, released: bool) {
     }    
 }
 
+// F1/F2/F3 step the BG/OBJ0/OBJ1 palette through the built-in and user palettes
+fn switch_palette(mmu: &mut MMU, palettes: &mut PaletteConfig, layer: PaletteLayer) {
+    let name = palettes.cycle(layer).name.clone();
+    mmu.ppu.set_dmg_colors(palettes.colors());
+    println!("{:?} palette: {}", layer, name);
+}
+
+fn toggle_render_mode(mmu: &mut MMU) {
+    let mode = match mmu.ppu.render_mode() {
+        RenderMode::Accurate => RenderMode::Fast,
+        RenderMode::Fast => RenderMode::Accurate,
+    };
+    mmu.ppu.set_render_mode(mode);
+    println!("render mode: {:?}", mode);
+}
+
+fn toggle_layer(mmu: &mut MMU, layer: Layer) {
+    let shown = mmu.ppu.layers_mut().toggle(layer);
+    println!("{:?} layer: {}", layer, if shown {"on"} else {"off"});
+}
+
+#[cfg(debug_assertions)]
+fn toggle_highlight(mmu: &mut MMU) {
+    let layers = mmu.ppu.layers_mut();
+    layers.highlight = !layers.highlight;
+    println!("layer highlight: {}", if layers.highlight {"on"} else {"off"});
+}
+
+// F12 saves what's on screen, the SGB border included
+fn save_screenshot(mmu: &mut MMU, sgb_mode: bool, scale: usize) {
+    let path = format!("screenshot-{}.png", mmu.ppu.current_frame);
+    let result = if sgb_mode {
+        let frame = mmu.sgb.render(mmu.ppu.shade_buffer());
+        png::save(&path, SGB_WIDTH, SGB_HEIGHT, &frame, scale)
+    } else {
+        mmu.ppu.save_screenshot(&path, scale)
+    };
+    match result {
+        Ok(()) => println!("saved {}", path),
+        Err(error) => println!("screenshot failed: {}", error),
+    }
+}
+
+// F10 starts and stops recording to a .y4m next to the screenshots
+fn toggle_recording(mmu: &MMU, recorder: &mut Option<Recorder>, sgb_mode: bool) {
+    match recorder.take() {
+        Some(recording) => match recording.finish() {
+            Ok(frames) => println!("recording stopped, {} frames", frames),
+            Err(error) => println!("recording failed: {}", error),
+        },
+        None => {
+            let path = format!("recording-{}.y4m", mmu.ppu.current_frame);
+            let (width, height) = if sgb_mode {(SGB_WIDTH, SGB_HEIGHT)} else {(XRES, YRES)};
+            match Recorder::new(&path, width, height) {
+                Ok(recording) => {
+                    println!("recording to {}", path);
+                    *recorder = Some(recording);
+                }
+                Err(error) => println!("recording failed: {}", error),
+            }
+        }
+    }
+}
+
+fn record_frame(mmu: &mut MMU, recorder: &mut Option<Recorder>, sgb_mode: bool) {
+    let Some(recording) = recorder else {
+        return;
+    };
+    let result = if sgb_mode {
+        let frame = mmu.sgb.render(mmu.ppu.shade_buffer());
+        recording.push_frame(&frame)
+    } else {
+        recording.push_frame(mmu.ppu.video_buffer())
+    };
+    if let Err(error) = result {
+        println!("recording failed: {}", error);
+        *recorder = None;
+    }
+}
+
+fn next_upscaler(filter: &mut Filter) {
+    filter.upscaler = filter.upscaler.next();
+    println!("upscaler: {:?}", filter.upscaler);
+}
+
 //pub static now:Instant = Instant::now();
 const TARGET_FRAME_TIME:u32 = 17 as u32; // 1000/60 is about 16.667
 
@@ -192,7 +342,10 @@ fn main() {
     //roms/instr_timing.gb
     //roms/ppu-acceptance/hblank_ly_scx_timing-GS.gb
     //roms/ppu-acceptance/intr_2_0_timing.gb
-    let mut f = File::open("roms/drmario.gb").unwrap_or_else(|error| {
+    // the first argument that isn't a --flag picks the ROM
+    let rom_path = std::env::args().skip(1).find(|arg| !arg.starts_with("--"))
+        .unwrap_or_else(|| "roms/drmario.gb".to_string());
+    let mut f = File::open(&rom_path).unwrap_or_else(|error| {
         panic!("Problem opening the file: {:?}", error);
     });
 
@@ -206,14 +359,73 @@ fn main() {
     bootfile.read_to_end(&mut boot_file);
 
     let mut mem = MMU::default();
-    let mut com = CPU::default();
+
+    let mut palettes = if std::path::Path::new(PALETTE_CONFIG).exists() {
+        PaletteConfig::load(PALETTE_CONFIG).unwrap_or_else(|error| {
+            panic!("Problem loading the palettes: {}", error);
+        })
+    } else {
+        PaletteConfig::default()
+    };
+    mem.ppu.set_dmg_colors(palettes.colors());
 
     let mut dbg = DBG::default();
 
+    // put rom into memory
+    mem.cartridge.from_rom_file(&rom_file);
+    let cgb_mode = mem.cartridge.is_cgb();
+    mem.set_cgb_mode(cgb_mode);
+
+    // --sgb runs DMG games on a Super Game Boy, framed by its border
+    let sgb_mode = !cgb_mode && std::env::args().any(|arg| arg == "--sgb");
+    mem.set_sgb_mode(sgb_mode);
+
+    let mut com = if cgb_mode {
+        CPU::cgb_default()
+    } else if sgb_mode {
+        CPU::sgb_default()
+    } else {
+        CPU::default()
+    };
+
+    // --colorize gives DMG games the colours a CGB would pick for them
+    let colorize = !cgb_mode && !sgb_mode && std::env::args().any(|arg| arg == "--colorize");
+    let mut button_combo = None;
+    if colorize {
+        mem.ppu.set_dmg_colors(colorize::cartridge_colors(&mem.cartridge));
+    }
+
+    // --fast swaps the pixel FIFO for the scanline renderer, F4 toggles it
+    if std::env::args().any(|arg| arg == "--fast") {
+        mem.ppu.set_render_mode(RenderMode::Fast);
+    }
+
+    // --screenshot-scale=N upscales the F12 screenshots
+    let screenshot_scale = std::env::args()
+        .find_map(|arg| arg.strip_prefix("--screenshot-scale=").and_then(|n| n.parse().ok()))
+        .unwrap_or(1);
+
+    // --filter=nearestN|scale2x|scale3x, --lcd-grid and --blend set up the
+    // post processing, F11 cycles the upscaler, G and H toggle the other two
+    let upscaler = std::env::args()
+        .find_map(|arg| arg.strip_prefix("--filter=").map(Upscaler::from_name))
+        .unwrap_or(Ok(Upscaler::Nearest(SCALE)))
+        .unwrap_or_else(|error| panic!("{}", error));
+    let mut filter = Filter::new(upscaler);
+    filter.lcd_grid = std::env::args().any(|arg| arg == "--lcd-grid");
+    filter.blend = std::env::args().any(|arg| arg == "--blend");
+
+    let (window_width, window_height) = if sgb_mode {
+        filter.output_size(SGB_WIDTH, SGB_HEIGHT)
+    } else {
+        filter.output_size(XRES, YRES)
+    };
+    let mut screen_size = (window_width, window_height);
+
     let mut gameboy_window = Window::new(
         "Gameboy - ESC to exit",
-        GAMEBOY_WIDTH,
-        GAMEBOY_HEIGHT,
+        window_width,
+        window_height,
         WindowOptions {
             resize: true,
             ..WindowOptions::default()},
@@ -238,13 +450,12 @@ fn main() {
     // Limit to max ~60 fps update rate
     //window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
 
-    let mut gameboy_buffer: Vec<u32> = vec![0; GAMEBOY_WIDTH * GAMEBOY_HEIGHT];
+    let mut gameboy_buffer: Vec<u32> = vec![0; window_width * window_height];
     let mut dbg_buffer: Vec<u32> = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT* SCALE * SCALE];
+    let mut debug_view = DebugView::Tiles;
 
     
 
-    // put rom into memory
-    mem.cartridge.from_rom_file(&rom_file);
     //mem.cartridge.from_boot_file(&boot_file);
 
     //println!("first byte at 0000 is {:#X}", mem.read_byte(0x0000) as u16);
@@ -254,10 +465,13 @@ fn main() {
     //let mut c = 0;
     //'running: loop
     let mut prev_frame: u32 = 0;
+    let mut blended_frame: Vec<u32> = Vec::new();
     let mut prev_time: u128 = 0;
     let mut start_timer: u128 = 0;
     let mut frame_count: u64 = 0;
 
+    let mut recorder: Option<Recorder> = None;
+
     let mut gran: u8 = 0;
     let gran_length = 10;
 
@@ -278,6 +492,51 @@ fn main() {
         //let mut p = 0;
         //dbg_buffer.len() 
         let key_array = [Key::W, Key::A, Key::S, Key::D, Key::Comma, Key::Period, Key::Enter, Key::RightShift];
+        let palette_keys = [(Key::F1, PaletteLayer::Bg), (Key::F2, PaletteLayer::Obj0), (Key::F3, PaletteLayer::Obj1)];
+        for (k, layer) in palette_keys {
+            // the SGB picks its own colours
+            if !sgb_mode && gameboy_window.is_key_pressed(k, KeyRepeat::No) {
+                switch_palette(&mut mem, &mut palettes, layer);
+            }
+        }
+        if gameboy_window.is_key_pressed(Key::F5, KeyRepeat::No) {
+            debug_view = debug_view.next();
+            dbg_buffer.fill(0);
+            println!("debug view: {:?}", debug_view);
+            if let DebugView::Oam = debug_view {
+                print_sprite_table(&mem);
+            }
+        }
+        if gameboy_window.is_key_pressed(Key::F4, KeyRepeat::No) {
+            toggle_render_mode(&mut mem);
+        }
+        let layer_keys = [(Key::F6, Layer::Background), (Key::F7, Layer::Window), (Key::F8, Layer::Objects)];
+        for (k, layer) in layer_keys {
+            if gameboy_window.is_key_pressed(k, KeyRepeat::No) {
+                toggle_layer(&mut mem, layer);
+            }
+        }
+        #[cfg(debug_assertions)]
+        if gameboy_window.is_key_pressed(Key::F9, KeyRepeat::No) {
+            toggle_highlight(&mut mem);
+        }
+        if gameboy_window.is_key_pressed(Key::F10, KeyRepeat::No) {
+            toggle_recording(&mem, &mut recorder, sgb_mode);
+        }
+        if gameboy_window.is_key_pressed(Key::F11, KeyRepeat::No) {
+            next_upscaler(&mut filter);
+        }
+        if gameboy_window.is_key_pressed(Key::G, KeyRepeat::No) {
+            filter.lcd_grid = !filter.lcd_grid;
+            println!("lcd grid: {}", if filter.lcd_grid {"on"} else {"off"});
+        }
+        if gameboy_window.is_key_pressed(Key::H, KeyRepeat::No) {
+            filter.blend = !filter.blend;
+            println!("frame blending: {}", if filter.blend {"on"} else {"off"});
+        }
+        if gameboy_window.is_key_pressed(Key::F12, KeyRepeat::No) {
+            save_screenshot(&mut mem, sgb_mode, screenshot_scale);
+        }
         for k in key_array {
             press_keys(&mut mem, k, gameboy_window.is_key_pressed(k, KeyRepeat::Yes), gameboy_window.is_key_released(k));
             /* if gameboy_window.is_key_pressed(k, KeyRepeat::Yes) {
@@ -287,6 +546,15 @@ fn main() {
                 println!("key2:{:?}", k);
             } */
         }
+        if colorize && mem.ppu.current_frame < BOOT_LOGO_FRAMES {
+            if let Some(combo) = ButtonCombo::from_joypad(&mem.joypad) {
+                if button_combo != Some(combo) {
+                    button_combo = Some(combo);
+                    mem.ppu.set_dmg_colors(combo.colors());
+                    println!("palette combo: {:?}", combo);
+                }
+            }
+        }
         for i in 0..gameboy_buffer.len() {
             //update_dbg_window(&mem, &mut buffer);
             /* gameboy_window.get_keys_pressed(KeyRepeat::No).map(|keys| {
@@ -306,6 +574,8 @@ fn main() {
                 prev_frame, mem.ppu.current_frame, mem.read_byte(0xFF41)); */
             if prev_frame != mem.ppu.current_frame {
                 gran += 1;
+                record_frame(&mut mem, &mut recorder, sgb_mode);
+                blend_frame(&mut mem, &mut filter, sgb_mode, &mut blended_frame);
             }
             if gran == gran_length {
                 let end: u128 = now.elapsed().as_millis();
@@ -327,13 +597,22 @@ fn main() {
 
                 prev_time = end;
 
-                update_gameboy_window(&mem, &mut gameboy_buffer);
+                screen_size = update_gameboy_window(&blended_frame, &filter, sgb_mode, &mut gameboy_buffer);
                 if dbg_window.is_open() && !dbg_window.is_key_down(Key::Escape) { 
-                    update_dbg_window(&mem, &mut dbg_buffer);
+                    update_debug_view(&mem, debug_view, &mut dbg_buffer);
                 }
             }
             prev_frame = mem.ppu.current_frame;
         }
+        // a locked up CPU is reported, the screen just stays as it is like
+        // on the real thing
+        for event in dbg.take_events() {
+            println!("{}", event);
+        }
+        // STOP turned the clock off, no frames come to pace the loop
+        if mem.stopped() {
+            thread::sleep(Duration::from_millis(TARGET_FRAME_TIME as u64));
+        }
         /* for i in 0..gameboy_buffer.len() {
             update_gameboy_window(&mem, &mut gameboy_buffer);
         } */
@@ -367,7 +646,7 @@ fn main() {
 
 
         gameboy_window
-            .update_with_buffer(&gameboy_buffer, GAMEBOY_WIDTH, GAMEBOY_HEIGHT)
+            .update_with_buffer(&gameboy_buffer, screen_size.0, screen_size.1)
             .unwrap();
 
         dbg_window
@@ -377,4 +656,9 @@ fn main() {
         /* canvas.present();
         ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60)); */
     }
-}
\ No newline at end of file
+
+    // closing the window mid recording still leaves a complete file
+    if recorder.is_some() {
+        toggle_recording(&mem, &mut recorder, sgb_mode);
+    }
+}
@@ -17,10 +17,14 @@ pub use crate::timer::Timer;
 pub use crate::ppu::PPU;
 pub use crate::cartridge::Cartridge;
 pub use crate::joypad::JoyPad;
+pub use crate::sgb::Sgb;
+use crate::bus::Bus;
+use interrupts::InterruptType;
 
 pub mod interrupts;
 pub mod serial;
 pub mod dma;
+pub mod speed;
 
 pub trait DmaTransfer {
     fn dma_tick(&mut self);
@@ -29,6 +33,9 @@ pub trait DmaTransfer {
 
 pub struct MMU {
     ram: [u8;65536], //0x0000 to 0xFFFF
+    wram: [u8; 0x8000], // 8 banks of 4KiB, banks 2-7 are CGB only
+    wram_bank: u8,
+    cgb_mode: bool,
     pub serial: serial::Serial,
     pub timer: Timer,
     pub interrupts: interrupts::Interrupts,
@@ -36,12 +43,22 @@ pub struct MMU {
     pub cartridge: Cartridge,
     pub joypad: JoyPad,
     pub oam_dma: dma::OamDma,
+    pub hdma: dma::HDma,
+    pub speed: speed::SpeedSwitch,
+    pub sgb: Sgb,
+    // STOP ran, nothing moves until a button is pressed
+    stopped: bool,
+    // the joypad lines last M-cycle, a line going low requests an interrupt
+    joypad_lines: u8,
 }
 
 impl Default for MMU {
     fn default() -> Self {
         Self {
             ram: [0; 65536],
+            wram: [0; 0x8000],
+            wram_bank: 1,
+            cgb_mode: false,
             serial: serial::Serial::new(),
             timer: Timer::default(),
             interrupts: interrupts::Interrupts::default(),
@@ -49,6 +66,11 @@ impl Default for MMU {
             cartridge: Cartridge::default(),
             joypad: JoyPad::default(),
             oam_dma: dma::OamDma::default(),
+            hdma: dma::HDma::default(),
+            speed: speed::SpeedSwitch::default(),
+            sgb: Sgb::default(),
+            stopped: false,
+            joypad_lines: 0,
         }
     }
 }
@@ -57,6 +79,9 @@ impl MMU {
     pub fn new() -> MMU {
         MMU { 
             ram: [0; 65536],
+            wram: [0; 0x8000],
+            wram_bank: 1,
+            cgb_mode: false,
             serial: serial::Serial::new(),
             timer: Timer::new(),
             interrupts: interrupts::Interrupts::default(),
@@ -64,14 +89,38 @@ impl MMU {
             cartridge: Cartridge::default(),
             joypad: JoyPad::default(),
             oam_dma: dma::OamDma::default(),
+            hdma: dma::HDma::default(),
+            speed: speed::SpeedSwitch::default(),
+            sgb: Sgb::default(),
+            stopped: false,
+            joypad_lines: 0,
         }
     }
 
+    pub fn set_cgb_mode(&mut self, cgb_mode: bool) {
+        self.cgb_mode = cgb_mode;
+        self.ppu.set_cgb_mode(cgb_mode);
+    }
+    pub fn cgb_mode(&self) -> bool {
+        self.cgb_mode
+    }
+
+    // waiting for a button after STOP
+    pub fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    pub fn set_sgb_mode(&mut self, sgb_mode: bool) {
+        self.sgb.set_enabled(sgb_mode);
+    }
+
     pub fn read_byte(&self, address: u16) -> u8 {
         if address < 0x8000 {
             self.cartridge.read_cart(address)
         } else if 0x8000 <= address && address <= 0x9FFF {
             self.ppu.read_vram(address)
+        } else if (0xC000..=0xFDFF).contains(&address) {
+            self.wram[self.wram_index(address)]
         } else if 0xFE00 <= address && address <= 0xFE9F {
             if self.oam_dma.in_transfer {0xFF} else {
                 self.ppu.read_oam(address)
@@ -88,6 +137,9 @@ impl MMU {
     pub fn write_byte(&mut self, address: u16, value: u8) {
         if 0x8000 <= address && address <= 0x9FFF {
             self.ppu.write_vram(address, value);
+        } else if (0xC000..=0xFDFF).contains(&address) {
+            let index = self.wram_index(address);
+            self.wram[index] = value;
         } else if 0xFE00 <= address && address <= 0xFE9F {
             //println!("{}", self.oam_dma.in_transfer);
             if !self.oam_dma.in_transfer {
@@ -104,6 +156,16 @@ impl MMU {
     }
 
 
+    // 0xE000-0xFDFF echoes 0xC000-0xDDFF, 0xD000-0xDFFF is the SVBK bank
+    fn wram_index(&self, address: u16) -> usize {
+        let address = if address >= 0xE000 {address - 0x2000} else {address};
+        if address < 0xD000 {
+            (address - 0xC000) as usize
+        } else {
+            self.wram_bank as usize * 0x1000 + (address - 0xD000) as usize
+        }
+    }
+
     pub fn read_io(&self, address: u16) -> u8 {
         match address {
             0xFF00 => self.joypad.read_joypad(),
@@ -114,12 +176,26 @@ impl MMU {
             0xFF46 => self.oam_dma.read_register(),
             0xFF47..=0xFF4B => self.ppu.lcd.lcd_read(address),
             0xFF0F => self.interrupts.read_requested(),
+            0xFF4D if self.cgb_mode => self.speed.read_register(),
+            0xFF4F if self.cgb_mode => self.ppu.read_vram_bank(),
+            0xFF51..=0xFF54 if self.cgb_mode => 0xFF,
+            0xFF55 if self.cgb_mode => self.hdma.read_control(),
+            0xFF68..=0xFF6B if self.cgb_mode => self.ppu.lcd.lcd_read(address),
+            0xFF70 if self.cgb_mode => 0xF8 | self.wram_bank,
             _ => self.ram[address as usize],
         }
     }
     pub fn write_io(&mut self, address: u16, value: u8) {
         match address {
-            0xFF00 => self.joypad.write_joypad(value),
+            0xFF00 => {
+                self.joypad.write_joypad(value);
+                if let Some(packet) = self.joypad.take_sgb_packet() {
+                    // the SGB only listens to games that say they support it
+                    if self.sgb.enabled() && self.cartridge.supports_sgb() {
+                        self.sgb.handle_packet(packet, &mut self.joypad, self.ppu.current_frame);
+                    }
+                }
+            },
             0xFF01 => self.serial.write_data(value),
             0xFF02 => self.serial.write_control(value),
             0xFF04 => self.timer.write_div(),
@@ -130,6 +206,16 @@ impl MMU {
             0xFF46 => self.oam_dma.dma_start(value),
             0xFF47..=0xFF4B => self.ppu.lcd.lcd_write(address, value),
             0xFF0F => self.interrupts.write_requested(value),
+            0xFF4D if self.cgb_mode => self.speed.write_register(value),
+            0xFF4F if self.cgb_mode => self.ppu.write_vram_bank(value),
+            0xFF51 if self.cgb_mode => self.hdma.write_source_high(value),
+            0xFF52 if self.cgb_mode => self.hdma.write_source_low(value),
+            0xFF53 if self.cgb_mode => self.hdma.write_destination_high(value),
+            0xFF54 if self.cgb_mode => self.hdma.write_destination_low(value),
+            0xFF55 if self.cgb_mode => self.hdma.write_control(value),
+            0xFF68..=0xFF6B if self.cgb_mode => self.ppu.lcd.lcd_write(address, value),
+            // bank 0 selects bank 1
+            0xFF70 if self.cgb_mode => self.wram_bank = (value & 0b111).max(1),
             _ => self.ram[address as usize] = value,
         };
     }
@@ -176,24 +262,115 @@ impl MMU {
         }
     }
 }
+impl Bus for MMU {
+    fn read(&mut self, address: u16) -> u8 {
+        self.read_byte(address)
+    }
+    fn write(&mut self, address: u16, value: u8) {
+        self.write_byte(address, value);
+    }
+
+    fn tick(&mut self, m_cycles: u8) {
+        // the PPU runs at the same rate in both speeds, so in double speed
+        // it only gets 2 dots per M-cycle while the timer keeps getting 4
+        let ppu_dots = if self.speed.double_speed() {2} else {4};
+        for _ in 0..m_cycles {
+            let lines = self.joypad.low_lines();
+            if lines & !self.joypad_lines != 0 {
+                self.interrupts.request_interrupt(InterruptType::Joypad);
+                self.stopped = false;
+            }
+            self.joypad_lines = lines;
+            // the clock is off, DIV and the LCD don't move either
+            if self.stopped {
+                continue;
+            }
+
+            self.speed.tick();
+            for tick in 0..4 {
+                self.timer.ticks(&mut self.interrupts);
+                if tick < ppu_dots {
+                    self.ppu.ppu_ticks(&mut self.interrupts)
+                }
+            }
+            self.dma_tick();
+        }
+    }
+
+    fn interrupt_enable(&self) -> u8 {
+        self.interrupts.read_enable()
+    }
+    fn interrupt_flags(&self) -> u8 {
+        self.interrupts.read_requested()
+    }
+    fn acknowledge_interrupt(&mut self, interrupt: InterruptType) {
+        self.interrupts.clear_request(interrupt);
+    }
+
+    fn cpu_stalled(&self) -> bool {
+        self.hdma.cpu_stalled() || self.stopped || self.speed.stalled()
+    }
+    fn button_held(&self) -> bool {
+        self.joypad.low_lines() != 0
+    }
+    // DIV resets either way. A CGB with KEY1 armed switches speed and comes
+    // back by itself, anything else waits for a button
+    fn stop(&mut self) {
+        self.timer.write_div();
+        if !(self.cgb_mode && self.speed.try_switch()) {
+            self.stopped = true;
+        }
+    }
+}
+
 impl DmaTransfer for MMU {
     fn dma_tick(&mut self) {
         //println!("in_transfer:{}", self.oam_dma.in_transfer);
         
         if self.oam_dma.in_transfer {
-            println!("dma_write");
             if self.oam_dma.start_delay > 0 {
                 self.oam_dma.start_delay -= 1;
             } else {
                 // don't need the 0xFE00 but why not
                 let new_address = 0xFE00 | self.oam_dma.address_byte as u16;
                 let read_address = (self.oam_dma.value as u16 * 0x100) + self.oam_dma.address_byte as u16;
-                println!("write_oam:{:#X}, read_byte:{:#X}:{:#X}", new_address, read_address, self.read_byte(read_address));
                 self.ppu.write_oam(new_address, self.read_byte(read_address));
                 self.oam_dma.address_byte += 1;
                 self.oam_dma.in_transfer = self.oam_dma.address_byte < 0xA0;
                 //println!("transfer:{}", self.oam_dma.in_transfer);
             }
         }
+
+        if self.cgb_mode {
+            self.hdma_tick();
+        }
+
+        if self.sgb.transfer_pending(self.ppu.current_frame) {
+            let data = self.ppu.screen_tile_data();
+            self.sgb.finish_transfer(&data);
+        }
+    }
+}
+
+impl MMU {
+    // one M-cycle of VRAM DMA. A 16 byte block costs 8 M-cycles in normal
+    // speed and 16 in double speed, the copy rate is tied to the PPU clock.
+    fn hdma_tick(&mut self) {
+        self.hdma.hblank_update(self.ppu.in_hblank());
+
+        let bytes = if self.speed.double_speed() {1} else {2};
+        for _ in 0..bytes {
+            if !self.hdma.cpu_stalled() {
+                break;
+            }
+            let value = self.read_byte(self.hdma.source);
+            self.ppu.write_vram(0x8000 | (self.hdma.destination & 0x1FFF), value);
+
+            self.hdma.source = self.hdma.source.wrapping_add(1);
+            self.hdma.destination = self.hdma.destination.wrapping_add(1);
+            self.hdma.remaining -= 1;
+            self.hdma.block_left -= 1;
+            self.hdma.active = self.hdma.remaining > 0;
+        }
     }
 }
@@ -21,4 +21,68 @@ impl OamDma {
     pub fn read_register(&self) -> u8 {
         self.address_byte
     }
-}
\ No newline at end of file
+}
+
+// CGB VRAM DMA, HDMA1-HDMA5 (0xFF51-0xFF55)
+// General purpose DMA copies everything at once, HBlank DMA copies 16 bytes
+// at the start of every HBlank. The CPU is stopped while a block is copied.
+#[derive(Default)]
+pub struct HDma {
+    pub(super) source: u16,
+    pub(super) destination: u16,
+    pub(super) remaining: u16,
+    pub(super) block_left: u16,
+    pub(super) hblank_mode: bool,
+    pub(super) active: bool,
+    pub(super) in_hblank: bool,
+}
+
+impl HDma {
+    pub fn write_source_high(&mut self, value: u8) {
+        self.source = (self.source & 0x00F0) | ((value as u16) << 8);
+    }
+    pub fn write_source_low(&mut self, value: u8) {
+        self.source = (self.source & 0xFF00) | (value & 0xF0) as u16;
+    }
+    pub fn write_destination_high(&mut self, value: u8) {
+        self.destination = (self.destination & 0x00F0) | (((value & 0x1F) as u16) << 8);
+    }
+    pub fn write_destination_low(&mut self, value: u8) {
+        self.destination = (self.destination & 0x1F00) | (value & 0xF0) as u16;
+    }
+
+    // HDMA5
+    // Bit7   Mode (0=General purpose, 1=HBlank)
+    // Bit6-0 Length in blocks of 16 bytes, minus one
+    pub fn write_control(&mut self, value: u8) {
+        if self.active && self.hblank_mode && value & 0x80 == 0 {
+            // writing 0 to bit 7 cancels a running HBlank DMA
+            self.active = false;
+            return;
+        }
+        self.remaining = ((value & 0x7F) as u16 + 1) * 16;
+        self.hblank_mode = value & 0x80 != 0;
+        self.block_left = if self.hblank_mode {0} else {self.remaining};
+        self.active = true;
+        // if we are already in HBlank the first block goes right away
+        self.in_hblank = false;
+    }
+
+    // Bit7 is 0 while active. Once finished it reads 0xFF, after a cancel it
+    // reads 0x80 with the blocks that were left.
+    pub fn read_control(&self) -> u8 {
+        let blocks = ((self.remaining / 16) as u8).wrapping_sub(1) & 0x7F;
+        if self.active {blocks} else {0x80 | blocks}
+    }
+
+    pub fn hblank_update(&mut self, in_hblank: bool) {
+        if self.active && self.hblank_mode && in_hblank && !self.in_hblank {
+            self.block_left = 16;
+        }
+        self.in_hblank = in_hblank;
+    }
+
+    pub fn cpu_stalled(&self) -> bool {
+        self.active && self.block_left > 0
+    }
+}
@@ -25,6 +25,18 @@ impl TryFrom<u8> for InterruptType {
     }
 }
 
+impl InterruptType {
+    pub fn vector(self) -> u16 {
+        match self {
+            InterruptType::Vblank => 0x40,
+            InterruptType::LcdStat => 0x48,
+            InterruptType::Timer => 0x50,
+            InterruptType::Serial => 0x58,
+            InterruptType::Joypad => 0x60,
+        }
+    }
+}
+
 bitflags! {
     struct InterruptFlags: u8 {
         const VBLANK   = 1 << 0;
@@ -121,13 +133,7 @@ impl Interrupts {
     }
 
     pub fn interrupt_addresses(&mut self, int_type: InterruptType) -> u16 {
-        match int_type {
-            InterruptType::Vblank => 0x40,
-            InterruptType::LcdStat => 0x48,
-            InterruptType::Timer => 0x50,
-            InterruptType::Serial => 0x58,
-            InterruptType::Joypad => 0x60,
-        }
+        int_type.vector()
     }
     /*
     pub fn interrupt_handle(&mut self, cpu:&mut CPU, mmu:&mut MMU) {
@@ -166,4 +172,7 @@ impl Interrupts {
     pub fn request_interrupt(&mut self, interrupt: InterruptType) {
         self.requested.insert(interrupt.into());
     }
+    pub fn clear_request(&mut self, interrupt: InterruptType) {
+        self.requested.remove(interrupt.into());
+    }
 }
\ No newline at end of file
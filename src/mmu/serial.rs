@@ -12,6 +12,15 @@ bitflags! {
 pub struct Serial {
     data: u8,
     control: SerialControl,
+    // every byte the game started sending with the internal clock,
+    // test ROMs print their results this way
+    output: Vec<u8>,
+}
+
+impl Default for Serial {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Serial {
@@ -19,6 +28,7 @@ impl Serial {
         Serial { 
             data: 0, 
             control: SerialControl::from_bits_truncate(0),
+            output: Vec::new(),
         }
     }
     pub fn get_data(&self) -> u8 {
@@ -32,5 +42,14 @@ impl Serial {
     }
     pub fn write_control(&mut self, value: u8) {
         self.control = SerialControl::from_bits_truncate(value);
+        if self.control.contains(SerialControl::START_FLAG | SerialControl::SHIFT_CLOCK) {
+            self.output.push(self.data);
+        }
+    }
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+    pub fn output_text(&self) -> String {
+        String::from_utf8_lossy(&self.output).into_owned()
     }
 }
\ No newline at end of file
@@ -0,0 +1,47 @@
+// KEY1 (0xFF4D) **CGB Mode Only**
+// Bit7 Current speed     (0=Normal, 1=Double) (Read Only)
+// Bit0 Prepare switch    (0=No, 1=Prepare)
+// The switch itself happens when STOP is executed with bit 0 set.
+
+// M-cycles the CPU sits still while the clock settles
+const SWITCH_STALL: u16 = 2050;
+
+#[derive(Default)]
+pub struct SpeedSwitch {
+    pub(super) double_speed: bool,
+    pub(super) prepare: bool,
+    pub(super) stall: u16,
+}
+
+impl SpeedSwitch {
+    pub fn double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    pub fn read_register(&self) -> u8 {
+        ((self.double_speed as u8) << 7) | 0x7E | self.prepare as u8
+    }
+
+    pub fn write_register(&mut self, value: u8) {
+        self.prepare = value & 0x01 != 0;
+    }
+
+    // called by STOP, returns true if the speed actually changed
+    pub fn try_switch(&mut self) -> bool {
+        if self.prepare {
+            self.double_speed = !self.double_speed;
+            self.prepare = false;
+            self.stall = SWITCH_STALL;
+            return true;
+        }
+        false
+    }
+
+    pub fn stalled(&self) -> bool {
+        self.stall > 0
+    }
+
+    pub fn tick(&mut self) {
+        self.stall = self.stall.saturating_sub(1);
+    }
+}
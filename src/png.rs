@@ -0,0 +1,249 @@
+use std::fs::{self, File};
+use std::io::Write;
+
+mod inflate;
+
+// Just enough of PNG to save screenshots: 8 bit RGB, no filtering, and the
+// zlib stream uses stored blocks so there's no compressor to carry around.
+// The files come out about as big as the raw pixels, fine for 160x144.
+//
+// Reading is less picky since reference images come from other tools: any
+// colour type, bit depths up to 8 (16 is cut down), all filters, but no
+// interlacing.
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+// pixels are 0xRRGGBB like video_buffer, every pixel becomes a scale x scale square
+pub fn encode(width: usize, height: usize, pixels: &[u32], scale: usize) -> Vec<u8> {
+    assert_eq!(pixels.len(), width * height, "pixel buffer doesn't match {}x{}", width, height);
+    let scale = scale.max(1);
+    let (out_width, out_height) = (width * scale, height * scale);
+
+    // every row starts with its filter type, 0 = none
+    let mut raw = Vec::with_capacity(out_height * (1 + out_width * 3));
+    for y in 0..out_height {
+        raw.push(0);
+        for x in 0..out_width {
+            let color = pixels[(y / scale) * width + x / scale];
+            raw.extend_from_slice(&[(color >> 16) as u8, (color >> 8) as u8, color as u8]);
+        }
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(out_width as u32).to_be_bytes());
+    header.extend_from_slice(&(out_height as u32).to_be_bytes());
+    // bit depth 8, colour type 2 (RGB), deflate, no filter method, no interlace
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_store(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+pub fn save(path: &str, width: usize, height: usize, pixels: &[u32], scale: usize) -> Result<(), String> {
+    let png = encode(width, height, pixels, scale);
+    let mut file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+    file.write_all(&png).map_err(|e| format!("{}: {}", path, e))
+}
+
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    // 0xRRGGBB, alpha is dropped
+    pub pixels: Vec<u32>,
+}
+
+pub fn load(path: &str) -> Result<Image, String> {
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    decode(&data).map_err(|e| format!("{}: {}", path, e))
+}
+
+pub fn decode(data: &[u8]) -> Result<Image, String> {
+    if data.len() < 8 || data[..8] != SIGNATURE {
+        return Err("not a PNG".to_string());
+    }
+
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut idat = Vec::new();
+    let mut pos = 8;
+    while pos + 8 <= data.len() {
+        let len = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+        let kind = &data[pos + 4..pos + 8];
+        let body = data.get(pos + 8..pos + 8 + len).ok_or("chunk runs past the end")?;
+        match kind {
+            b"IHDR" if len == 13 => header = Some(Header::parse(body)?),
+            b"PLTE" => palette = body.chunks_exact(3).map(|c| (c[0] as u32) << 16 | (c[1] as u32) << 8 | c[2] as u32).collect(),
+            b"IDAT" => idat.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        pos += 12 + len;
+    }
+    let header = header.ok_or("no IHDR")?;
+
+    // 2 bytes of zlib header in front, the adler32 at the end is ignored
+    let raw = inflate::inflate(idat.get(2..).ok_or("no image data")?)?;
+    let rows = unfilter(&header, &raw)?;
+
+    let mut pixels = Vec::with_capacity(header.width * header.height);
+    for row in rows.chunks(header.stride()) {
+        for x in 0..header.width {
+            pixels.push(header.pixel(row, x, &palette)?);
+        }
+    }
+    Ok(Image {width: header.width, height: header.height, pixels})
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: usize,
+    color_type: u8,
+}
+
+impl Header {
+    fn parse(body: &[u8]) -> Result<Header, String> {
+        let header = Header {
+            width: u32::from_be_bytes([body[0], body[1], body[2], body[3]]) as usize,
+            height: u32::from_be_bytes([body[4], body[5], body[6], body[7]]) as usize,
+            bit_depth: body[8] as usize,
+            color_type: body[9],
+        };
+        if body[12] != 0 {
+            return Err("interlaced PNGs aren't supported".to_string());
+        }
+        if ![1, 2, 4, 8, 16].contains(&header.bit_depth) || ![0, 2, 3, 4, 6].contains(&header.color_type) {
+            return Err(format!("bad bit depth {} / colour type {}", header.bit_depth, header.color_type));
+        }
+        Ok(header)
+    }
+
+    fn channels(&self) -> usize {
+        match self.color_type {
+            0 | 3 => 1, // grey, palette
+            4 => 2,     // grey + alpha
+            2 => 3,     // RGB
+            _ => 4,     // RGBA
+        }
+    }
+
+    fn stride(&self) -> usize {
+        (self.width * self.channels() * self.bit_depth).div_ceil(8)
+    }
+
+    // sample `channel` of pixel x, scaled to 8 bits unless it's a palette index
+    fn sample(&self, row: &[u8], x: usize, channel: usize) -> u32 {
+        let index = x * self.channels() + channel;
+        match self.bit_depth {
+            16 => row[index * 2] as u32,
+            8 => row[index] as u32,
+            depth => {
+                let bit = index * depth;
+                let max = (1 << depth) - 1;
+                let value = (row[bit / 8] >> (8 - depth - bit % 8)) as u32 & max;
+                if self.color_type == 3 {value} else {value * 255 / max}
+            }
+        }
+    }
+
+    fn pixel(&self, row: &[u8], x: usize, palette: &[u32]) -> Result<u32, String> {
+        Ok(match self.color_type {
+            0 | 4 => self.sample(row, x, 0) * 0x010101,
+            3 => *palette.get(self.sample(row, x, 0) as usize).ok_or("palette index out of range")?,
+            _ => self.sample(row, x, 0) << 16 | self.sample(row, x, 1) << 8 | self.sample(row, x, 2),
+        })
+    }
+}
+
+// undoes the per row filters, returns the rows without their filter bytes
+fn unfilter(header: &Header, raw: &[u8]) -> Result<Vec<u8>, String> {
+    let stride = header.stride();
+    // filters work on whole pixels, or bytes when a pixel is smaller
+    let bpp = (header.channels() * header.bit_depth).div_ceil(8);
+    if raw.len() < header.height * (stride + 1) {
+        return Err("not enough image data".to_string());
+    }
+
+    let mut out = vec![0u8; header.height * stride];
+    for y in 0..header.height {
+        let filter = raw[y * (stride + 1)];
+        let line = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+        for i in 0..stride {
+            let left = if i >= bpp {out[y * stride + i - bpp]} else {0};
+            let up = if y > 0 {out[(y - 1) * stride + i]} else {0};
+            let up_left = if y > 0 && i >= bpp {out[(y - 1) * stride + i - bpp]} else {0};
+            let predicted = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(format!("bad filter type {}", filter)),
+            };
+            out[y * stride + i] = line[i].wrapping_add(predicted);
+        }
+    }
+    Ok(out)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {a} else if pb <= pc {b} else {c}
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    // the CRC covers the type and the data, not the length
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn zlib_store(data: &[u8]) -> Vec<u8> {
+    // CMF/FLG: deflate with a 32K window, no dictionary, (0x78 << 8 | 0x01) % 31 == 0
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        /*
+            BFINAL in bit 0, BTYPE 00 = stored, then LEN and its
+            complement NLEN, both little endian
+        */
+        out.push(if blocks.peek().is_none() {0x01} else {0x00});
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {(crc >> 1) ^ 0xEDB8_8320} else {crc >> 1};
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
@@ -1,9 +1,15 @@
 pub mod sprite;
 pub mod lcd;
 pub mod fifo;
+pub mod palette;
+pub mod colorize;
+mod scanline;
+pub mod viewer;
+pub mod layers;
 
 use sprite::{Sprite, SelectedSprite};
-use fifo::{PixelFifo, FetchState};
+use fifo::{PixelFifo, FetchState, BgAttributes};
+use layers::{Layer, Layers};
 
 pub use crate::mmu::interrupts::{Interrupts, InterruptType};
 
@@ -12,43 +18,65 @@ use arrayvec::ArrayVec;
 pub const XRES: usize = 160; // already in state_machine. FIX THIS!!!
 pub const YRES: usize = 144;
 pub const SCALE: usize = 1;
-pub const TILE_COLORS: [u32; 4] = [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000];
 
 
+// Accurate runs the pixel FIFO every dot, Fast draws each line in one go
+// at the start of HBlank.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RenderMode {
+    Accurate,
+    Fast,
+}
+
 pub struct PPU {
     /*mode: u8,
     mode_clock: usize,
     background_buffer: Vec<u32>,
     viewport: Vec<u32>,*/
-    vram: [u8; 0x2000],
+    vram: [u8; 0x4000], // two 8KiB banks, bank 1 only exists on CGB
+    vram_bank: u8,
+    cgb_mode: bool,
+    render_mode: RenderMode,
+    layers: Layers,
     oam: [Sprite; 40],
     selected_oam: ArrayVec<SelectedSprite, 10>,
     sprite_count: u8,
-    fetched_sprites: [Sprite; 3],
+    fetched_sprites: [Sprite; 10],
     fetched_sprite_count: u8,
     pub current_frame: u32,
+    // WY matched LY at some point this frame, the window can start from now on
+    window_y_triggered: bool,
+    // lines the window actually drew this frame, picks the window map row
     window_line: u8,
     line_ticks: u32,
     pub(super) pixel_fifo: PixelFifo,
     pub(super) lcd: lcd::Lcd,
     pub(super) video_buffer: Vec<u32>,
+    // the DMG shade of every pixel, what the SGB colours
+    pub(super) shade_buffer: Vec<u8>,
 }
 
 impl Default for PPU {
     fn default() -> Self {
         let mut ppu = Self {
-            vram: [0; 0x2000],
+            vram: [0; 0x4000],
+            vram_bank: 0,
+            cgb_mode: false,
+            render_mode: RenderMode::Accurate,
+            layers: Layers::default(),
             oam: [Sprite::default(); 40],
             selected_oam: ArrayVec::<_, 10>::new(),
             sprite_count: 0,
-            fetched_sprites: [Sprite::default(); 3],
+            fetched_sprites: [Sprite::default(); 10],
             fetched_sprite_count: 0,
             current_frame: 0,
+            window_y_triggered: false,
             window_line: 0,
             line_ticks: 400,
             pixel_fifo: PixelFifo::default(),
             lcd: lcd::Lcd::default(),
             video_buffer: vec![0; XRES * YRES],
+            shade_buffer: vec![0; XRES * YRES],
             //add more
         };
         ppu.lcd.lcd_write(0xFF47, 0xFC);
@@ -61,26 +89,96 @@ impl Default for PPU {
 impl PPU {
     pub fn new() -> PPU {
         PPU {
-            vram: [0; 0x2000],
+            vram: [0; 0x4000],
+            vram_bank: 0,
+            cgb_mode: false,
+            render_mode: RenderMode::Accurate,
+            layers: Layers::default(),
             oam: [Sprite::default(); 40],
             selected_oam: ArrayVec::<_, 10>::new(),
             sprite_count: 0,
-            fetched_sprites: [Sprite::default(); 3],
+            fetched_sprites: [Sprite::default(); 10],
             fetched_sprite_count: 0,
             current_frame: 0,
+            window_y_triggered: false,
             window_line: 0,
             line_ticks: 0,
             pixel_fifo: PixelFifo::default(),
             lcd: lcd::Lcd::new(),
             video_buffer: vec![0; XRES * YRES],
+            shade_buffer: vec![0; XRES * YRES],
+        }
+    }
+
+    pub fn video_buffer(&self) -> &[u32] {
+        &self.video_buffer
+    }
+    pub fn shade_buffer(&self) -> &[u8] {
+        &self.shade_buffer
+    }
+
+    // the current screen as a PNG, scale 1 is the plain 160x144
+    pub fn screenshot(&self, scale: usize) -> Vec<u8> {
+        crate::png::encode(XRES, YRES, &self.video_buffer, scale)
+    }
+    pub fn save_screenshot(&self, path: &str, scale: usize) -> Result<(), String> {
+        crate::png::save(path, XRES, YRES, &self.video_buffer, scale)
+    }
+
+    pub fn dmg_colors(&self) -> palette::DmgColors {
+        self.lcd.dmg_colors()
+    }
+    pub fn set_dmg_colors(&mut self, colors: palette::DmgColors) {
+        self.lcd.set_dmg_colors(colors);
+    }
+
+    pub fn render_mode(&self) -> RenderMode {
+        self.render_mode
+    }
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) {
+        self.render_mode = render_mode;
+    }
+
+    pub fn layers(&self) -> Layers {
+        self.layers
+    }
+    pub fn layers_mut(&mut self) -> &mut Layers {
+        &mut self.layers
+    }
+
+    // what a hidden BG or window pixel draws as
+    fn hidden_bg_color(&self) -> u32 {
+        if self.cgb_mode {
+            self.lcd.cgb_bg_palette.color(0, 0)
+        } else {
+            self.lcd.bg_colors[0]
         }
     }
 
+    pub fn set_cgb_mode(&mut self, cgb_mode: bool) {
+        self.cgb_mode = cgb_mode;
+    }
+
     pub fn read_vram(&self, address: u16) -> u8 {
-        self.vram[(address - 0x8000) as usize]
+        self.vram_at(self.vram_bank, address)
     }
     pub fn write_vram(&mut self, address: u16, value:u8) {
-        self.vram[(address - 0x8000) as usize] = value;
+        self.vram[self.vram_bank as usize * 0x2000 + (address - 0x8000) as usize] = value;
+    }
+
+    // the fetcher picks its bank from the tile attributes, not from VBK
+    fn vram_at(&self, bank: u8, address: u16) -> u8 {
+        self.vram[bank as usize * 0x2000 + (address - 0x8000) as usize]
+    }
+
+    // VBK (0xFF4F)
+    pub fn read_vram_bank(&self) -> u8 {
+        0xFE | self.vram_bank
+    }
+    pub fn write_vram_bank(&mut self, value: u8) {
+        if self.cgb_mode {
+            self.vram_bank = value & 0x01;
+        }
     }
 
     pub fn read_oam(&self, address: u16) -> u8 {
@@ -90,10 +188,33 @@ impl PPU {
     }
     pub fn write_oam(&mut self, address: u16, data:u8) {
         let index = (address & 0xFF) as usize;
-        println!("write, index:{:#X}, i2:{}, i3:{}, data:{}", index, index/4, index%4, data);
         self.oam[index / 4].set_at_offset((index % 4) as u8, data);
     }
 
+    // What an SGB VRAM transfer sees: the tiles of the first 256 BG map
+    // entries on screen (20 per row), 16 bytes each.
+    pub fn screen_tile_data(&self) -> [u8; 0x1000] {
+        let control = self.lcd.lcd_control;
+        let mut data = [0; 0x1000];
+
+        for (tile, chunk) in data.chunks_exact_mut(16).enumerate() {
+            let map_address = control.bg_tilemap() + (tile / 20 * 32 + tile % 20) as u16;
+            let mut tile_num = self.vram_at(0, map_address);
+            if control.bg_window_tile_data() == 0x8800 {
+                tile_num = tile_num.wrapping_add(0x80);
+            }
+            let tile_address = control.bg_window_tile_data() + tile_num as u16 * 16;
+            for (i, byte) in chunk.iter_mut().enumerate() {
+                *byte = self.vram_at(0, tile_address + i as u16);
+            }
+        }
+        data
+    }
+
+    pub fn in_hblank(&self) -> bool {
+        self.lcd.lcd_status.current_mode() == 0 && (self.lcd.ly as usize) < YRES
+    }
+
     pub fn ppu_ticks(&mut self, interrupt: &mut Interrupts) {
         self.line_ticks += 1;
         match self.lcd.lcd_status.current_mode() {
@@ -108,7 +229,11 @@ impl PPU {
     pub fn pipeline_process(&mut self) {
         self.pixel_fifo.map_y = self.lcd.ly.wrapping_add(self.lcd.scroll_y);
         self.pixel_fifo.map_x = self.pixel_fifo.fetch_x.wrapping_add(self.lcd.scroll_x);
-        self.pixel_fifo.tile_y = ((self.lcd.ly.wrapping_add(self.lcd.scroll_y)) % 8) * 2;
+        self.pixel_fifo.tile_y = if self.pixel_fifo.window_active {
+            (self.window_line % 8) * 2
+        } else {
+            ((self.lcd.ly.wrapping_add(self.lcd.scroll_y)) % 8) * 2
+        };
 
         if self.line_ticks & 0x1 == 0 {
             self.pipeline_fetch();
@@ -130,22 +255,21 @@ impl PPU {
     pub fn fetch_tile(&mut self) {
         self.fetched_sprite_count = 0;
 
-        if self.lcd.lcd_control.bg_window_priority() {
+        // on CGB LCDC bit 0 only takes away the BG priority, the BG is still drawn
+        if self.cgb_mode || self.lcd.lcd_control.bg_window_priority() {
             /* self.pixel_fifo.tile_x = ((self.lcd.scroll_x / 8) + self.pixel_fifo.fetch_x) & 0x1F;
             self.pixel_fifo.tile_y = self.lcd.ly.wrapping_add(self.lcd.scroll_y);
 
             let tile_index = (self.pixel_fifo.tile_x as u16).wrapping_add(32 * ((self.pixel_fifo.tile_y / 8) as u16)); */
 
-            self.pixel_fifo.bgw_fetch_data[0] = self.read_vram(self.lcd.lcd_control.bg_tilemap()
-                + (self.pixel_fifo.map_x / 8) as u16 + ((self.pixel_fifo.map_y / 8) as u16 * 32) as u16);
-            /* self.pixel_fifo.bgw_fetch_data[0] = self.read_vram(self.lcd.lcd_control.bg_tilemap()
-                + tile_index); */
-            
-            if self.lcd.lcd_control.bg_window_tile_data() == 0x8800 {
-                self.pixel_fifo.bgw_fetch_data[0] = self.pixel_fifo.bgw_fetch_data[0].wrapping_add(0x80);
+            if self.pixel_fifo.window_active {
+                self.fetch_map_entry(self.lcd.lcd_control.window_tilemap()
+                    + self.pixel_fifo.window_tile_x as u16 + (self.window_line / 8) as u16 * 32);
+                self.pixel_fifo.window_tile_x = (self.pixel_fifo.window_tile_x + 1) & 0x1F;
+            } else {
+                self.fetch_map_entry(self.lcd.lcd_control.bg_tilemap()
+                    + (self.pixel_fifo.map_x / 8) as u16 + ((self.pixel_fifo.map_y / 8) as u16 * 32));
             }
-
-            self.pipeline_load_window_tile();
         }
         //println!("selected_oam:{}", self.selected_oam.len());
         if self.lcd.lcd_control.sprite_enable() && !self.selected_oam.is_empty() {
@@ -157,10 +281,28 @@ impl PPU {
         self.pixel_fifo.fetch_x += 8;
     }
 
+    // reads the tile number and, on CGB, its attributes from bank 1
+    fn fetch_map_entry(&mut self, map_address: u16) {
+        self.pixel_fifo.bgw_fetch_data[0] = self.vram_at(0, map_address);
+
+        if self.lcd.lcd_control.bg_window_tile_data() == 0x8800 {
+            self.pixel_fifo.bgw_fetch_data[0] = self.pixel_fifo.bgw_fetch_data[0].wrapping_add(0x80);
+        }
+
+        self.pixel_fifo.bgw_fetch_attr = if self.cgb_mode {
+            BgAttributes::from_bits_truncate(self.vram_at(1, map_address))
+        } else {
+            BgAttributes::empty()
+        };
+    }
+
     pub fn fetch_data(&mut self, row: u8) {
-        self.pixel_fifo.bgw_fetch_data[row as usize + 1] = self.read_vram(
+        let attr = self.pixel_fifo.bgw_fetch_attr;
+        let tile_y = if attr.y_flip() {14 - self.pixel_fifo.tile_y} else {self.pixel_fifo.tile_y};
+
+        self.pixel_fifo.bgw_fetch_data[row as usize + 1] = self.vram_at(attr.vram_bank(),
             self.lcd.lcd_control.bg_window_tile_data() + self.pixel_fifo.bgw_fetch_data[0] as u16 * 16
-            + (self.pixel_fifo.tile_y + row) as u16);
+            + (tile_y + row) as u16);
 
         self.pipeline_load_sprite_data(row);
         
@@ -175,8 +317,27 @@ impl PPU {
         if self.pixel_fifo.bgfifo.length() <= 8 {
             let x  = self.pixel_fifo.fetch_x as i16 - (8 - self.lcd.scroll_x % 8) as i16;
 
+            let attr = self.pixel_fifo.bgw_fetch_attr;
+
             for i in 0..8 {
-                let bit = 7 - i;
+                if self.pixel_fifo.window_active {
+                    if self.pixel_fifo.window_skip > 0 {
+                        self.pixel_fifo.window_skip -= 1;
+                        continue;
+                    }
+                } else if let Some((start, skip)) = self.window_start() {
+                    // the rest of this BG tile is dropped and the fetcher
+                    // starts over on the window
+                    if self.pixel_fifo.fifo_x >= start {
+                        self.pixel_fifo.window_active = true;
+                        self.pixel_fifo.window_tile_x = 0;
+                        self.pixel_fifo.window_skip = skip;
+                        self.pixel_fifo.fetch_x = self.pixel_fifo.fifo_x;
+                        break;
+                    }
+                }
+
+                let bit = if attr.x_flip() {i} else {7 - i};
                 let hi: bool = self.pixel_fifo.bgw_fetch_data[2] & (1 << bit) != 0;
                 let low: bool = self.pixel_fifo.bgw_fetch_data[1] & (1 << bit) != 0;
 
@@ -184,24 +345,34 @@ impl PPU {
                 if hi {color |= 0b10;}
                 if low {color |= 0b01;}
 
+                let mut bg_color:u32 = if self.cgb_mode {
+                    self.lcd.cgb_bg_palette.color(attr.palette(), color)
+                } else if self.lcd.lcd_control.bg_window_priority() {
+                    self.lcd.bg_colors[color as usize]
+                } else {
+                    self.lcd.bg_colors[0]
+                };
+
+                // LCDC bit 0 blanks the BG on DMG, on CGB it just lets objects win
                 if !self.lcd.lcd_control.bg_window_priority() {
                     color = 0x0;
                 }
 
-                let mut bg_color:u32 = self.lcd.bg_colors[color as usize];
-
-                let sprite_enable = self.lcd.lcd_control.sprite_enable();
+                let layer = if self.pixel_fifo.window_active {Layer::Window} else {Layer::Background};
+                if !self.layers.shows(layer) {
+                    color = 0x0;
+                    bg_color = self.hidden_bg_color();
+                }
 
-                let display_color: u32;
+                let sprite_enable = self.lcd.lcd_control.sprite_enable() && self.layers.objects;
 
                 if x >= 0 {
-                    display_color = if sprite_enable {
-                        let (priority, sp_color) = self.fetch_sprite_pixels(color, &mut bg_color);
-                        sp_color
-                    } else {
-                        bg_color
+                    let sprite = if sprite_enable {self.fetch_sprite_pixels(color, attr.priority())} else {None};
+                    let (display_color, shade) = match sprite {
+                        Some((sp_color, sp_shade)) => (self.layers.tint(sp_color, Layer::Objects), sp_shade),
+                        None => (self.layers.tint(bg_color, layer), self.lcd.bg_shade(color)),
                     };
-                    self.pixel_fifo.bgfifo.push(display_color, true);
+                    self.pixel_fifo.bgfifo.push(display_color, shade, true);
                     
                     self.pixel_fifo.fifo_x += 1;
                 }
@@ -226,6 +397,7 @@ impl PPU {
                 let buffer_index = self.pixel_fifo.push_x as usize + self.lcd.ly as usize * XRES;
                 
                 self.video_buffer[buffer_index] = color;
+                self.shade_buffer[buffer_index] = bg_pixel.shade();
                 self.pixel_fifo.push_x = self.pixel_fifo.push_x.wrapping_add(1);
             }
             self.pixel_fifo.line_x = self.pixel_fifo.line_x.wrapping_add(1);
@@ -239,63 +411,44 @@ impl PPU {
     }
 
     pub fn load_sprites(&mut self) {
-        let cur_y = self.lcd.ly;
-        let size  = self.lcd.lcd_control.sprite_size();
-        //let mut count = 0;
-    
+        let cur_y = self.lcd.ly as u16 + 16;
+        let size  = self.lcd.lcd_control.sprite_size() as u16;
+
+        // OAM scan walks the 40 entries in order and keeps the first 10 that
+        // cover this line. X is not checked here, so sprites at X=0 or X>=168
+        // still use up a slot even though they never show on screen.
         for (i, &sprite) in self.oam.iter().enumerate() {
-            //if sprite.x() > 0 {println!("x:{}", sprite.x());}
-            if sprite.x() == 0 {
-                continue;
-            }
-            
-            if self.selected_oam.len() >= 10 {
+            if self.selected_oam.is_full() {
                 break;
             }
-            
-    
-            if (sprite.y() <= cur_y + 16) && (sprite.y() + size > cur_y + 16) {
-                //println!("selected:{}, spritex:{}, ly:{}, i:{}", self.selected_oam.len(), sprite.x(), cur_y, i);
-                if self.selected_oam.is_empty() || self.selected_oam[self.selected_oam.len() - 1].sprite().x() > sprite.x() {
-                    self.selected_oam.insert(0, SelectedSprite::new(sprite, i as u8));
-                    continue;
-                }
 
-                let mut c = 0;
-                while c < self.selected_oam.len() {
-                    //println!("c:{}", c);
-                    if c + 1 == self.selected_oam.len() {
-                        self.selected_oam.push(SelectedSprite::new(sprite, i as u8));
-                        break;
-                    }
-                    if self.selected_oam[c as usize].sprite().x() > sprite.x() {
-                        self.selected_oam.insert(c as usize, SelectedSprite::new(sprite, i as u8));
-                        break;
-                    }
-                    
-                    c += 1;
-                }
+            let sprite_y = sprite.y() as u16;
+            if sprite_y <= cur_y && sprite_y + size > cur_y {
+                self.selected_oam.push(SelectedSprite::new(sprite, i as u8));
             }
         }
+
+        // DMG drawing priority: the smaller X wins, and on equal X the lower
+        // OAM index wins. CGB only looks at the OAM index, which is the order
+        // the scan already produced.
+        if !self.cgb_mode {
+            self.selected_oam.sort_by_key(|selected| (selected.sprite().x(), selected.index()));
+        }
         self.sprite_count = self.selected_oam.len() as u8;
     }
 
     pub fn pipeline_load_sprite_tile(&mut self) {
-        for (i, &selected_sprite) in self.selected_oam.iter().enumerate() {
-            let sprite_x = selected_sprite.sprite().x();
-            let sp_x = (sprite_x - 8) + (self.lcd.scroll_x % 8);
+        // fifo_x rather than fetch_x, the window can start mid tile
+        let fifo_x = self.pixel_fifo.fifo_x as i16;
 
-            let inbound: bool = (sp_x >= self.pixel_fifo.fetch_x) && (sp_x < (self.pixel_fifo.fetch_x + 8));
-            let inbound2: bool = ((sp_x + 8) >= self.pixel_fifo.fetch_x) && ((sp_x + 8) < self.pixel_fifo.fetch_x + 8);
+        for &selected_sprite in self.selected_oam.iter() {
+            let sp_x = self.sprite_fifo_x(selected_sprite.sprite());
 
-            if inbound || inbound2 {
+            // keep every sprite that overlaps the 8 pixels of this fetch
+            if sp_x + 8 > fifo_x && sp_x < fifo_x + 8 {
                 self.fetched_sprites[self.fetched_sprite_count as usize] = selected_sprite.sprite();
                 self.fetched_sprite_count += 1;
             }
-
-            if self.fetched_sprite_count >= 3 {
-                break;
-            }
         }
     }
 
@@ -312,26 +465,24 @@ impl PPU {
 
             //println!("index:{}, ly:{}, offset:{}", tile_index, cur_y, offset);
 
-            let data = self.read_vram(0x8000 + (tile_index as u16 * 16) + tile_y as u16 + offset as u16);
+            let bank = if self.cgb_mode {self.fetched_sprites[i as usize].vram_bank()} else {0};
+            let data = self.vram_at(bank, 0x8000 + (tile_index as u16 * 16) + tile_y as u16 + offset as u16);
             self.pixel_fifo.fetch_sprite_data[((i * 2) + offset) as usize] = data;
             
         }
     }
 
-    pub fn fetch_sprite_pixels(&mut self, bg_color: u8, color: &mut u32) -> (bool, u32) {
-        for (i, &sprite) in self.fetched_sprites.iter().enumerate() {
-            if self.fetched_sprite_count == i as u8 {
-                break;
-            }
-            
-            let sp_x = sprite.x() - 8 + (self.lcd.scroll_x % 8);
-
-            if sp_x + 8 < self.pixel_fifo.fifo_x {
-                continue;
-            }
-
-            let offset: i16 = self.pixel_fifo.fifo_x as i16 - sp_x as i16;
-            if (offset < 0) || (offset > 7) {
+    // colour and shade of the sprite pixel drawn here, None if the BG wins
+    pub fn fetch_sprite_pixels(&mut self, bg_color: u8, bg_priority: bool) -> Option<(u32, u8)> {
+        let fifo_x = self.pixel_fifo.fifo_x as i16;
+
+        // fetched_sprites is already in priority order, so the first opaque
+        // pixel is the one that is drawn. Its BG priority flag is only checked
+        // afterwards: a lower priority sprite never shows through a higher one
+        // that lost to the background.
+        for (i, &sprite) in self.fetched_sprites[..self.fetched_sprite_count as usize].iter().enumerate() {
+            let offset = fifo_x - self.sprite_fifo_x(sprite);
+            if !(0..8).contains(&offset) {
                 continue;
             }
 
@@ -344,59 +495,60 @@ impl PPU {
             if hi {sp_color |= 0b10;}
             if lo {sp_color |= 0b01;}
 
-            let bg_priority = sprite.priority();
-
             if sp_color == 0 {
                 // transparent
                 continue;
             }
 
-            if !bg_priority || bg_color == 0 {
-                // false is sp2 and true is sp1
-                //let palette = if sprite.dmg_palette() {false} else {true};
-
-                let new_color = if sprite.dmg_palette() {
-                    self.lcd.sp2_colors[sp_color as usize]
-                } else {
-                    self.lcd.sp1_colors[sp_color as usize]
-                };
-
-                if sp_color > 0 {
-                    return (false, new_color);
-                }
+            if (sprite.priority() || bg_priority) && bg_color != 0 {
+                return None;
             }
+
+            let new_color = if self.cgb_mode {
+                self.lcd.cgb_obj_palette.color(sprite.cgb_palette(), sp_color)
+            } else if sprite.dmg_palette() {
+                self.lcd.sp2_colors[sp_color as usize]
+            } else {
+                self.lcd.sp1_colors[sp_color as usize]
+            };
+            return Some((new_color, self.lcd.sprite_shade(sprite.dmg_palette(), sp_color)));
         }
-       (true, *color)
+        None
+    }
+
+    // Left edge of a sprite in fifo_x coordinates. fifo_x also counts the
+    // SCX % 8 pixels that get thrown away at the start of the line, and
+    // sprites with X < 8 start off screen, hence the signed result.
+    fn sprite_fifo_x(&self, sprite: Sprite) -> i16 {
+        sprite.x() as i16 - 8 + (self.lcd.scroll_x % 8) as i16
     }
 
     pub fn selected_oam_reset(&mut self) {
         self.selected_oam = ArrayVec::<_, 10>::new();
     }
 
-    pub fn window_visible(&mut self) -> bool {
-        self.lcd.lcd_control.window_enable() && (self.lcd.window_x >= 0) && (self.lcd.window_x <= 166)
-            && (self.lcd.window_y >= 0) && (self.lcd.window_y < YRES as u8)
+    // WY is latched for the frame by the OAM scan, see mode_oam
+    pub fn check_window_y(&mut self) {
+        if self.lcd.ly == self.lcd.window_y {
+            self.window_y_triggered = true;
+        }
     }
 
-    pub fn pipeline_load_window_tile(&mut self) {
-        if self.window_visible() {
-            let win_x = self.lcd.window_x;
-            let win_y = self.lcd.window_y;
-
-            if (self.pixel_fifo.fetch_x + 7 >= win_x)
-                && (self.pixel_fifo.fetch_x + 7 < win_x + YRES as u8 + 14) && (self.lcd.ly >= win_y)
-                && (self.lcd.ly < win_y + XRES as u8) {
-                    
-                    let win_tile_y: u8  = self.window_line / 8;
-                    
-                    self.pixel_fifo.bgw_fetch_data[0] = self.read_vram(self.lcd.lcd_control.window_tilemap()
-                        + (self.pixel_fifo.fetch_x as u16 + 7 - win_x as u16)/8 + (win_tile_y as u16 * 32));
-                    
-                        if self.lcd.lcd_control.bg_window_tile_data() == 0x8800 {
-                        self.pixel_fifo.bgw_fetch_data[0] = self.pixel_fifo.bgw_fetch_data[0].wrapping_add(0x80);
-                    }
-            } 
+    // Where the window takes over in fifo_x coordinates, and how many of its
+    // pixels are cut off on the left. The window starts at WX - 7 on screen.
+    fn window_start(&self) -> Option<(u8, u8)> {
+        if !self.window_y_triggered || !self.lcd.lcd_control.window_enable() || self.lcd.window_x > 166 {
+            return None;
         }
+
+        let fine_x = self.lcd.scroll_x % 8;
+        Some(match self.lcd.window_x {
+            // WX=0 starts before the SCX % 8 pixels are thrown away, so the
+            // window gets shifted by them as well
+            0 => (0, 7),
+            wx @ 1..=6 => (fine_x, 7 - wx),
+            wx => (wx - 7 + fine_x, 0),
+        })
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,319 @@
+use crate::cartridge::Cartridge;
+use crate::joypad::{JoyPad, JoypadButtons};
+use super::palette::{rgb555_to_rgb888, DmgColors};
+
+/*
+ Colours the CGB boot rom gives to DMG games, ported from its tables. A game
+ published by Nintendo is looked up by its title checksum (sum of
+ 0x134-0x143). The first 65 checksums are unique, the last 14 are shared by
+ a few games each and the 4th letter of the title picks between them.
+ Everything else gets combination 0, the same as Right+A.
+ While the boot logo is shown the player can override it by holding a
+ direction plus optionally A or B.
+ */
+
+// RGB555, lightest first
+const PALETTES: [[u16; 4]; 30] = [
+    [0x7FFF, 0x32BF, 0x00D0, 0x0000], // 0 brown
+    [0x639F, 0x4279, 0x15B0, 0x04CB], // 1 dark brown
+    [0x7FFF, 0x6E31, 0x454A, 0x0000], // 2 dark blue
+    [0x7FFF, 0x1BEF, 0x0200, 0x0000], // 3 green
+    [0x7FFF, 0x421F, 0x1CF2, 0x0000], // 4 red
+    [0x7FFF, 0x5294, 0x294A, 0x0000], // 5 greyscale
+    [0x7FFF, 0x03FF, 0x012F, 0x0000], // 6 yellow
+    [0x7FFF, 0x03EF, 0x01D6, 0x0000],
+    [0x7FFF, 0x42B5, 0x3DC8, 0x0000],
+    [0x7E74, 0x03FF, 0x0180, 0x0000],
+    [0x67FF, 0x77AC, 0x1A13, 0x2D6B],
+    [0x7ED6, 0x4BFF, 0x2175, 0x0000],
+    [0x53FF, 0x4A5F, 0x7E52, 0x0000], // 12 pastel
+    [0x4FFF, 0x7ED2, 0x3A4C, 0x1CE0],
+    [0x03ED, 0x7FFF, 0x255F, 0x0000],
+    [0x036A, 0x021F, 0x03FF, 0x7FFF],
+    [0x7FFF, 0x01DF, 0x0112, 0x0000],
+    [0x231F, 0x035F, 0x00F2, 0x0009],
+    [0x7FFF, 0x03EA, 0x011F, 0x0000], // 18 green/red
+    [0x299F, 0x001A, 0x000C, 0x0000],
+    [0x7FFF, 0x027F, 0x001F, 0x0000],
+    [0x7FFF, 0x03E0, 0x0206, 0x0120],
+    [0x7FFF, 0x7EEB, 0x001F, 0x7C00],
+    [0x7FFF, 0x3FFF, 0x7E00, 0x001F],
+    [0x7FFF, 0x03FF, 0x001F, 0x0000], // 24 orange
+    [0x03FF, 0x001F, 0x000C, 0x0000],
+    [0x7FFF, 0x033F, 0x0193, 0x0000],
+    [0x0000, 0x4200, 0x037F, 0x7FFF], // 27 inverted
+    [0x7FFF, 0x7E8C, 0x7C00, 0x0000], // 28 blue
+    [0x7FFF, 0x1BEF, 0x6180, 0x0000], // 29 dark green
+];
+
+// Where each layer's 4 colours start in PALETTES, counted in colours. The
+// boot rom doesn't care about palette boundaries, a few combinations start
+// in the middle of one and run into the next.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Combination {
+    obj0: usize,
+    obj1: usize,
+    bg: usize,
+}
+
+const fn comb(obj0: usize, obj1: usize, bg: usize) -> Combination {
+    Combination { obj0: obj0 * 4, obj1: obj1 * 4, bg: bg * 4 }
+}
+
+// offsets in colours rather than palettes
+const fn raw(obj0: usize, obj1: usize, bg: usize) -> Combination {
+    Combination { obj0, obj1, bg }
+}
+
+const COMBINATIONS: [Combination; 51] = [
+    comb(4, 4, 29), // 0, Right+A and the default
+    comb(18, 18, 18), // 1, Right
+    comb(20, 20, 20),
+    comb(24, 24, 24), // 3, Down+A
+    comb(9, 9, 9),
+    comb(0, 0, 0), // 5, Up
+    comb(27, 27, 27), // 6, Right+B
+    comb(5, 5, 5), // 7, Left+B
+    comb(12, 12, 12), // 8, Down
+    comb(26, 26, 26),
+    comb(16, 8, 8), // 10
+    comb(4, 28, 28),
+    comb(4, 2, 2),
+    comb(3, 4, 4),
+    comb(4, 29, 29),
+    comb(28, 4, 28), // 15
+    comb(2, 17, 2),
+    comb(16, 16, 8),
+    comb(4, 4, 7),
+    comb(4, 4, 18),
+    comb(4, 4, 20), // 20
+    comb(19, 19, 9),
+    raw(4 * 4 - 1, 4 * 4 - 1, 11 * 4),
+    comb(17, 17, 2),
+    comb(4, 4, 2),
+    comb(4, 4, 3), // 25
+    comb(28, 28, 0),
+    comb(3, 3, 0),
+    comb(0, 0, 1), // 28, Up+B
+    comb(18, 22, 18),
+    comb(20, 22, 20), // 30
+    comb(24, 22, 24),
+    comb(16, 22, 8),
+    comb(17, 4, 13),
+    raw(28 * 4 - 1, 0, 14 * 4),
+    raw(28 * 4 - 1, 4 * 4, 15 * 4), // 35
+    comb(19, 22, 9),
+    comb(16, 28, 10),
+    comb(4, 23, 28),
+    comb(17, 22, 2),
+    comb(4, 0, 2), // 40, Left+A
+    comb(4, 28, 3),
+    comb(28, 3, 0),
+    comb(3, 28, 4), // 43, Up+A
+    comb(21, 28, 4),
+    comb(3, 28, 0), // 45
+    comb(25, 3, 28),
+    comb(0, 28, 8),
+    comb(4, 3, 28), // 48, Left
+    comb(28, 3, 6), // 49, Down+B
+    comb(4, 28, 29),
+];
+
+// checksums from here on are shared, see FOURTH_LETTERS
+const UNIQUE_CHECKSUMS: usize = 65;
+
+const CHECKSUMS: [u8; 79] = [
+    0x00,
+    0x88, // ALLEY WAY
+    0x16, // YAKUMAN
+    0x36, // BASEBALL
+    0xD1, // TENNIS
+    0xDB, // TETRIS
+    0xF2, // QIX
+    0x3C, // DR.MARIO
+    0x8C, // RADARMISSION
+    0x92, // F1RACE
+    0x3D, // YOSSY NO TAMAGO
+    0x5C,
+    0x58, // X
+    0xC9, // MARIOLAND2
+    0x3E, // YOSSY NO COOKIE
+    0x70, // ZELDA
+    0x1D,
+    0x59, // SUPERMARIOLAND3
+    0x69, // TETRIS FLASH
+    0x19, // DONKEY KONG
+    0x35, // MARIO'S PICROSS
+    0xA8,
+    0x14, // POKEMON RED
+    0xAA, // POKEMON GREEN
+    0x75, // PICROSS 2
+    0x95, // YOSSY NO PANEPON
+    0x99, // KIRAKIRA KIDS
+    0x34, // GAMEBOY GALLERY
+    0x6F, // POCKETCAMERA
+    0x15, // POKEMON YELLOW
+    0xFF, // BALLOON KID
+    0x97, // KINGOFTHEZOO
+    0x4B, // DMG FOOTBALL
+    0x90, // WORLD CUP
+    0x17, // OTHELLO
+    0x10, // SUPER RC PRO-AM
+    0x39, // DYNABLASTER
+    0xF7, // BOY AND BLOB GB2
+    0xF6, // MEGAMAN
+    0xA2, // STAR WARS-NOA
+    0x49, // KIRBY DREAM LAND
+    0x4E, // WAVERACE
+    0x43,
+    0x68, // LOLO2
+    0xE0, // YOSHI'S COOKIE
+    0x8B, // MYSTIC QUEST
+    0xF0,
+    0xCE, // TOPRANKINGTENNIS
+    0x0C, // MANSELL
+    0x29, // MEGAMAN3
+    0xE8, // SPACE INVADERS
+    0xB7, // GAME&WATCH
+    0x86, // DONKEYKONGLAND95
+    0x9A, // ASTEROIDS/MISCMD
+    0x52, // STREET FIGHTER 2
+    0x01, // DEFENDER/JOUST
+    0x9D, // KILLERINSTINCT95
+    0x71, // TETRIS BLAST
+    0x9C, // PINOCCHIO
+    0xBD,
+    0x5D, // BA.TOSHINDEN
+    0x6D, // NETTOU KOF 95
+    0x67,
+    0x3F, // TETRIS PLUS
+    0x6B, // DONKEYKONGLAND 3
+    // shared
+    0xB3, 0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4,
+];
+
+/*
+ The 4th letter for each shared checksum, in rows of 14 lined up with the
+ last 14 CHECKSUMS. A checksum found at index i checks row 0 first, then
+ i + 14 in row 1 and i + 28 in row 2; the one that matches is the index into
+ COMBINATION_PER_CHECKSUM.
+
+   0xB3 KIRBY2 (B), TETRIS ATTACK (R)   0x46 SUPER MARIOLAND (E), METROID2 (R)
+   0x28 GOLF (F)                        0xA5 SOLARSTRIKER (A)
+   0x27 KIRBY BLOCKBALL (B)             0x61 POKEMON BLUE (E), VEGAS STAKES (A)
+   0x18 DONKEYKONGLAND (K)              0xBF KID ICARUS ( )
+   0xF4 PAC-IN-TIME (-)
+ */
+const FOURTH_LETTERS: &[u8; 29] = b"BEFAARBEKEK R-URAR INAILICE R";
+
+// indices into COMBINATIONS, one per CHECKSUMS entry and then one per
+// FOURTH_LETTERS entry
+const COMBINATION_PER_CHECKSUM: [u8; 94] = [
+    0, 4, 5, 35, 34, 3, 31, 15, 10, 5,
+    19, 36, 7, 37, 30, 44, 21, 32, 31, 20,
+    5, 33, 13, 14, 5, 29, 5, 18, 9, 3,
+    2, 26, 25, 25, 41, 42, 26, 45, 42, 45,
+    36, 38, 26, 42, 30, 41, 34, 34, 5, 42,
+    6, 5, 33, 25, 42, 42, 40, 2, 16, 25,
+    42, 42, 5, 0, 39,
+    // row 0 of the shared ones
+    36, 22, 25, 6, 32, 12, 36, 11, 39, 18, 39, 24, 31, 50,
+    // row 1
+    17, 46, 6, 27, 0, 47, 41, 41, 0, 0, 19, 34, 23, 18,
+    // row 2
+    29,
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ButtonCombo {
+    Up,
+    UpA,
+    UpB,
+    Left,
+    LeftA,
+    LeftB,
+    Down,
+    DownA,
+    DownB,
+    Right,
+    RightA,
+    RightB,
+}
+
+impl ButtonCombo {
+    // a single direction has to be held, A wins over B like on the boot rom
+    pub fn from_joypad(joypad: &JoyPad) -> Option<ButtonCombo> {
+        let up = joypad.is_pressed(JoypadButtons::Up);
+        let down = joypad.is_pressed(JoypadButtons::Down);
+        let left = joypad.is_pressed(JoypadButtons::Left);
+        let right = joypad.is_pressed(JoypadButtons::Right);
+        let a = joypad.is_pressed(JoypadButtons::A);
+        let b = joypad.is_pressed(JoypadButtons::B);
+
+        let combo = match (up, down, left, right) {
+            (true, false, false, false) => [ButtonCombo::Up, ButtonCombo::UpA, ButtonCombo::UpB],
+            (false, true, false, false) => [ButtonCombo::Down, ButtonCombo::DownA, ButtonCombo::DownB],
+            (false, false, true, false) => [ButtonCombo::Left, ButtonCombo::LeftA, ButtonCombo::LeftB],
+            (false, false, false, true) => [ButtonCombo::Right, ButtonCombo::RightA, ButtonCombo::RightB],
+            _ => return None,
+        };
+        Some(if a { combo[1] } else if b { combo[2] } else { combo[0] })
+    }
+
+    pub fn colors(self) -> DmgColors {
+        let combination = match self {
+            ButtonCombo::Up => 5,
+            ButtonCombo::UpA => 43,
+            ButtonCombo::UpB => 28,
+            ButtonCombo::Left => 48,
+            ButtonCombo::LeftA => 40,
+            ButtonCombo::LeftB => 7,
+            ButtonCombo::Down => 8,
+            ButtonCombo::DownA => 3,
+            ButtonCombo::DownB => 49,
+            ButtonCombo::Right => 1,
+            ButtonCombo::RightA => 0,
+            ButtonCombo::RightB => 6,
+        };
+        combination_colors(COMBINATIONS[combination])
+    }
+}
+
+pub fn cartridge_colors(cartridge: &Cartridge) -> DmgColors {
+    combination_colors(COMBINATIONS[cartridge_combination(cartridge)])
+}
+
+fn cartridge_combination(cartridge: &Cartridge) -> usize {
+    title_combination(cartridge).unwrap_or(0)
+}
+
+fn title_combination(cartridge: &Cartridge) -> Option<usize> {
+    if !cartridge.is_nintendo() {
+        return None;
+    }
+    let checksum = cartridge.title_checksum();
+    let letter = cartridge.title_fourth_letter();
+
+    let mut index = CHECKSUMS.iter().position(|&sum| sum == checksum)?;
+    if index >= UNIQUE_CHECKSUMS {
+        index = (index..COMBINATION_PER_CHECKSUM.len()).step_by(14)
+            .find(|&i| FOURTH_LETTERS[i - UNIQUE_CHECKSUMS] == letter)?;
+    }
+    Some(COMBINATION_PER_CHECKSUM[index] as usize)
+}
+
+fn combination_colors(combination: Combination) -> DmgColors {
+    DmgColors {
+        bg: palette_colors(combination.bg),
+        obj0: palette_colors(combination.obj0),
+        obj1: palette_colors(combination.obj1),
+    }
+}
+
+// 4 colours from `offset` on, carrying on into the next palette if need be
+fn palette_colors(offset: usize) -> [u32; 4] {
+    std::array::from_fn(|i| {
+        let color = offset + i;
+        rgb555_to_rgb888(PALETTES[color / 4][color % 4])
+    })
+}
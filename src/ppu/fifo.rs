@@ -1,4 +1,43 @@
 pub use fixed_vec_deque::FixedVecDeque;
+use bitflags::bitflags;
+
+/*
+ BG map attributes, stored in VRAM bank 1 at the same address as the tile number **CGB Mode Only**
+ Bit7   BG-to-OAM Priority (0=Use OAM priority bit, 1=BG Priority)
+ Bit6   Y flip             (0=Normal, 1=Vertically mirrored)
+ Bit5   X flip             (0=Normal, 1=Horizontally mirrored)
+ Bit3   Tile VRAM-Bank     (0=Bank 0, 1=Bank 1)
+ Bit2-0 Palette number     (BGP0-7)
+ */
+
+bitflags! {
+    #[derive(Default)]
+    pub struct BgAttributes: u8 {
+        const PRIORITY  = 1 << 7;
+        const Y_FLIP    = 1 << 6;
+        const X_FLIP    = 1 << 5;
+        const VRAM_BANK = 1 << 3;
+        const PALETTE   = 0b111;
+    }
+}
+
+impl BgAttributes {
+    pub fn priority(&self) -> bool {
+        self.intersects(Self::PRIORITY)
+    }
+    pub fn y_flip(&self) -> bool {
+        self.intersects(Self::Y_FLIP)
+    }
+    pub fn x_flip(&self) -> bool {
+        self.intersects(Self::X_FLIP)
+    }
+    pub fn vram_bank(&self) -> u8 {
+        self.intersects(Self::VRAM_BANK) as u8
+    }
+    pub fn palette(&self) -> u8 {
+        self.bits() & Self::PALETTE.bits()
+    }
+}
 
 pub enum FetchState {
     TileNum,
@@ -11,6 +50,7 @@ pub enum FetchState {
 #[derive(Default, Copy, Clone)]
 pub struct BgFifoPixel {
     color: u32,
+    shade: u8,
     bg_priority: bool,
 }
 
@@ -18,6 +58,9 @@ impl BgFifoPixel {
     pub fn get_color(&self) -> u32 {
         self.color
     }
+    pub fn shade(&self) -> u8 {
+        self.shade
+    }
     pub fn bg_priority(&self) -> bool {
         self.bg_priority
     }
@@ -36,9 +79,10 @@ impl Default for BgFifo {
 }
 
 impl BgFifo {
-    pub fn push(&mut self, color: u32, bg_priority: bool) {
+    pub fn push(&mut self, color: u32, shade: u8, bg_priority: bool) {
         *self.pixels.push_back() = BgFifoPixel {
             color,
+            shade,
             bg_priority,
         }
     }
@@ -101,13 +145,17 @@ pub struct PixelFifo {
     pub(super) push_x: u8,
     pub(super) fetch_x: u8,
     pub(super) bgw_fetch_data: [u8; 3],
-    pub(super) fetch_sprite_data: [u8; 6], //oam data..
+    pub(super) bgw_fetch_attr: BgAttributes,
+    pub(super) fetch_sprite_data: [u8; 20], //oam data, two bytes per fetched sprite
     pub(super) sprite_count: u8,
     pub(super) map_x: u8,
     pub(super) map_y: u8,
     pub(super) tile_x: u8,
     pub(super) tile_y: u8,
     pub(super) fifo_x: u8,
+    pub(super) window_active: bool, // the fetcher switched to the window this line
+    pub(super) window_tile_x: u8,
+    pub(super) window_skip: u8,
 }
 
 impl Default for PixelFifo {
@@ -120,13 +168,17 @@ impl Default for PixelFifo {
             push_x: 0,
             fetch_x: 0,
             bgw_fetch_data: [0; 3],
-            fetch_sprite_data: [0; 6], //oam data..
+            bgw_fetch_attr: BgAttributes::default(),
+            fetch_sprite_data: [0; 20], //oam data, two bytes per fetched sprite
             sprite_count: 0,
             map_x: 0,
             map_y: 0,
             tile_x: 0,
             tile_y: 0,
             fifo_x: 0,
+            window_active: false,
+            window_tile_x: 0,
+            window_skip: 0,
         }
     }
 }
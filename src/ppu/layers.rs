@@ -0,0 +1,63 @@
+// Debug switches for finding out which layer draws what. They sit on top of
+// LCDC, a hidden layer draws as if every pixel were colour 0.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Layer {
+    Background,
+    Window,
+    Objects,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Layers {
+    pub background: bool,
+    pub window: bool,
+    pub objects: bool,
+    // tints every pixel by the layer it came from
+    pub highlight: bool,
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Self {
+            background: true,
+            window: true,
+            objects: true,
+            highlight: false,
+        }
+    }
+}
+
+impl Layers {
+    pub fn shows(&self, layer: Layer) -> bool {
+        match layer {
+            Layer::Background => self.background,
+            Layer::Window => self.window,
+            Layer::Objects => self.objects,
+        }
+    }
+
+    pub fn toggle(&mut self, layer: Layer) -> bool {
+        let shown = match layer {
+            Layer::Background => &mut self.background,
+            Layer::Window => &mut self.window,
+            Layer::Objects => &mut self.objects,
+        };
+        *shown = !*shown;
+        *shown
+    }
+
+    // half way between the pixel and red/green/blue for BG/window/objects,
+    // debug builds only
+    pub fn tint(&self, color: u32, layer: Layer) -> u32 {
+        if !cfg!(debug_assertions) || !self.highlight {
+            return color;
+        }
+        let tint = match layer {
+            Layer::Background => 0xFF0000,
+            Layer::Window => 0x00FF00,
+            Layer::Objects => 0x0000FF,
+        };
+        ((color >> 1) & 0x7F7F7F) + ((tint >> 1) & 0x7F7F7F)
+    }
+}
@@ -1,5 +1,7 @@
 use bitflags::bitflags;
 
+pub use crate::ppu::palette::{CgbPalette, DmgColors, GREYSCALE};
+
 pub mod state_machine;
 
 pub enum Mode {
@@ -125,8 +127,6 @@ impl LcdStatus {
     }
 }
 
-pub const COLORS_DEFAULT: [u32; 4] = [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000]; 
-
 pub struct Lcd {
     pub(super) lcd_control: LcdControl,
     pub(super) lcd_status: LcdStatus,
@@ -141,6 +141,9 @@ pub struct Lcd {
     pub(super) bg_colors: [u32; 4],
     pub(super) sp1_colors: [u32; 4],
     pub(super) sp2_colors: [u32; 4],
+    dmg_colors: DmgColors,
+    pub(super) cgb_bg_palette: CgbPalette,
+    pub(super) cgb_obj_palette: CgbPalette,
 }
 
 impl Default for Lcd {
@@ -156,9 +159,12 @@ impl Default for Lcd {
             dmg_sprite_palette: [0xFF; 2],
             window_y: 0,
             window_x: 0,
-            bg_colors:[0xFFFFFF, 0x555555, 0xAAAAAA, 0x000000],
-            sp1_colors: [0xFFFFFF, 0x555555, 0xAAAAAA, 0x000000],
-            sp2_colors: [0xFFFFFF, 0x555555, 0xAAAAAA, 0x000000],
+            bg_colors: GREYSCALE,
+            sp1_colors: GREYSCALE,
+            sp2_colors: GREYSCALE,
+            dmg_colors: DmgColors::default(),
+            cgb_bg_palette: CgbPalette::default(),
+            cgb_obj_palette: CgbPalette::default(),
         }
     }
 }
@@ -176,9 +182,12 @@ impl Lcd {
             dmg_sprite_palette: [0xFF; 2],
             window_y: 0,
             window_x: 0,
-            bg_colors:[0xFFFFFF, 0x555555, 0xAAAAAA, 0x000000],
-            sp1_colors: [0xFFFFFF, 0x555555, 0xAAAAAA, 0x000000],
-            sp2_colors: [0xFFFFFF, 0x555555, 0xAAAAAA, 0x000000],
+            bg_colors: GREYSCALE,
+            sp1_colors: GREYSCALE,
+            sp2_colors: GREYSCALE,
+            dmg_colors: DmgColors::default(),
+            cgb_bg_palette: CgbPalette::default(),
+            cgb_obj_palette: CgbPalette::default(),
         }
     }
     pub fn lcd_read(&self, address: u16) -> u8 {
@@ -194,6 +203,10 @@ impl Lcd {
             0xFF49 => self.dmg_sprite_palette[1],
             0xFF4A => self.window_y,
             0xFF4B => self.window_x,
+            0xFF68 => self.cgb_bg_palette.read_spec(),
+            0xFF69 => self.cgb_bg_palette.read_data(),
+            0xFF6A => self.cgb_obj_palette.read_spec(),
+            0xFF6B => self.cgb_obj_palette.read_data(),
             _ => unreachable!("Unsupported address {:#X}. How did this happen lol!", address),
         }
     }
@@ -211,16 +224,42 @@ impl Lcd {
             0xFF49 => {self.dmg_sprite_palette[1] = value; self.update_palette(value & 0b11111100, 2);},
             0xFF4A => self.window_y = value,
             0xFF4B => self.window_x = value,
+            0xFF68 => self.cgb_bg_palette.write_spec(value),
+            0xFF69 => self.cgb_bg_palette.write_data(value),
+            0xFF6A => self.cgb_obj_palette.write_spec(value),
+            0xFF6B => self.cgb_obj_palette.write_data(value),
             _ => unreachable!("Unsupported address {:#X}. How did this happen lol!", address),
         }
     }
 
     pub fn update_palette(&mut self, palette_data: u8, pal_num: u8) {
+        let shade = |i: usize| ((palette_data >> (2 * i)) & 0b11) as usize;
         match pal_num {
-            0 => for i in 0..4 {self.bg_colors[i] = COLORS_DEFAULT[((palette_data >> 2 * i) & 0b11) as usize];},
-            1 => for i in 0..4 {self.sp1_colors[i] = COLORS_DEFAULT[((palette_data >> 2 * i) & 0b11) as usize];},
-            2 => for i in 0..4 {self.sp2_colors[i] = COLORS_DEFAULT[((palette_data >> 2 * i) & 0b11) as usize];},
+            0 => for i in 0..4 {self.bg_colors[i] = self.dmg_colors.bg[shade(i)];},
+            1 => for i in 0..4 {self.sp1_colors[i] = self.dmg_colors.obj0[shade(i)];},
+            2 => for i in 0..4 {self.sp2_colors[i] = self.dmg_colors.obj1[shade(i)];},
             _ => unreachable!("update_palette! unknown palette {}", pal_num),
         }
     }
+
+    // the shade (0-3) BGP or OBP0/OBP1 gives a colour number, before it
+    // becomes a colour. The SGB colours these itself
+    pub(super) fn bg_shade(&self, color: u8) -> u8 {
+        (self.dmg_bg_palette >> (color * 2)) & 0b11
+    }
+    pub(super) fn sprite_shade(&self, obp1: bool, color: u8) -> u8 {
+        (self.dmg_sprite_palette[obp1 as usize] >> (color * 2)) & 0b11
+    }
+
+    pub fn dmg_colors(&self) -> DmgColors {
+        self.dmg_colors
+    }
+
+    // swaps the shades and reapplies BGP/OBP0/OBP1 so the change shows up right away
+    pub fn set_dmg_colors(&mut self, colors: DmgColors) {
+        self.dmg_colors = colors;
+        self.update_palette(self.dmg_bg_palette, 0);
+        self.update_palette(self.dmg_sprite_palette[0] & 0b11111100, 1);
+        self.update_palette(self.dmg_sprite_palette[1] & 0b11111100, 2);
+    }
 }
\ No newline at end of file
@@ -1,18 +1,14 @@
-pub use crate::ppu::{PPU, lcd::{Lcd, Mode}, fifo::{PixelFifo, FetchState}};
+pub use crate::ppu::{PPU, RenderMode, lcd::{Lcd, Mode}, fifo::{PixelFifo, FetchState}};
 pub use crate::mmu::{MMU, interrupts::{Interrupts, InterruptType}};
 
 pub const LINES_PER_FRAME: usize = 154;
 pub const TICKS_PER_LINE: usize = 456;
+const FAST_TRANSFER_END: u32 = 80 + 172;
 pub const XRES: usize = 160;
 pub const YRES: usize = 144;
 
 
 pub fn increment_ly(ppu: &mut PPU, interrupt: &mut Interrupts) {
-    if ppu.window_visible() && (ppu.lcd.ly >= ppu.lcd.window_y)
-        && (ppu.lcd.ly < ppu.lcd.window_y + YRES as u8) {
-        ppu.window_line += 1;
-    }
-
     ppu.lcd.ly += 1;
     if ppu.lcd.ly == ppu.lcd.ly_compare {
         ppu.lcd.lcd_status.equals_flag_set(true);
@@ -34,10 +30,13 @@ pub fn mode_oam(ppu: &mut PPU) {
         ppu.pixel_fifo.fetch_x = 0;
         ppu.pixel_fifo.push_x = 0;
         ppu.pixel_fifo.fifo_x = 0;
+        ppu.pixel_fifo.window_active = false;
+        ppu.pixel_fifo.window_skip = 0;
     }
 
     if ppu.line_ticks == 1 {
         //println!("tick");
+        ppu.check_window_y();
         ppu.selected_oam_reset();
         ppu.sprite_count = 0;
         ppu.load_sprites();
@@ -45,11 +44,29 @@ pub fn mode_oam(ppu: &mut PPU) {
 }
 
 pub fn mode_transfer(ppu: &mut PPU, interrupt: &mut Interrupts) {
-    ppu.pipeline_process();
+    let line_done = match ppu.render_mode() {
+        RenderMode::Accurate => {
+            ppu.pipeline_process();
+            ppu.pixel_fifo.push_x >= XRES as u8
+        },
+        // mode 3 takes its shortest length plus the SCX % 8 pixels thrown away
+        RenderMode::Fast => {
+            let done = ppu.line_ticks >= FAST_TRANSFER_END + (ppu.lcd.scroll_x % 8) as u32;
+            if done {
+                ppu.render_scanline();
+            }
+            done
+        },
+    };
 
-    if ppu.pixel_fifo.push_x >= XRES as u8 {
+    if line_done {
         ppu.pipeline_reset();
 
+        // the window line counter only moves on lines the window was drawn
+        if ppu.pixel_fifo.window_active {
+            ppu.window_line += 1;
+        }
+
         let val = ppu.lcd.lcd_status.mode_value(&Mode::HBLANK);
         ppu.lcd.lcd_status.current_mode_set(val);
 
@@ -67,6 +84,7 @@ pub fn mode_vblank(ppu: &mut PPU, interrupt: &mut Interrupts) {
             ppu.lcd.lcd_status.current_mode_set(val);
             ppu.lcd.ly = 0;
             ppu.window_line = 0;
+            ppu.window_y_triggered = false;
         }
         ppu.line_ticks = 0;
     }
@@ -0,0 +1,271 @@
+/*
+ CGB colour palette RAM, 8 palettes of 4 colours for BG and another 8 for OBJ.
+ Every colour is 2 bytes of little endian RGB555:
+ Bit0-4   Red
+ Bit5-9   Green
+ Bit10-14 Blue
+
+ The RAM is only reachable through an index register and a data register:
+ BCPS/OCPS (0xFF68/0xFF6A)
+ Bit7   Auto increment (0=Disabled, 1=Increment after writing)
+ Bit5-0 Byte index (0x00-0x3F)
+ BCPD/OCPD (0xFF69/0xFF6B)
+ */
+
+pub struct CgbPalette {
+    data: [u8; 64],
+    index: u8,
+    auto_increment: bool,
+}
+
+impl Default for CgbPalette {
+    fn default() -> Self {
+        Self {
+            // the boot rom leaves every colour white
+            data: [0xFF; 64],
+            index: 0,
+            auto_increment: false,
+        }
+    }
+}
+
+impl CgbPalette {
+    pub fn read_spec(&self) -> u8 {
+        ((self.auto_increment as u8) << 7) | 0x40 | self.index
+    }
+    pub fn write_spec(&mut self, value: u8) {
+        self.auto_increment = value & 0x80 != 0;
+        self.index = value & 0x3F;
+    }
+
+    pub fn read_data(&self) -> u8 {
+        self.data[self.index as usize]
+    }
+    pub fn write_data(&mut self, value: u8) {
+        self.data[self.index as usize] = value;
+        if self.auto_increment {
+            self.index = (self.index + 1) & 0x3F;
+        }
+    }
+
+    pub fn color(&self, palette: u8, color: u8) -> u32 {
+        let i = ((palette & 0b111) as usize * 4 + color as usize) * 2;
+        rgb555_to_rgb888(u16::from_le_bytes([self.data[i], self.data[i + 1]]))
+    }
+}
+
+pub fn rgb555_to_rgb888(rgb: u16) -> u32 {
+    let r = scale_channel(rgb & 0x1F);
+    let g = scale_channel((rgb >> 5) & 0x1F);
+    let b = scale_channel((rgb >> 10) & 0x1F);
+    (r << 16) | (g << 8) | b
+}
+
+// 5 bit channel to 8 bit, repeating the top bits so 0x1F maps to 0xFF
+fn scale_channel(value: u16) -> u32 {
+    let value = value as u32;
+    (value << 3) | (value >> 2)
+}
+
+// Shades a DMG palette register (BGP/OBP0/OBP1) can pick from, lightest first.
+pub const GREYSCALE: [u32; 4] = [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000];
+pub const CLASSIC_GREEN: [u32; 4] = [0x9BBC0F, 0x8BAC0F, 0x306230, 0x0F380F];
+pub const POCKET_GREY: [u32; 4] = [0xC4CFA1, 0x8B956D, 0x4D533C, 0x1F1F1F];
+pub const HIGH_CONTRAST: [u32; 4] = [0xFFFFFF, 0xBDBDBD, 0x424242, 0x000000];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaletteLayer {
+    Bg,
+    Obj0,
+    Obj1,
+}
+
+// The colours the PPU currently uses for each DMG palette register
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DmgColors {
+    pub bg: [u32; 4],
+    pub obj0: [u32; 4],
+    pub obj1: [u32; 4],
+}
+
+impl Default for DmgColors {
+    fn default() -> Self {
+        Self {
+            bg: GREYSCALE,
+            obj0: GREYSCALE,
+            obj1: GREYSCALE,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NamedPalette {
+    pub name: String,
+    pub colors: [u32; 4],
+}
+
+impl NamedPalette {
+    pub fn new(name: &str, colors: [u32; 4]) -> NamedPalette {
+        NamedPalette {
+            name: name.to_string(),
+            colors,
+        }
+    }
+}
+
+/*
+ Built-in palettes plus any user palettes, and which one each layer uses.
+ The config file is plain text, one entry per line, # starts a comment:
+
+   # name = lightest .. darkest, as RGB hex
+   gbc_brown = FFFFFF FFAD63 843100 000000
+   bg   = classic_green
+   obj0 = gbc_brown
+   obj1 = pocket_grey
+
+ bg, obj0 and obj1 are reserved names, anything else defines a palette.
+ */
+#[derive(Clone, Debug)]
+pub struct PaletteConfig {
+    palettes: Vec<NamedPalette>,
+    bg: usize,
+    obj0: usize,
+    obj1: usize,
+}
+
+impl Default for PaletteConfig {
+    fn default() -> Self {
+        Self {
+            palettes: vec![
+                NamedPalette::new("greyscale", GREYSCALE),
+                NamedPalette::new("classic_green", CLASSIC_GREEN),
+                NamedPalette::new("pocket_grey", POCKET_GREY),
+                NamedPalette::new("high_contrast", HIGH_CONTRAST),
+            ],
+            bg: 0,
+            obj0: 0,
+            obj1: 0,
+        }
+    }
+}
+
+impl PaletteConfig {
+    pub fn load(path: &str) -> Result<PaletteConfig, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("can't read palette config {}: {}", path, error))?;
+        PaletteConfig::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<PaletteConfig, String> {
+        let mut config = PaletteConfig::default();
+        let mut selections: Vec<(PaletteLayer, &str, usize)> = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line_num = i + 1;
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = line.split_once('=')
+                .ok_or(format!("line {}: expected `name = value`", line_num))?;
+            let (key, value) = (key.trim(), value.trim());
+
+            match key {
+                "bg" => selections.push((PaletteLayer::Bg, value, line_num)),
+                "obj0" => selections.push((PaletteLayer::Obj0, value, line_num)),
+                "obj1" => selections.push((PaletteLayer::Obj1, value, line_num)),
+                _ => {
+                    let colors = parse_colors(value)
+                        .map_err(|error| format!("line {}: {}", line_num, error))?;
+                    config.add_palette(NamedPalette::new(key, colors));
+                }
+            }
+        }
+
+        // palettes can be selected before they are defined
+        for (layer, name, line_num) in selections {
+            if !config.select(layer, name) {
+                return Err(format!("line {}: unknown palette `{}`", line_num, name));
+            }
+        }
+        Ok(config)
+    }
+
+    // a user palette with a built-in name replaces the built-in one
+    pub fn add_palette(&mut self, palette: NamedPalette) {
+        match self.palettes.iter_mut().find(|p| p.name == palette.name) {
+            Some(existing) => *existing = palette,
+            None => self.palettes.push(palette),
+        }
+    }
+
+    pub fn palettes(&self) -> &[NamedPalette] {
+        &self.palettes
+    }
+
+    pub fn select(&mut self, layer: PaletteLayer, name: &str) -> bool {
+        match self.palettes.iter().position(|p| p.name == name) {
+            Some(index) => {
+                *self.layer_index(layer) = index;
+                true
+            },
+            None => false,
+        }
+    }
+
+    // moves the layer to the next palette in the list, wrapping around
+    pub fn cycle(&mut self, layer: PaletteLayer) -> &NamedPalette {
+        let count = self.palettes.len();
+        let index = self.layer_index(layer);
+        *index = (*index + 1) % count;
+        let index = *index;
+        &self.palettes[index]
+    }
+
+    pub fn selected(&self, layer: PaletteLayer) -> &NamedPalette {
+        match layer {
+            PaletteLayer::Bg => &self.palettes[self.bg],
+            PaletteLayer::Obj0 => &self.palettes[self.obj0],
+            PaletteLayer::Obj1 => &self.palettes[self.obj1],
+        }
+    }
+
+    pub fn colors(&self) -> DmgColors {
+        DmgColors {
+            bg: self.selected(PaletteLayer::Bg).colors,
+            obj0: self.selected(PaletteLayer::Obj0).colors,
+            obj1: self.selected(PaletteLayer::Obj1).colors,
+        }
+    }
+
+    fn layer_index(&mut self, layer: PaletteLayer) -> &mut usize {
+        match layer {
+            PaletteLayer::Bg => &mut self.bg,
+            PaletteLayer::Obj0 => &mut self.obj0,
+            PaletteLayer::Obj1 => &mut self.obj1,
+        }
+    }
+}
+
+fn parse_colors(value: &str) -> Result<[u32; 4], String> {
+    let mut colors = [0; 4];
+    let mut count = 0;
+
+    for word in value.split_whitespace() {
+        if count == 4 {
+            return Err(String::from("a palette has exactly 4 colours"));
+        }
+        let hex = word.trim_start_matches("0x");
+        colors[count] = match u32::from_str_radix(hex, 16) {
+            Ok(color) if hex.len() == 6 => color,
+            _ => return Err(format!("`{}` is not an RGB hex colour", word)),
+        };
+        count += 1;
+    }
+
+    if count != 4 {
+        return Err(String::from("a palette has exactly 4 colours"));
+    }
+    Ok(colors)
+}
@@ -0,0 +1,133 @@
+use super::{PPU, XRES};
+use super::fifo::BgAttributes;
+use super::layers::Layer;
+
+// Fast renderer: the whole line is drawn in one go when mode 3 ends,
+// using the registers as they are at that point. Same rules as the FIFO,
+// just without the per dot timing.
+
+impl PPU {
+    pub(super) fn render_scanline(&mut self) {
+        let ly = self.lcd.ly;
+        let fine_x = self.lcd.scroll_x % 8;
+        let window_start = self.window_start();
+        let bg_enabled = self.cgb_mode || self.lcd.lcd_control.bg_window_priority();
+
+        for x in 0..XRES as u8 {
+            // same coordinates the FIFO uses, see window_start
+            let fifo_x = x + fine_x;
+            let window_x = match window_start {
+                Some((start, skip)) if fifo_x >= start => Some(skip + fifo_x - start),
+                _ => None,
+            };
+            if window_x.is_some() {
+                self.pixel_fifo.window_active = true;
+            }
+
+            let (mut color, attr) = if !bg_enabled {
+                (0, BgAttributes::empty())
+            } else if let Some(window_x) = window_x {
+                self.bg_pixel(self.lcd.lcd_control.window_tilemap(), window_x, self.window_line)
+            } else {
+                self.bg_pixel(self.lcd.lcd_control.bg_tilemap(),
+                    x.wrapping_add(self.lcd.scroll_x), ly.wrapping_add(self.lcd.scroll_y))
+            };
+
+            let bg_color = if self.cgb_mode {
+                self.lcd.cgb_bg_palette.color(attr.palette(), color)
+            } else if self.lcd.lcd_control.bg_window_priority() {
+                self.lcd.bg_colors[color as usize]
+            } else {
+                self.lcd.bg_colors[0]
+            };
+            if !self.lcd.lcd_control.bg_window_priority() {
+                color = 0;
+            }
+
+            let layer = if window_x.is_some() {Layer::Window} else {Layer::Background};
+            let bg_color = if self.layers.shows(layer) {
+                bg_color
+            } else {
+                color = 0;
+                self.hidden_bg_color()
+            };
+
+            let sprite_color = if self.lcd.lcd_control.sprite_enable() && self.layers.objects {
+                self.sprite_pixel(x, color, attr.priority())
+            } else {
+                None
+            };
+
+            let (pixel, shade) = match sprite_color {
+                Some((sprite_color, shade)) => (self.layers.tint(sprite_color, Layer::Objects), shade),
+                None => (self.layers.tint(bg_color, layer), self.lcd.bg_shade(color)),
+            };
+            self.video_buffer[x as usize + ly as usize * XRES] = pixel;
+            self.shade_buffer[x as usize + ly as usize * XRES] = shade;
+        }
+    }
+
+    // colour number and attributes of one BG or window pixel, x/y in map space
+    pub(super) fn bg_pixel(&self, map: u16, x: u8, y: u8) -> (u8, BgAttributes) {
+        let map_address = map + (x / 8) as u16 + (y / 8) as u16 * 32;
+
+        let mut tile = self.vram_at(0, map_address);
+        if self.lcd.lcd_control.bg_window_tile_data() == 0x8800 {
+            tile = tile.wrapping_add(0x80);
+        }
+        let attr = if self.cgb_mode {
+            BgAttributes::from_bits_truncate(self.vram_at(1, map_address))
+        } else {
+            BgAttributes::empty()
+        };
+
+        let row = if attr.y_flip() {7 - y % 8} else {y % 8};
+        let bit = if attr.x_flip() {x % 8} else {7 - x % 8};
+        let address = self.lcd.lcd_control.bg_window_tile_data() + tile as u16 * 16 + row as u16 * 2;
+        (tile_color(self.vram_at(attr.vram_bank(), address), self.vram_at(attr.vram_bank(), address + 1), bit), attr)
+    }
+
+    // colour and shade of the first opaque sprite pixel in priority order,
+    // None if the BG wins
+    fn sprite_pixel(&self, x: u8, bg_color: u8, bg_priority: bool) -> Option<(u32, u8)> {
+        let size = self.lcd.lcd_control.sprite_size();
+        let screen_x = x as i16;
+
+        for selected in self.selected_oam.iter() {
+            let sprite = selected.sprite();
+            let offset = screen_x - (sprite.x() as i16 - 8);
+            if !(0..8).contains(&offset) {
+                continue;
+            }
+
+            let line = self.lcd.ly + 16 - sprite.y();
+            let row = if sprite.y_flip() {size - 1 - line} else {line};
+            let tile = if size == 16 {sprite.tile() & !0x01} else {sprite.tile()};
+            let bank = if self.cgb_mode {sprite.vram_bank()} else {0};
+            let address = 0x8000 + tile as u16 * 16 + row as u16 * 2;
+            let bit = if sprite.x_flip() {offset as u8} else {7 - offset as u8};
+
+            let color = tile_color(self.vram_at(bank, address), self.vram_at(bank, address + 1), bit);
+            if color == 0 {
+                continue;
+            }
+            if (sprite.priority() || bg_priority) && bg_color != 0 {
+                return None;
+            }
+
+            let pixel = if self.cgb_mode {
+                self.lcd.cgb_obj_palette.color(sprite.cgb_palette(), color)
+            } else if sprite.dmg_palette() {
+                self.lcd.sp2_colors[color as usize]
+            } else {
+                self.lcd.sp1_colors[color as usize]
+            };
+            return Some((pixel, self.lcd.sprite_shade(sprite.dmg_palette(), color)));
+        }
+        None
+    }
+}
+
+pub(super) fn tile_color(low: u8, high: u8, bit: u8) -> u8 {
+    (((high >> bit) & 1) << 1) | ((low >> bit) & 1)
+}
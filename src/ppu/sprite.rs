@@ -38,6 +38,10 @@ impl SelectedSprite {
     pub fn sprite(&self) -> Sprite {
         self.sprite
     }
+
+    pub fn index(&self) -> u8 {
+        self.index
+    }
 }
 
 #[derive(Clone, Copy, Default, Debug)]
@@ -110,10 +114,10 @@ impl Sprite {
     pub fn dmg_palette(&self) -> bool {
         self.flags.intersects(SpriteFlags::DMG_PALETTE)
     }
-    pub fn vram_bank(&self) -> bool {
-        self.flags.intersects(SpriteFlags::VRAM_BANK)
+    pub fn vram_bank(&self) -> u8 {
+        self.flags.intersects(SpriteFlags::VRAM_BANK) as u8
     }
-    pub fn cgb_palette(&self) -> bool {
-        self.flags.intersects(SpriteFlags::CGB_PALETTE)
+    pub fn cgb_palette(&self) -> u8 {
+        self.flags.bits() & SpriteFlags::CGB_PALETTE.bits()
     }
 }
\ No newline at end of file
@@ -0,0 +1,220 @@
+use super::{PPU, XRES, YRES};
+use super::scanline::tile_color;
+use super::sprite::Sprite;
+
+// Debug views of VRAM, OAM and the palettes. They only read PPU state and
+// hand back plain pixel buffers, drawing them is up to the frontend.
+
+const VIEWPORT_COLOR: u32 = 0xFF0000;
+const WINDOW_COLOR: u32 = 0x0080FF;
+const EMPTY_COLOR: u32 = 0x808080;
+const SWATCH: usize = 8;
+
+pub struct PixelBuffer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl PixelBuffer {
+    pub fn new(width: usize, height: usize, color: u32) -> PixelBuffer {
+        PixelBuffer {
+            width,
+            height,
+            pixels: vec![color; width * height],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u32 {
+        self.pixels[y * self.width + x]
+    }
+    pub fn set(&mut self, x: usize, y: usize, color: u32) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, color: u32) {
+        for row in y..y + height {
+            for col in x..x + width {
+                self.set(col, row, color);
+            }
+        }
+    }
+
+    // copies another buffer in at (x, y)
+    fn draw(&mut self, x: usize, y: usize, other: &PixelBuffer) {
+        for row in 0..other.height {
+            for col in 0..other.width {
+                self.set(x + col, y + row, other.get(col, row));
+            }
+        }
+    }
+}
+
+pub enum TileMap {
+    Low,  // 0x9800
+    High, // 0x9C00
+}
+
+// one OAM entry, decoded
+pub struct SpriteInfo {
+    pub index: usize,
+    pub y: u8,
+    pub x: u8,
+    pub tile: u8,
+    pub behind_bg: bool,
+    pub y_flip: bool,
+    pub x_flip: bool,
+    pub dmg_palette: u8,
+    pub cgb_palette: u8,
+    pub vram_bank: u8,
+    pub visible: bool, // inside the screen area at all
+    pub pixels: PixelBuffer,
+}
+
+impl PPU {
+    // 384 tiles of one VRAM bank, 16 per row, shaded with the raw DMG colours
+    pub fn tile_data_view(&self, bank: u8) -> PixelBuffer {
+        let colors = self.lcd.dmg_colors().bg;
+        let mut buffer = PixelBuffer::new(16 * 8, 24 * 8, 0);
+
+        for tile in 0..384 {
+            let address = 0x8000 + tile as u16 * 16;
+            for y in 0..8 {
+                let low = self.vram_at(bank & 1, address + y as u16 * 2);
+                let high = self.vram_at(bank & 1, address + y as u16 * 2 + 1);
+                for x in 0..8 {
+                    let color = tile_color(low, high, 7 - x as u8);
+                    buffer.set((tile % 16) * 8 + x, (tile / 16) * 8 + y, colors[color as usize]);
+                }
+            }
+        }
+        buffer
+    }
+
+    // the whole 256x256 map with the screen outlined, plus where the window
+    // covers it when the window is on
+    pub fn tilemap_view(&self, map: TileMap) -> PixelBuffer {
+        let map = match map {
+            TileMap::Low => 0x9800,
+            TileMap::High => 0x9C00,
+        };
+        let mut buffer = PixelBuffer::new(256, 256, 0);
+
+        for y in 0..256 {
+            for x in 0..256 {
+                let (color, attr) = self.bg_pixel(map, x as u8, y as u8);
+                let color = if self.cgb_mode {
+                    self.lcd.cgb_bg_palette.color(attr.palette(), color)
+                } else {
+                    self.lcd.bg_colors[color as usize]
+                };
+                buffer.set(x, y, color);
+            }
+        }
+
+        let (scx, scy) = (self.lcd.scroll_x as usize, self.lcd.scroll_y as usize);
+        if self.lcd.lcd_control.window_enable() && self.lcd.window_x <= 166 && (self.lcd.window_y as usize) < YRES {
+            let left = (self.lcd.window_x as usize).saturating_sub(7);
+            let top = self.lcd.window_y as usize;
+            outline(&mut buffer, scx + left, scy + top, XRES - left, YRES - top, WINDOW_COLOR);
+        }
+        outline(&mut buffer, scx, scy, XRES, YRES, VIEWPORT_COLOR);
+        buffer
+    }
+
+    pub fn sprite_table(&self) -> Vec<SpriteInfo> {
+        let height = self.lcd.lcd_control.sprite_size() as usize;
+
+        self.oam.iter().enumerate().map(|(index, &sprite)| SpriteInfo {
+            index,
+            y: sprite.y(),
+            x: sprite.x(),
+            tile: sprite.tile(),
+            behind_bg: sprite.priority(),
+            y_flip: sprite.y_flip(),
+            x_flip: sprite.x_flip(),
+            dmg_palette: sprite.dmg_palette() as u8,
+            cgb_palette: sprite.cgb_palette(),
+            vram_bank: sprite.vram_bank(),
+            visible: sprite.x() > 0 && sprite.x() < 168
+                && sprite.y() as usize + height > 16 && sprite.y() < 160,
+            pixels: self.sprite_pixels(sprite, height),
+        }).collect()
+    }
+
+    // all 40 sprites in a 8x5 grid, one 8x16 cell each plus a 1 pixel gap
+    pub fn oam_view(&self) -> PixelBuffer {
+        let mut buffer = PixelBuffer::new(8 * 9, 5 * 17, EMPTY_COLOR);
+        for info in self.sprite_table() {
+            buffer.draw((info.index % 8) * 9, (info.index / 8) * 17, &info.pixels);
+        }
+        buffer
+    }
+
+    // BGP, OBP0 and OBP1 as 4 swatches each, followed on CGB by the 8 BG
+    // palettes (left) next to the 8 OBJ palettes (right)
+    pub fn palette_view(&self) -> PixelBuffer {
+        let rows = if self.cgb_mode {3 + 8} else {3};
+        let mut buffer = PixelBuffer::new(SWATCH * 9, SWATCH * rows, 0);
+
+        for (row, colors) in [self.lcd.bg_colors, self.lcd.sp1_colors, self.lcd.sp2_colors].iter().enumerate() {
+            for (i, &color) in colors.iter().enumerate() {
+                buffer.fill(i * SWATCH, row * SWATCH, SWATCH, SWATCH, color);
+            }
+        }
+        if self.cgb_mode {
+            for palette in 0..8 {
+                for color in 0..4 {
+                    let y = (3 + palette as usize) * SWATCH;
+                    buffer.fill(color as usize * SWATCH, y, SWATCH, SWATCH,
+                        self.lcd.cgb_bg_palette.color(palette, color));
+                    buffer.fill((5 + color as usize) * SWATCH, y, SWATCH, SWATCH,
+                        self.lcd.cgb_obj_palette.color(palette, color));
+                }
+            }
+        }
+        buffer
+    }
+
+    fn sprite_pixels(&self, sprite: Sprite, height: usize) -> PixelBuffer {
+        let mut buffer = PixelBuffer::new(8, 16, EMPTY_COLOR);
+        let tile = if height == 16 {sprite.tile() & !0x01} else {sprite.tile()};
+        let bank = if self.cgb_mode {sprite.vram_bank()} else {0};
+
+        for y in 0..height {
+            let row = if sprite.y_flip() {height - 1 - y} else {y};
+            let address = 0x8000 + tile as u16 * 16 + row as u16 * 2;
+            let (low, high) = (self.vram_at(bank, address), self.vram_at(bank, address + 1));
+            for x in 0..8 {
+                let bit = if sprite.x_flip() {x as u8} else {7 - x as u8};
+                let color = tile_color(low, high, bit);
+                if color == 0 {
+                    continue;
+                }
+                buffer.set(x, y, if self.cgb_mode {
+                    self.lcd.cgb_obj_palette.color(sprite.cgb_palette(), color)
+                } else if sprite.dmg_palette() {
+                    self.lcd.sp2_colors[color as usize]
+                } else {
+                    self.lcd.sp1_colors[color as usize]
+                });
+            }
+        }
+        buffer
+    }
+}
+
+// rectangle outline that wraps around the 256x256 map like the scroll does
+fn outline(buffer: &mut PixelBuffer, x: usize, y: usize, width: usize, height: usize, color: u32) {
+    if width == 0 || height == 0 {
+        return;
+    }
+    for i in 0..width {
+        buffer.set((x + i) % 256, y % 256, color);
+        buffer.set((x + i) % 256, (y + height - 1) % 256, color);
+    }
+    for i in 0..height {
+        buffer.set(x % 256, (y + i) % 256, color);
+        buffer.set((x + width - 1) % 256, (y + i) % 256, color);
+    }
+}
@@ -0,0 +1,83 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+// Records frames to a YUV4MPEG2 (.y4m) file, which is just a text header
+// followed by raw frames. ffmpeg and mpv read it as is.
+//
+// Every frame the PPU finishes gets written, and the header says the
+// frames are 70224 dots apart, so the timing follows the emulated clock
+// no matter how smooth the host was. There's no APU yet, so no audio.
+
+// 4194304 Hz / 70224 dots per frame, about 59.73 fps
+const FRAME_RATE: &str = "F4194304:70224";
+
+pub struct Recorder {
+    out: BufWriter<File>,
+    width: usize,
+    height: usize,
+    frames: u32,
+    // one plane each, reused every frame
+    y: Vec<u8>,
+    u: Vec<u8>,
+    v: Vec<u8>,
+}
+
+impl Recorder {
+    pub fn new(path: &str, width: usize, height: usize) -> Result<Recorder, String> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut out = BufWriter::new(file);
+        // C444 keeps full colour resolution, the pixels are too big to subsample
+        writeln!(out, "YUV4MPEG2 W{} H{} {} Ip A1:1 C444", width, height, FRAME_RATE)
+            .map_err(|e| format!("{}: {}", path, e))?;
+
+        Ok(Recorder {
+            out,
+            width,
+            height,
+            frames: 0,
+            y: vec![0; width * height],
+            u: vec![0; width * height],
+            v: vec![0; width * height],
+        })
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    // pixels are 0xRRGGBB like video_buffer
+    pub fn push_frame(&mut self, pixels: &[u32]) -> Result<(), String> {
+        assert_eq!(pixels.len(), self.width * self.height, "frame doesn't match the recording size");
+        for (i, &color) in pixels.iter().enumerate() {
+            let (y, u, v) = rgb_to_yuv(color);
+            self.y[i] = y;
+            self.u[i] = u;
+            self.v[i] = v;
+        }
+
+        let result = self.out.write_all(b"FRAME\n")
+            .and_then(|_| self.out.write_all(&self.y))
+            .and_then(|_| self.out.write_all(&self.u))
+            .and_then(|_| self.out.write_all(&self.v));
+        result.map_err(|e| e.to_string())?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<u32, String> {
+        self.out.flush().map_err(|e| e.to_string())?;
+        Ok(self.frames)
+    }
+}
+
+// BT.601 studio range, the usual integer approximation
+pub fn rgb_to_yuv(color: u32) -> (u8, u8, u8) {
+    let r = ((color >> 16) & 0xFF) as i32;
+    let g = ((color >> 8) & 0xFF) as i32;
+    let b = (color & 0xFF) as i32;
+
+    let y = ((66 * r + 129 * g + 25 * b + 128) >> 8) + 16;
+    let u = ((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128;
+    let v = ((112 * r - 94 * g - 18 * b + 128) >> 8) + 128;
+    (y as u8, u as u8, v as u8)
+}
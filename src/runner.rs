@@ -0,0 +1,162 @@
+use std::fs;
+use std::path::Path;
+
+use crate::cpu::CPU;
+use crate::dbg::DBG;
+use crate::mmu::MMU;
+use crate::png;
+use crate::ppu::{XRES, YRES};
+
+// Runs a ROM without any windows, for CI and the test ROM harnesses.
+// The frontend loop boils down to do_cycle, this just adds ways to stop.
+
+// T-cycles per frame in normal speed
+const FRAME_TICKS: usize = 70224;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StopCondition {
+    // the serial log contains this text
+    Serial(String),
+    // an instruction is about to run at this address
+    Pc(u16),
+    // jumping to itself with no interrupt to get it out again
+    InfiniteLoop,
+    // a LD B,B just ran
+    Breakpoint,
+    // an illegal opcode locked the CPU up
+    Lockup,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RunResult {
+    Stopped(StopCondition),
+    // ran all the frames without meeting a condition
+    OutOfFrames,
+}
+
+pub struct Runner {
+    pub cpu: CPU,
+    pub mmu: MMU,
+    dbg: DBG,
+}
+
+impl Runner {
+    pub fn new(rom: &[u8]) -> Runner {
+        let mut mmu = MMU::default();
+        mmu.cartridge.from_rom_file(rom);
+        let cgb_mode = mmu.cartridge.is_cgb();
+        mmu.set_cgb_mode(cgb_mode);
+
+        Runner {
+            cpu: if cgb_mode {CPU::cgb_default()} else {CPU::default()},
+            mmu,
+            dbg: DBG::silent(),
+        }
+    }
+
+    pub fn from_file(path: &str) -> Result<Runner, String> {
+        let rom = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(Runner::new(&rom))
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.mmu.ppu.current_frame
+    }
+
+    pub fn step(&mut self) {
+        self.cpu.do_cycle(&mut self.mmu, &mut self.dbg);
+    }
+
+    // runs until one of the conditions holds or `frames` more frames have gone by
+    pub fn run(&mut self, frames: u32, until: &[StopCondition]) -> RunResult {
+        let end = self.frame() + frames;
+        // no frames go by while STOP waits for a button, so give up after
+        // a frame's worth of CPU cycles each too (double speed takes twice)
+        let end_ticks = self.cpu.get_ticks() + frames as usize * FRAME_TICKS * 2;
+        // only look at the serial log again when it grew
+        let mut serial_len = usize::MAX;
+        let mut breakpoint = false;
+
+        while self.frame() < end && self.cpu.get_ticks() < end_ticks {
+            let serial_grew = self.mmu.serial.output().len() != serial_len;
+            serial_len = self.mmu.serial.output().len();
+
+            for condition in until {
+                let met = match condition {
+                    StopCondition::Serial(text) => serial_grew && self.serial_text().contains(text.as_str()),
+                    StopCondition::Pc(pc) => !self.cpu.get_halted() && self.cpu.get_pc() == *pc,
+                    StopCondition::InfiniteLoop => self.stuck(),
+                    StopCondition::Breakpoint => breakpoint,
+                    StopCondition::Lockup => self.cpu.locked(),
+                };
+                if met {
+                    return RunResult::Stopped(condition.clone());
+                }
+            }
+            self.step();
+            breakpoint = self.cpu.take_breakpoint();
+        }
+        RunResult::OutOfFrames
+    }
+
+    pub fn serial_text(&self) -> String {
+        self.mmu.serial.output_text()
+    }
+
+    pub fn registers(&self) -> String {
+        format!("{:?}", self.cpu)
+    }
+
+    // how Mooneye's tests say they passed, failures leave 0x42 everywhere
+    pub fn fibonacci_registers(&self) -> bool {
+        let cpu = &self.cpu;
+        [cpu.get_b(), cpu.get_c(), cpu.get_d(), cpu.get_e(), cpu.get_h(), cpu.get_l()] == [3, 5, 8, 13, 21, 34]
+    }
+
+    pub fn save_screen(&self, path: &str) -> Result<(), String> {
+        self.mmu.ppu.save_screenshot(path, 1)
+    }
+
+    // compares the screen with a reference PNG. When they differ the actual
+    // screen and a diff (matching pixels faded, the others red) are written to
+    // `failures` as <name>-actual.png and <name>-diff.png
+    pub fn compare_screen(&self, reference: &str, failures: &str) -> Result<(), String> {
+        let expected = png::load(reference)?;
+        if (expected.width, expected.height) != (XRES, YRES) {
+            return Err(format!("{} is {}x{}, not {}x{}", reference, expected.width, expected.height, XRES, YRES));
+        }
+
+        let screen = self.mmu.ppu.video_buffer();
+        let wrong = screen.iter().zip(expected.pixels.iter()).filter(|(actual, expected)| actual != expected).count();
+        if wrong == 0 {
+            return Ok(());
+        }
+        let diff: Vec<u32> = screen.iter().zip(expected.pixels.iter())
+            .map(|(&actual, &expected)| if actual == expected {((actual >> 2) & 0x3F3F3F) + 0xC0C0C0} else {0xFF0000})
+            .collect();
+
+        let name = Path::new(reference).file_stem().and_then(|stem| stem.to_str()).unwrap_or("screen");
+        fs::create_dir_all(failures).map_err(|e| format!("{}: {}", failures, e))?;
+        let actual_path = format!("{}/{}-actual.png", failures, name);
+        let diff_path = format!("{}/{}-diff.png", failures, name);
+        png::save(&actual_path, XRES, YRES, screen, 1)?;
+        png::save(&diff_path, XRES, YRES, &diff, 1)?;
+        Err(format!("{} pixels differ from {}, see {} and {}", wrong, reference, actual_path, diff_path))
+    }
+
+    fn stuck(&mut self) -> bool {
+        if self.cpu.get_halted() {
+            return false;
+        }
+        let pc = self.cpu.get_pc();
+        let jumps_to_itself = match self.mmu.read_byte(pc) {
+            // JR -2
+            0x18 => self.mmu.read_byte(pc.wrapping_add(1)) == 0xFE,
+            // JP a16
+            0xC3 => u16::from_le_bytes([self.mmu.read_byte(pc.wrapping_add(1)), self.mmu.read_byte(pc.wrapping_add(2))]) == pc,
+            _ => false,
+        };
+        // an interrupt could still break out of it
+        jumps_to_itself && (!self.cpu.get_ime() || self.mmu.interrupts.read_enable() == 0)
+    }
+}
@@ -0,0 +1,402 @@
+pub mod packet;
+
+use packet::PACKET_SIZE;
+use crate::joypad::JoyPad;
+use crate::ppu::palette::rgb555_to_rgb888;
+use crate::ppu::{XRES, YRES};
+
+/*
+ Super Game Boy. The SNES side draws a 256x224 frame with the Game Boy screen
+ in the middle, coloured by 4 palettes picked per 8x8 cell (20x18 cells), and a
+ border around it. Colour 0 is shared by every palette.
+ The game talks to it with packets (see packet.rs), bigger data is sent by
+ putting it on screen for a frame (VRAM transfer).
+ */
+
+pub const SGB_WIDTH: usize = 256;
+pub const SGB_HEIGHT: usize = 224;
+const SCREEN_X: usize = 48;
+const SCREEN_Y: usize = 40;
+
+const CELLS_X: usize = XRES / 8;
+const CELLS_Y: usize = YRES / 8;
+const ATTR_FILE_SIZE: usize = CELLS_X * CELLS_Y / 4;
+const ATTR_FILES: usize = 45;
+
+// SGB palette 1-A, what the SNES starts with
+const DEFAULT_PALETTE: [u16; 4] = [0x67BF, 0x265B, 0x10B5, 0x2866];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Transfer {
+    Palettes,
+    BorderTiles(u8),
+    BorderMap,
+    AttrFiles,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Mask {
+    None,
+    Freeze,
+    Black,
+    Color0,
+}
+
+pub struct Sgb {
+    enabled: bool,
+    palettes: [[u16; 4]; 4],
+    system_palettes: Vec<[u16; 4]>,
+    attributes: [u8; CELLS_X * CELLS_Y],
+    attr_files: Vec<u8>,
+    border_tiles: Vec<u8>,
+    border_map: Vec<u8>,
+    border_palettes: [[u16; 16]; 4],
+    mask: Mask,
+    frozen: Vec<u8>,
+    command: Vec<u8>,
+    transfer: Option<(Transfer, u32)>,
+}
+
+impl Default for Sgb {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            palettes: [DEFAULT_PALETTE; 4],
+            system_palettes: vec![[0; 4]; 512],
+            attributes: [0; CELLS_X * CELLS_Y],
+            attr_files: vec![0; ATTR_FILES * ATTR_FILE_SIZE],
+            border_tiles: vec![0; 0x2000],
+            border_map: vec![0; 0x800],
+            border_palettes: [[0; 16]; 4],
+            mask: Mask::None,
+            frozen: Vec::new(),
+            command: Vec::new(),
+            transfer: None,
+        }
+    }
+}
+
+impl Sgb {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    // collects the packets of a command, the low 3 bits of the first byte
+    // say how many there are
+    pub fn handle_packet(&mut self, packet: [u8; PACKET_SIZE], joypad: &mut JoyPad, frame: u32) {
+        self.command.extend_from_slice(&packet);
+        let length = (self.command[0] & 0b111).max(1) as usize;
+        if self.command.len() < length * PACKET_SIZE {
+            return;
+        }
+
+        let command = std::mem::take(&mut self.command);
+        self.run_command(&command, joypad, frame);
+    }
+
+    fn run_command(&mut self, data: &[u8], joypad: &mut JoyPad, frame: u32) {
+        match data[0] >> 3 {
+            0x00 => self.set_palette_pair(data, 0, 1),
+            0x01 => self.set_palette_pair(data, 2, 3),
+            0x02 => self.set_palette_pair(data, 0, 3),
+            0x03 => self.set_palette_pair(data, 1, 2),
+            0x04 => self.attr_block(data),
+            0x05 => self.attr_line(data),
+            0x06 => self.attr_divide(data),
+            0x07 => self.attr_chr(data),
+            0x0A => self.pal_set(data),
+            0x0B => self.transfer = Some((Transfer::Palettes, frame)),
+            0x11 => joypad.set_players(match data[1] & 0b11 {
+                1 => 2,
+                3 => 4,
+                _ => 1,
+            }),
+            0x13 => self.transfer = Some((Transfer::BorderTiles(data[1] & 1), frame)),
+            0x14 => self.transfer = Some((Transfer::BorderMap, frame)),
+            0x15 => self.transfer = Some((Transfer::AttrFiles, frame)),
+            0x16 => {
+                self.load_attr_file(data[1] & 0x3F);
+                if data[1] & 0x40 != 0 {
+                    self.set_mask(Mask::None);
+                }
+            },
+            0x17 => self.set_mask(match data[1] & 0b11 {
+                1 => Mask::Freeze,
+                2 => Mask::Black,
+                3 => Mask::Color0,
+                _ => Mask::None,
+            }),
+            // sound, SNES code upload and the rest don't change the picture
+            _ => (),
+        }
+    }
+
+    // VRAM transfers read the screen of the frame after the command
+    pub fn transfer_pending(&self, frame: u32) -> bool {
+        matches!(self.transfer, Some((_, command_frame)) if command_frame != frame)
+    }
+
+    pub fn finish_transfer(&mut self, data: &[u8; 0x1000]) {
+        let transfer = match self.transfer.take() {
+            Some((transfer, _)) => transfer,
+            None => return,
+        };
+
+        match transfer {
+            Transfer::Palettes => {
+                for (palette, bytes) in self.system_palettes.iter_mut().zip(data.chunks_exact(8)) {
+                    for (color, pair) in palette.iter_mut().zip(bytes.chunks_exact(2)) {
+                        *color = u16::from_le_bytes([pair[0], pair[1]]);
+                    }
+                }
+            },
+            Transfer::BorderTiles(half) => {
+                let start = half as usize * 0x1000;
+                self.border_tiles[start..start + 0x1000].copy_from_slice(data);
+            },
+            Transfer::BorderMap => {
+                self.border_map.copy_from_slice(&data[..0x800]);
+                // palettes 4-7 follow the map
+                for (palette, bytes) in self.border_palettes.iter_mut().zip(data[0x800..0x880].chunks_exact(32)) {
+                    for (color, pair) in palette.iter_mut().zip(bytes.chunks_exact(2)) {
+                        *color = u16::from_le_bytes([pair[0], pair[1]]);
+                    }
+                }
+            },
+            Transfer::AttrFiles => {
+                self.attr_files.copy_from_slice(&data[..ATTR_FILES * ATTR_FILE_SIZE]);
+            },
+        }
+    }
+
+    // PAL01/PAL23/PAL03/PAL12: colour 0 for everyone, then 3 colours each
+    fn set_palette_pair(&mut self, data: &[u8], first: usize, second: usize) {
+        let color = |i: usize| u16::from_le_bytes([data[1 + i * 2], data[2 + i * 2]]);
+
+        for palette in self.palettes.iter_mut() {
+            palette[0] = color(0);
+        }
+        for i in 1..4 {
+            self.palettes[first][i] = color(i);
+            self.palettes[second][i] = color(i + 3);
+        }
+    }
+
+    // PAL_SET: 4 of the system palettes from PAL_TRN, optionally an ATTR file
+    fn pal_set(&mut self, data: &[u8]) {
+        for i in 0..4 {
+            let index = u16::from_le_bytes([data[1 + i * 2], data[2 + i * 2]]) as usize & 0x1FF;
+            self.palettes[i] = self.system_palettes[index];
+        }
+        let color0 = self.palettes[0][0];
+        for palette in self.palettes.iter_mut() {
+            palette[0] = color0;
+        }
+
+        if data[9] & 0x80 != 0 {
+            self.load_attr_file(data[9] & 0x3F);
+        }
+        if data[9] & 0x40 != 0 {
+            self.set_mask(Mask::None);
+        }
+    }
+
+    /*
+     ATTR_BLK data sets, 6 bytes each:
+     Byte0 Bit0 change inside, Bit1 change border, Bit2 change outside
+     Byte1 Bit0-1 inside palette, Bit2-3 border palette, Bit4-5 outside palette
+     Byte2-5 X1, Y1, X2, Y2 in cells
+     */
+    fn attr_block(&mut self, data: &[u8]) {
+        let count = (data[1] as usize).min(0x12);
+
+        for set in data[2..].chunks_exact(6).take(count) {
+            let (control, palettes) = (set[0] & 0b111, set[1]);
+            let (x1, y1, x2, y2) = (set[2] as usize, set[3] as usize, set[4] as usize, set[5] as usize);
+            let inside = palettes & 0b11;
+            let outside = (palettes >> 4) & 0b11;
+            // only inside or only outside also colours the border
+            let (change_border, border) = match control {
+                0b001 => (true, inside),
+                0b100 => (true, outside),
+                _ => (control & 0b010 != 0, (palettes >> 2) & 0b11),
+            };
+
+            for y in 0..CELLS_Y {
+                for x in 0..CELLS_X {
+                    let in_box = (x1..=x2).contains(&x) && (y1..=y2).contains(&y);
+                    let on_edge = in_box && (x == x1 || x == x2 || y == y1 || y == y2);
+                    let cell = &mut self.attributes[y * CELLS_X + x];
+
+                    if on_edge {
+                        if change_border {
+                            *cell = border;
+                        }
+                    } else if in_box {
+                        if control & 0b001 != 0 {
+                            *cell = inside;
+                        }
+                    } else if control & 0b100 != 0 {
+                        *cell = outside;
+                    }
+                }
+            }
+        }
+    }
+
+    // ATTR_LIN: Bit0-4 line, Bit5-6 palette, Bit7 (0=column, 1=row)
+    fn attr_line(&mut self, data: &[u8]) {
+        let count = data[1] as usize;
+
+        for &line in data[2..].iter().take(count) {
+            let index = (line & 0x1F) as usize;
+            let palette = (line >> 5) & 0b11;
+            if line & 0x80 != 0 {
+                if index < CELLS_Y {
+                    self.attributes[index * CELLS_X..(index + 1) * CELLS_X].fill(palette);
+                }
+            } else if index < CELLS_X {
+                for y in 0..CELLS_Y {
+                    self.attributes[y * CELLS_X + index] = palette;
+                }
+            }
+        }
+    }
+
+    /*
+     ATTR_DIV:
+     Byte1 Bit0-1 palette right/below, Bit2-3 palette left/above,
+           Bit4-5 palette on the line, Bit6 split (0=left/right, 1=above/below)
+     Byte2 X or Y of the line
+     */
+    fn attr_divide(&mut self, data: &[u8]) {
+        let after = data[1] & 0b11;
+        let before = (data[1] >> 2) & 0b11;
+        let on_line = (data[1] >> 4) & 0b11;
+        let horizontal = data[1] & 0x40 != 0;
+        let line = data[2] as usize;
+
+        for y in 0..CELLS_Y {
+            for x in 0..CELLS_X {
+                let position = if horizontal { y } else { x };
+                self.attributes[y * CELLS_X + x] = match position.cmp(&line) {
+                    std::cmp::Ordering::Less => before,
+                    std::cmp::Ordering::Equal => on_line,
+                    std::cmp::Ordering::Greater => after,
+                };
+            }
+        }
+    }
+
+    // ATTR_CHR: a start cell, a count, a direction and 2 bits per cell
+    fn attr_chr(&mut self, data: &[u8]) {
+        let (mut x, mut y) = (data[1] as usize, data[2] as usize);
+        let count = (u16::from_le_bytes([data[3], data[4]]) as usize).min(CELLS_X * CELLS_Y);
+        let top_to_bottom = data[5] & 1 != 0;
+
+        for i in 0..count {
+            let byte = match data.get(6 + i / 4) {
+                Some(byte) => *byte,
+                None => break,
+            };
+            if x >= CELLS_X || y >= CELLS_Y {
+                break;
+            }
+            self.attributes[y * CELLS_X + x] = (byte >> (6 - (i % 4) * 2)) & 0b11;
+
+            if top_to_bottom {
+                y += 1;
+                if y == CELLS_Y {
+                    y = 0;
+                    x += 1;
+                }
+            } else {
+                x += 1;
+                if x == CELLS_X {
+                    x = 0;
+                    y += 1;
+                }
+            }
+        }
+    }
+
+    fn load_attr_file(&mut self, file: u8) {
+        let file = file as usize;
+        if file >= ATTR_FILES {
+            return;
+        }
+        let bytes = &self.attr_files[file * ATTR_FILE_SIZE..(file + 1) * ATTR_FILE_SIZE];
+        for (i, cell) in self.attributes.iter_mut().enumerate() {
+            *cell = (bytes[i / 4] >> (6 - (i % 4) * 2)) & 0b11;
+        }
+    }
+
+    fn set_mask(&mut self, mask: Mask) {
+        self.mask = mask;
+        self.frozen.clear();
+    }
+
+    // screen holds the shade (0-3) of every Game Boy pixel, see
+    // PPU::shade_buffer
+    pub fn render(&mut self, screen: &[u8]) -> Vec<u32> {
+        let backdrop = rgb555_to_rgb888(self.palettes[0][0]);
+        let mut frame = vec![backdrop; SGB_WIDTH * SGB_HEIGHT];
+
+        if self.mask == Mask::Freeze && self.frozen.is_empty() {
+            self.frozen = screen.to_vec();
+        }
+        let screen = if self.mask == Mask::Freeze { &self.frozen[..] } else { screen };
+
+        for y in 0..YRES {
+            for x in 0..XRES {
+                let shade = (screen[y * XRES + x] & 0b11) as usize;
+                let palette = self.attributes[(y / 8) * CELLS_X + x / 8] as usize;
+                frame[(SCREEN_Y + y) * SGB_WIDTH + SCREEN_X + x] = match self.mask {
+                    Mask::Black => 0x000000,
+                    Mask::Color0 => backdrop,
+                    _ => rgb555_to_rgb888(self.palettes[palette][shade]),
+                };
+            }
+        }
+
+        self.draw_border(&mut frame);
+        frame
+    }
+
+    /*
+     Border map entries, 32x28 of them:
+     Bit0-7   tile number
+     Bit10-12 palette (4-7)
+     Bit14    X flip
+     Bit15    Y flip
+     Tiles are SNES 4bpp: planes 0/1 interleaved in the first 16 bytes,
+     planes 2/3 in the last 16. Colour 0 is see-through.
+     PCT_TRN sends 32 rows, the last 4 are below the screen.
+     */
+    fn draw_border(&self, frame: &mut [u32]) {
+        let entries = self.border_map.chunks_exact(2).take(32 * SGB_HEIGHT / 8);
+        for (i, entry) in entries.enumerate() {
+            let entry = u16::from_le_bytes([entry[0], entry[1]]);
+            let tile = &self.border_tiles[(entry & 0xFF) as usize * 32..][..32];
+            let palette = &self.border_palettes[((entry >> 10) & 0b11) as usize];
+            let (x_flip, y_flip) = (entry & 0x4000 != 0, entry & 0x8000 != 0);
+            let (tile_x, tile_y) = ((i % 32) * 8, (i / 32) * 8);
+
+            for row in 0..8 {
+                let r = if y_flip { 7 - row } else { row };
+                let planes = [tile[r * 2], tile[r * 2 + 1], tile[16 + r * 2], tile[16 + r * 2 + 1]];
+                for col in 0..8 {
+                    let bit = if x_flip { col } else { 7 - col };
+                    let color = planes.iter().enumerate()
+                        .fold(0, |color, (plane, byte)| color | (((byte >> bit) & 1) << plane));
+                    if color != 0 {
+                        frame[(tile_y + row) * SGB_WIDTH + tile_x + col] = rgb555_to_rgb888(palette[color as usize]);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+/*
+ SGB command packets are sent one bit at a time through P14/P15 (0xFF00):
+ P14=0 P15=0  reset, starts a packet
+ P14=0 P15=1  a 0 bit
+ P14=1 P15=0  a 1 bit
+ P14=1 P15=1  idle, written between every pulse
+ A packet is 16 bytes sent LSB first followed by a 0 stop bit.
+ */
+
+pub const PACKET_SIZE: usize = 16;
+
+#[derive(Default)]
+pub struct PacketReceiver {
+    // None while no packet is being sent
+    bit: Option<usize>,
+    data: [u8; PACKET_SIZE],
+    lines: u8,
+    ready: Option<[u8; PACKET_SIZE]>,
+}
+
+impl PacketReceiver {
+    // lines are bits 4-5 of the joypad write
+    pub fn write(&mut self, lines: u8) {
+        let lines = lines & 0x30;
+        if lines == self.lines {
+            return;
+        }
+        self.lines = lines;
+
+        match lines {
+            0x00 => {
+                self.bit = Some(0);
+                self.data = [0; PACKET_SIZE];
+            },
+            0x10 | 0x20 => {
+                let bit = match self.bit {
+                    Some(bit) => bit,
+                    None => return,
+                };
+                if bit == PACKET_SIZE * 8 {
+                    // stop bit, a 1 here means the packet got garbled
+                    if lines == 0x20 {
+                        self.ready = Some(self.data);
+                    }
+                    self.bit = None;
+                    return;
+                }
+                if lines == 0x10 {
+                    self.data[bit / 8] |= 1 << (bit % 8);
+                }
+                self.bit = Some(bit + 1);
+            },
+            _ => (),
+        }
+    }
+
+    pub fn take_packet(&mut self) -> Option<[u8; PACKET_SIZE]> {
+        self.ready.take()
+    }
+}
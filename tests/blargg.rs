@@ -0,0 +1,97 @@
+// Blargg's test ROMs print their results over serial, ending in "Passed"
+// or "Failed". Expects the ROMs under <roms>/blargg/, see common::rom_path.
+//
+// Like tests/mooneye.rs, ROMs marked `false` aren't expected to pass yet and
+// don't fail the run, one that passes is reported so it can be flipped.
+
+mod common;
+
+use doma_emu::runner::{Runner, StopCondition};
+
+// even the slow ones are done in well under a minute
+const MAX_FRAMES: u32 = 60 * 60;
+
+fn run_suite(roms: &[(&str, bool)]) {
+    let mut failed = Vec::new();
+    for &(rom, expect_pass) in roms {
+        let Some(path) = common::rom_path(&format!("blargg/{}", rom)) else {
+            continue;
+        };
+        let mut runner = Runner::from_file(path.to_str().unwrap()).unwrap();
+        runner.run(MAX_FRAMES, &[
+            StopCondition::Serial("Passed".to_string()),
+            StopCondition::Serial("Failed".to_string()),
+            StopCondition::InfiniteLoop,
+        ]);
+
+        // the details come after "Failed", give it a moment to print them
+        if runner.serial_text().contains("Failed") {
+            runner.run(30, &[StopCondition::InfiniteLoop]);
+        }
+
+        let output = runner.serial_text();
+        match (output.contains("Passed"), expect_pass) {
+            (false, true) => {
+                let result = if output.contains("Failed") {"failed"} else {"didn't finish"};
+                failed.push(format!("{} {}:\n{}", rom, result, output));
+            },
+            (true, false) => eprintln!("{} passes now, mark it as expected to pass", rom),
+            _ => {}
+        }
+    }
+    assert!(failed.is_empty(), "\n{}", failed.join("\n"));
+}
+
+#[test]
+fn cpu_instrs() {
+    run_suite(&[
+        ("cpu_instrs/individual/01-special.gb", true),
+        ("cpu_instrs/individual/02-interrupts.gb", true),
+        ("cpu_instrs/individual/03-op sp,hl.gb", true),
+        ("cpu_instrs/individual/04-op r,imm.gb", true),
+        ("cpu_instrs/individual/05-op rp.gb", true),
+        ("cpu_instrs/individual/06-ld r,r.gb", true),
+        ("cpu_instrs/individual/07-jr,jp,call,ret,rst.gb", true),
+        ("cpu_instrs/individual/08-misc instrs.gb", true),
+        ("cpu_instrs/individual/09-op r,r.gb", true),
+        ("cpu_instrs/individual/10-bit ops.gb", true),
+        ("cpu_instrs/individual/11-op a,(hl).gb", true),
+    ]);
+}
+
+#[test]
+fn instr_timing() {
+    run_suite(&[("instr_timing/instr_timing.gb", true)]);
+}
+
+#[test]
+fn mem_timing() {
+    // accesses land on their own M-cycle now, not confirmed against the ROMs
+    run_suite(&[
+        ("mem_timing/individual/01-read_timing.gb", false),
+        ("mem_timing/individual/02-write_timing.gb", false),
+        ("mem_timing/individual/03-modify_timing.gb", false),
+    ]);
+}
+
+#[test]
+fn halt_bug() {
+    // not run against the ROM since the HALT bug went in
+    run_suite(&[("halt_bug.gb", false)]);
+}
+
+#[test]
+fn oam_bug() {
+    // the OAM corruption bug isn't emulated. 1, 3 and 6 don't need it, but
+    // they rely on the LCD-on timing that 1 checks, unconfirmed as well
+    run_suite(&[
+        ("oam_bug/rom_singles/1-lcd_sync.gb", false),
+        ("oam_bug/rom_singles/2-causes.gb", false),
+        ("oam_bug/rom_singles/3-non_causes.gb", false),
+        ("oam_bug/rom_singles/4-scanline_timing.gb", false),
+        ("oam_bug/rom_singles/5-timing_bug.gb", false),
+        ("oam_bug/rom_singles/6-timing_no_bug.gb", false),
+        ("oam_bug/rom_singles/7-timing_effect.gb", false),
+        ("oam_bug/rom_singles/8-instr_effect.gb", false),
+    ]);
+}
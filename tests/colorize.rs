@@ -0,0 +1,66 @@
+// The CGB boot rom's colours for DMG games, picked by title
+
+use doma_emu::cartridge::Cartridge;
+use doma_emu::ppu::colorize::{cartridge_colors, ButtonCombo};
+use doma_emu::ppu::palette::rgb555_to_rgb888;
+
+// a ROM with just enough header for the lookup
+fn cartridge(title: &str, old_licensee: u8) -> Cartridge {
+    let mut rom = vec![0; 0x8000];
+    rom[0x134..0x134 + title.len()].copy_from_slice(title.as_bytes());
+    rom[0x14B] = old_licensee;
+    let mut cartridge = Cartridge::default();
+    cartridge.from_rom_file(&rom);
+    cartridge
+}
+
+fn colors(palette: [u16; 4]) -> [u32; 4] {
+    palette.map(rgb555_to_rgb888)
+}
+
+const RED: [u16; 4] = [0x7FFF, 0x421F, 0x1CF2, 0x0000];
+const BLUE: [u16; 4] = [0x7FFF, 0x7E8C, 0x7C00, 0x0000];
+
+#[test]
+fn title_with_a_unique_checksum() {
+    let zelda = cartridge_colors(&cartridge("ZELDA", 0x01));
+    assert_eq!(zelda.bg, colors(RED));
+    assert_eq!(zelda.obj0, colors([0x7FFF, 0x03E0, 0x0206, 0x0120]));
+    assert_eq!(zelda.obj1, colors(BLUE));
+}
+
+#[test]
+fn shared_checksum_goes_by_the_fourth_letter() {
+    // both sum to 0x46
+    let metroid = cartridge_colors(&cartridge("METROID2", 0x01));
+    let mario = cartridge_colors(&cartridge("SUPER MARIOLAND", 0x01));
+    assert_ne!(metroid, mario);
+    assert_eq!(metroid.bg, colors(BLUE));
+
+    // the sprites start on the last colour of one palette
+    assert_eq!(mario.obj0, colors([0x0000, 0x7FFF, 0x421F, 0x1CF2]));
+    assert_eq!(mario.obj1, mario.obj0);
+
+    // same checksum, but a 4th letter that isn't in the table
+    let other = cartridge_colors(&cartridge("MERTOID2", 0x01));
+    assert_eq!(other, ButtonCombo::RightA.colors());
+}
+
+#[test]
+fn new_licensee_code() {
+    let mut rom = vec![0; 0x8000];
+    rom[0x134..0x139].copy_from_slice(b"ZELDA");
+    rom[0x144..0x146].copy_from_slice(b"01");
+    rom[0x14B] = 0x33;
+    let mut zelda = Cartridge::default();
+    zelda.from_rom_file(&rom);
+    assert_eq!(cartridge_colors(&zelda), cartridge_colors(&cartridge("ZELDA", 0x01)));
+}
+
+#[test]
+fn default_colours() {
+    let default = ButtonCombo::RightA.colors();
+    // not published by Nintendo
+    assert_eq!(cartridge_colors(&cartridge("ZELDA", 0x08)), default);
+    assert_eq!(cartridge_colors(&cartridge("NOT A REAL GAME", 0x01)), default);
+}
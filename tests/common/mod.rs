@@ -0,0 +1,39 @@
+// Each test crate uses its own share of these
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use doma_emu::bus::{Bus, TestBus};
+use doma_emu::cpu::{CPU, Registers};
+
+// Test ROMs aren't in the repo. They're looked up under roms/ (or wherever
+// DOMA_TEST_ROMS points), laid out like the archives they come in, and
+// missing ones are skipped rather than failed.
+pub fn rom_path(path: &str) -> Option<PathBuf> {
+    let dir = std::env::var("DOMA_TEST_ROMS").unwrap_or_else(|_| "roms".to_string());
+    let rom = PathBuf::from(dir).join(path);
+    if rom.exists() {
+        Some(rom)
+    } else {
+        eprintln!("skipping {}, ROM not found", rom.display());
+        None
+    }
+}
+
+// the opcodes with no instruction behind them
+pub const ILLEGAL: [u8; 11] = [0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD];
+
+// every legal opcode followed by 0x1234 as its operand, then every CB one
+pub fn all_opcodes() -> impl Iterator<Item = Vec<u8>> {
+    (0..=0xFFu8).filter(|op| !ILLEGAL.contains(op)).map(|op| vec![op, 0x34, 0x12])
+        .chain((0..=0xFFu8).map(|op| vec![0xCB, op]))
+}
+
+// a CPU about to run `code` at 0x100, with the stack and HL in RAM
+pub fn cpu_at(code: &[u8], f: u8) -> (CPU, TestBus) {
+    let mut bus = TestBus::new();
+    for (i, &byte) in code.iter().enumerate() {
+        bus.write(0x100 + i as u16, byte);
+    }
+    (CPU::from_registers(Registers {pc: 0x100, sp: 0xD000, h: 0xC0, f, ..Registers::default()}), bus)
+}
@@ -0,0 +1,129 @@
+// The CPU on a TestBus, no MMU behind it
+
+mod common;
+
+use common::{all_opcodes, cpu_at, ILLEGAL};
+use doma_emu::bus::{Bus, BusAccess, TestBus};
+use doma_emu::cpu::{CPU, CpuEvent, Registers};
+
+fn load(bus: &mut TestBus, address: u16, code: &[u8]) {
+    for (i, &byte) in code.iter().enumerate() {
+        bus.write(address + i as u16, byte);
+    }
+}
+
+#[test]
+fn ticks_the_bus_per_instruction() {
+    let mut bus = TestBus::new();
+    // NOP, LD A,(HL), CALL 0x0200
+    load(&mut bus, 0x100, &[0x00, 0x7E, 0xCD, 0x00, 0x02]);
+    let mut cpu = CPU::from_registers(Registers {pc: 0x100, sp: 0xFFFE, h: 0xC0, ..Registers::default()});
+
+    cpu.step(&mut bus);
+    assert_eq!(bus.cycles(), 1);
+    cpu.step(&mut bus);
+    assert_eq!(bus.cycles(), 3);
+    cpu.step(&mut bus);
+    assert_eq!(bus.cycles(), 9);
+    assert_eq!(cpu.get_pc(), 0x200);
+}
+
+#[test]
+fn logs_the_stack_writes() {
+    let mut bus = TestBus::new();
+    // PUSH BC
+    load(&mut bus, 0x100, &[0xC5]);
+    let mut cpu = CPU::from_registers(Registers {pc: 0x100, sp: 0xD000, b: 0x12, c: 0x34, ..Registers::default()});
+
+    bus.start_log();
+    cpu.step(&mut bus);
+    assert_eq!(bus.take_log(), [
+        BusAccess {cycle: 0, address: 0x100, value: 0xC5, write: false},
+        BusAccess {cycle: 2, address: 0xCFFF, value: 0x12, write: true},
+        BusAccess {cycle: 3, address: 0xCFFE, value: 0x34, write: true},
+    ]);
+}
+
+#[test]
+fn dispatches_interrupts() {
+    let mut bus = TestBus::new();
+    load(&mut bus, 0x100, &[0x00]);
+    // timer and serial enabled and requested, timer goes first
+    bus.write(0xFFFF, 0b01100);
+    bus.write(0xFF0F, 0b01100);
+    let mut cpu = CPU::from_registers(Registers {pc: 0x100, sp: 0xD000, ime: true, ..Registers::default()});
+
+    cpu.step(&mut bus);
+    assert_eq!(cpu.get_pc(), 0x50);
+    assert_eq!(cpu.get_sp(), 0xCFFE);
+    assert!(!cpu.get_ime());
+    assert_eq!(bus.interrupt_flags(), 0b01000);
+    assert_eq!([bus.read(0xCFFE), bus.read(0xCFFF)], [0x01, 0x01]);
+}
+
+// every instruction ticks exactly the cycles it says it took, and never
+// touches memory twice in one of them
+#[test]
+fn one_access_per_cycle() {
+    for code in all_opcodes() {
+        let (mut cpu, mut bus) = cpu_at(&code, 0x00);
+
+        bus.start_log();
+        let m_cycles = cpu.run_instruction(&mut bus);
+        let log = bus.take_log();
+
+        assert_eq!(bus.cycles(), m_cycles as usize, "{:02X?} ticked {} cycles, took {}", code, bus.cycles(), m_cycles);
+        for pair in log.windows(2) {
+            assert!(pair[0].cycle < pair[1].cycle, "{:02X?} accessed memory twice in one cycle: {:?}", code, log);
+        }
+        assert!(log.last().is_none_or(|access| access.cycle < m_cycles as usize), "{:02X?}: {:?}", code, log);
+    }
+}
+
+#[test]
+fn illegal_opcodes_lock_up() {
+    for opcode in ILLEGAL {
+        let mut bus = TestBus::new();
+        load(&mut bus, 0x100, &[opcode, 0x00]);
+        // an interrupt waiting doesn't get it out either
+        bus.write(0xFFFF, 0x01);
+        bus.write(0xFF0F, 0x01);
+        let mut cpu = CPU::from_registers(Registers {pc: 0x100, sp: 0xD000, ime: true, ..Registers::default()});
+
+        bus.start_log();
+        cpu.step(&mut bus);
+        assert!(cpu.locked());
+        assert_eq!(cpu.take_event(), Some(CpuEvent::IllegalOpcode {opcode, pc: 0x100}));
+        assert_eq!(cpu.take_event(), None);
+
+        for _ in 0..100 {
+            cpu.step(&mut bus);
+        }
+        assert_eq!(bus.take_log().len(), 1, "{:02X} touched the bus after locking up", opcode);
+        assert_eq!(bus.cycles(), 101);
+        assert_eq!((cpu.get_pc(), cpu.get_sp()), (0x100, 0xD000));
+    }
+}
+
+// ADD HL,rr sets H on a carry out of bit 11, not bit 10
+#[test]
+fn add_hl_half_carry() {
+    let cases = [
+        (0x0800, 0x0800, true),
+        (0x0400, 0x0400, false),
+        (0x0FFF, 0x0001, true),
+        (0x8000, 0x8000, false),
+    ];
+    for (hl, bc, half_carry) in cases {
+        let mut bus = TestBus::new();
+        // ADD HL,BC
+        load(&mut bus, 0x100, &[0x09]);
+        let [h, l] = u16::to_be_bytes(hl);
+        let [b, c] = u16::to_be_bytes(bc);
+        let mut cpu = CPU::from_registers(Registers {pc: 0x100, h, l, b, c, ..Registers::default()});
+
+        cpu.step(&mut bus);
+        assert_eq!(cpu.get_hl(), hl.wrapping_add(bc));
+        assert_eq!(cpu.get_flag_h(), half_carry, "{:04X} + {:04X}", hl, bc);
+    }
+}
@@ -0,0 +1,94 @@
+use doma_emu::mmu::MMU;
+use doma_emu::ppu::viewer::TileMap;
+
+#[test]
+fn tilemap_view_outlines_the_screen() {
+    let mut mmu = MMU::new();
+    mmu.write_byte(0xFF42, 250);
+    mmu.write_byte(0xFF43, 20);
+
+    let view = mmu.ppu.tilemap_view(TileMap::Low);
+    assert_eq!((view.width, view.height), (256, 256));
+    // top left corner, and the bottom edge wraps around to the top of the map
+    assert_eq!(view.get(20, 250), 0xFF0000);
+    assert_eq!(view.get(20 + 159, (250 + 143) % 256), 0xFF0000);
+    assert_ne!(view.get(100, 100), 0xFF0000);
+}
+
+#[test]
+fn sprite_table_decodes_oam() {
+    let mut mmu = MMU::new();
+    for (offset, value) in [40u8, 30, 0x12, 0xF0].iter().enumerate() {
+        mmu.write_byte(0xFE08 + offset as u16, *value);
+    }
+
+    let table = mmu.ppu.sprite_table();
+    assert_eq!(table.len(), 40);
+    let sprite = &table[2];
+    assert_eq!((sprite.x, sprite.y, sprite.tile), (30, 40, 0x12));
+    assert!(sprite.behind_bg && sprite.y_flip && sprite.x_flip);
+    assert_eq!(sprite.dmg_palette, 1);
+    assert!(sprite.visible);
+    assert!(!table[0].visible);
+}
+
+#[test]
+fn palette_view_shows_cgb_palettes() {
+    let mut mmu = MMU::new();
+    assert_eq!(mmu.ppu.palette_view().height, 3 * 8);
+
+    mmu.set_cgb_mode(true);
+    // BG palette 1, colour 0 = pure red
+    mmu.write_byte(0xFF68, 0x88);
+    mmu.write_byte(0xFF69, 0x1F);
+    mmu.write_byte(0xFF69, 0x00);
+
+    let view = mmu.ppu.palette_view();
+    assert_eq!(view.height, 11 * 8);
+    assert_eq!(view.get(0, 4 * 8), 0xFF0000);
+}
+
+#[test]
+fn hidden_objects_leave_the_background() {
+    let frame = |objects: bool, sprite: bool| {
+        let mut mmu = MMU::new();
+        for address in 0x8010..0x8020 {
+            mmu.write_byte(address, 0xFF);
+        }
+        mmu.write_byte(0xFF40, 0x93);
+        // tile 1 over an all colour 0 background
+        for (offset, value) in [16u8, 8, 0x01, 0x00].iter().enumerate() {
+            mmu.write_byte(0xFE00 + offset as u16, if sprite {*value} else {0});
+        }
+        mmu.ppu.layers_mut().objects = objects;
+        while mmu.ppu.current_frame < 2 {
+            mmu.ppu.ppu_ticks(&mut mmu.interrupts);
+        }
+        mmu.ppu.video_buffer().to_vec()
+    };
+
+    assert_ne!(frame(true, true), frame(true, false));
+    assert_eq!(frame(false, true), frame(true, false));
+}
+
+#[test]
+fn sgb_mode_shows_real_colours() {
+    let mut mmu = MMU::new();
+    mmu.set_sgb_mode(true);
+    // tile 0 is colour 1 all over, and the map is all tile 0
+    for address in (0x8000..0x8010).step_by(2) {
+        mmu.write_byte(address, 0xFF);
+    }
+    mmu.write_byte(0xFF40, 0x91);
+    mmu.write_byte(0xFF47, 0xE4);
+
+    assert_eq!(mmu.ppu.tile_data_view(0).get(3, 3), 0xAAAAAA);
+    assert_eq!(mmu.ppu.tilemap_view(TileMap::Low).get(100, 100), 0xAAAAAA);
+    assert_eq!(mmu.ppu.palette_view().get(8, 0), 0xAAAAAA);
+
+    // BGP still applies
+    mmu.write_byte(0xFF47, 0x1B);
+    assert_eq!(mmu.ppu.tilemap_view(TileMap::Low).get(100, 100), 0x555555);
+    assert_eq!(mmu.ppu.palette_view().get(0, 0), 0x000000);
+    assert_eq!(mmu.ppu.tile_data_view(0).get(3, 3), 0xAAAAAA);
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use common::{all_opcodes, cpu_at};
+use doma_emu::cpu::disasm::{disassemble, disassemble_all};
+use doma_emu::cpu::opcodes::instruction_length;
+
+#[test]
+fn mnemonics() {
+    let cases: [(&[u8], &str); 16] = [
+        (&[0x2A], "LD A,(HL+)"),
+        (&[0x32], "LD (HL-),A"),
+        (&[0x20, 0xF9], "JR NZ,$-5"),
+        (&[0x18, 0xFE], "JR $"),
+        (&[0x38, 0x03], "JR C,$+5"),
+        (&[0xCB, 0x7C], "BIT 7,H"),
+        (&[0xCB, 0x36], "SWAP (HL)"),
+        (&[0xCB, 0xC7], "SET 0,A"),
+        (&[0x01, 0x34, 0x12], "LD BC,$1234"),
+        (&[0x08, 0x00, 0xC0], "LD ($C000),SP"),
+        (&[0xE0, 0x40], "LDH ($FF40),A"),
+        (&[0xF2], "LD A,($FF00+C)"),
+        (&[0xE8, 0xFE], "ADD SP,-2"),
+        (&[0xF1], "POP AF"),
+        (&[0x9E], "SBC A,(HL)"),
+        (&[0xFD], "DB $FD"),
+    ];
+    for (bytes, text) in cases {
+        assert_eq!(disassemble(bytes, 0x100).text, text, "{:02X?}", bytes);
+    }
+}
+
+#[test]
+fn lengths_cycles_and_targets() {
+    let call = disassemble(&[0xCC, 0x00, 0x40], 0x200);
+    assert_eq!((call.length, call.cycles, call.cycles_taken, call.target), (3, 3, 6, Some(0x4000)));
+
+    let jr = disassemble(&[0x20, 0xF9], 0x105);
+    assert_eq!((jr.length, jr.cycles, jr.cycles_taken, jr.target), (2, 2, 3, Some(0x100)));
+
+    let rst = disassemble(&[0xEF], 0x300);
+    assert_eq!((rst.length, rst.cycles, rst.target), (1, 4, Some(0x28)));
+
+    let bit = disassemble(&[0xCB, 0x46], 0);
+    assert_eq!((bit.text.as_str(), bit.length, bit.cycles), ("BIT 0,(HL)", 2, 3));
+    let res = disassemble(&[0xCB, 0x86], 0);
+    assert_eq!((res.text.as_str(), res.cycles), ("RES 0,(HL)", 4));
+
+    // operands past the end read as 0
+    assert_eq!(disassemble(&[0xC3], 0).text, "JP $0000");
+}
+
+#[test]
+fn walks_a_block() {
+    let listing: Vec<String> = disassemble_all(&[0x00, 0xC3, 0x50, 0x01, 0x3E, 0x12, 0xCB, 0x11], 0x100)
+        .iter().map(|instruction| format!("{:04X} {}", instruction.address, instruction)).collect();
+    assert_eq!(listing, ["0100 NOP", "0101 JP $0150", "0104 LD A,$12", "0106 RL C"]);
+}
+
+// M-cycles the bus actually gets ticked running `code` with the given flags
+fn run(code: &[u8], f: u8) -> u8 {
+    let (mut cpu, mut bus) = cpu_at(code, f);
+    cpu.run_instruction(&mut bus);
+    bus.cycles() as u8
+}
+
+// the tables agree with what the CPU actually does
+#[test]
+fn agrees_with_the_cpu() {
+    for code in all_opcodes() {
+        let instruction = disassemble(&code, 0x100);
+        // STOP really is 2 bytes, executing it skips the second one
+        if code[0] != 0x10 {
+            assert_eq!(instruction.length, instruction_length(code[0]), "{} length", instruction);
+        }
+
+        let mut ran = [run(&code, 0x00), run(&code, 0xF0)];
+        ran.sort();
+        let mut expected = [instruction.cycles, instruction.cycles_taken];
+        expected.sort();
+        assert_eq!(ran, expected, "{} ({:02X?}) cycles", instruction, &code[..instruction.length as usize]);
+    }
+}
+
+// anything that doesn't jump moves PC past exactly its own bytes
+#[test]
+fn moves_pc_by_the_length() {
+    for code in all_opcodes() {
+        let instruction = disassemble(&code, 0x100);
+        if instruction.target.is_some() || instruction.text.starts_with("RET") || instruction.text == "JP HL" {
+            continue;
+        }
+        let (mut cpu, mut bus) = cpu_at(&code, 0x00);
+        cpu.run_instruction(&mut bus);
+        assert_eq!(cpu.get_pc(), 0x100 + instruction.length as u16, "{} ({:02X?})", instruction, code[0]);
+    }
+}
@@ -0,0 +1,81 @@
+use doma_emu::filter::{Filter, Upscaler};
+
+const W: u32 = 0xFFFFFF;
+const K: u32 = 0x000000;
+
+#[test]
+fn nearest_any_scale() {
+    let mut filter = Filter::new(Upscaler::Nearest(3));
+    let out = filter.apply(2, 1, &[W, K]);
+    assert_eq!(filter.output_size(2, 1), (6, 3));
+    assert_eq!(out, [W, W, W, K, K, K].repeat(3));
+}
+
+#[test]
+fn scale2x_rounds_diagonals() {
+    // a black diagonal on white
+    let pixels = [
+        K, W, W,
+        W, K, W,
+        W, W, K,
+    ];
+    let out = Filter::new(Upscaler::Scale2x).apply(3, 3, &pixels);
+    assert_eq!(out.len(), 36);
+    // the white pixel right of the centre gets its lower left corner filled in
+    let at = |x: usize, y: usize| out[y * 6 + x];
+    assert_eq!((at(4, 2), at(5, 2), at(4, 3), at(5, 3)), (W, W, K, W));
+    // flat areas stay flat
+    assert_eq!((at(4, 0), at(5, 0), at(4, 1), at(5, 1)), (W, W, W, W));
+}
+
+#[test]
+fn scale3x_keeps_lone_pixels_square() {
+    let mut pixels = [W; 9];
+    pixels[4] = K;
+    let out = Filter::new(Upscaler::Scale3x).apply(3, 3, &pixels);
+    for y in 0..9 {
+        for x in 0..9 {
+            let inside = (3..6).contains(&x) && (3..6).contains(&y);
+            assert_eq!(out[y * 9 + x], if inside {K} else {W}, "x:{} y:{}", x, y);
+        }
+    }
+}
+
+#[test]
+fn lcd_grid_darkens_pixel_edges() {
+    let mut filter = Filter::new(Upscaler::Nearest(2));
+    filter.lcd_grid = true;
+    let out = filter.apply(1, 1, &[0x808080]);
+    assert_eq!(out, [0x808080, 0x606060, 0x606060, 0x606060]);
+}
+
+#[test]
+fn blending_mixes_the_last_frame() {
+    let mut filter = Filter::new(Upscaler::Nearest(1));
+    filter.blend = true;
+    assert_eq!(filter.apply(1, 1, &[W]), [W]);
+    assert_eq!(filter.apply(1, 1, &[K]), [0x7F7F7F]);
+    assert_eq!(filter.apply(1, 1, &[K]), [K]);
+}
+
+#[test]
+fn filter_names() {
+    assert_eq!(Upscaler::from_name("nearest4"), Ok(Upscaler::Nearest(4)));
+    assert_eq!(Upscaler::from_name("scale3x"), Ok(Upscaler::Scale3x));
+    assert!(Upscaler::from_name("nearest0").is_err());
+    assert!(Upscaler::from_name("hq2x").is_err());
+}
+
+// the window only shows some frames, the blend still goes by the one
+// right before
+#[test]
+fn blending_sees_every_frame() {
+    let mut filter = Filter::new(Upscaler::Nearest(2));
+    filter.blend = true;
+    for frame in [W, W, K] {
+        filter.blend_frame(&[frame]);
+    }
+    let last = filter.blend_frame(&[K]);
+    assert_eq!(last, [K]);
+    assert_eq!(filter.scale(1, 1, &last), [K; 4]);
+}
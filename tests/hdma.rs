@@ -0,0 +1,120 @@
+// CGB VRAM DMA (HDMA1-HDMA5) on a bare MMU
+
+use doma_emu::bus::Bus;
+use doma_emu::mmu::MMU;
+
+fn cgb() -> MMU {
+    let mut mmu = MMU::new();
+    mmu.set_cgb_mode(true);
+    // something to copy, 0x40 bytes at 0xC100
+    for i in 0..0x40u16 {
+        mmu.write_byte(0xC100 + i, 0x80 | i as u8);
+    }
+    mmu
+}
+
+// source 0xC100 and destination 0x8100, with the low nibbles and the top
+// destination bits set to show they're ignored
+fn set_addresses(mmu: &mut MMU) {
+    mmu.write_byte(0xFF51, 0xC1);
+    mmu.write_byte(0xFF52, 0x0F);
+    mmu.write_byte(0xFF53, 0xE1);
+    mmu.write_byte(0xFF54, 0x0F);
+}
+
+// M-cycles until the CPU gets the bus back
+fn run_stall(mmu: &mut MMU) -> usize {
+    let mut cycles = 0;
+    while mmu.cpu_stalled() {
+        mmu.tick(1);
+        cycles += 1;
+        assert!(cycles < 10_000, "DMA never finished");
+    }
+    cycles
+}
+
+fn copied(mmu: &MMU) -> usize {
+    (0..0x40u16).take_while(|&i| mmu.ppu.read_vram(0x8100 + i) == 0x80 | i as u8).count()
+}
+
+#[test]
+fn general_purpose_copies_everything_at_once() {
+    let mut mmu = cgb();
+    set_addresses(&mut mmu);
+    // 2 blocks of 16 bytes
+    mmu.write_byte(0xFF55, 0x01);
+    assert!(mmu.cpu_stalled());
+    assert_eq!(mmu.read_byte(0xFF55), 0x01);
+
+    // 2 bytes per M-cycle
+    assert_eq!(run_stall(&mut mmu), 16);
+    assert_eq!(copied(&mmu), 0x20);
+    assert_eq!(mmu.read_byte(0xFF55), 0xFF);
+}
+
+#[test]
+fn one_byte_per_cycle_in_double_speed() {
+    let mut mmu = cgb();
+    mmu.write_byte(0xFF4D, 0x01);
+    mmu.stop();
+    run_stall(&mut mmu);
+    assert_eq!(mmu.read_byte(0xFF4D) & 0x80, 0x80);
+
+    set_addresses(&mut mmu);
+    mmu.write_byte(0xFF55, 0x01);
+    assert_eq!(run_stall(&mut mmu), 32);
+    assert_eq!(copied(&mmu), 0x20);
+}
+
+// ticks until the PPU is in HBlank, then out of it again
+fn next_hblank(mmu: &mut MMU) {
+    while mmu.ppu.in_hblank() {
+        mmu.tick(1);
+    }
+    while !mmu.ppu.in_hblank() {
+        mmu.tick(1);
+    }
+    run_stall(mmu);
+}
+
+#[test]
+fn hblank_copies_a_block_per_line() {
+    let mut mmu = cgb();
+    mmu.write_byte(0xFF40, 0x91);
+    set_addresses(&mut mmu);
+    // 3 blocks. Line 0 is already in HBlank, so the first one goes right away
+    assert!(mmu.ppu.in_hblank());
+    mmu.write_byte(0xFF55, 0x82);
+    assert_eq!(mmu.read_byte(0xFF55), 0x02);
+    // it's picked up on the next M-cycle, which copies the first 2 bytes
+    mmu.tick(1);
+    assert_eq!(1 + run_stall(&mut mmu), 8);
+    assert_eq!(copied(&mmu), 16);
+    assert_eq!(mmu.read_byte(0xFF55), 0x01);
+
+    for blocks in 2..=3 {
+        next_hblank(&mut mmu);
+        assert_eq!(copied(&mmu), blocks * 16);
+    }
+    assert_eq!(mmu.read_byte(0xFF55), 0xFF);
+    next_hblank(&mut mmu);
+    assert_eq!(copied(&mmu), 0x30);
+}
+
+#[test]
+fn cancelling_hblank_dma() {
+    let mut mmu = cgb();
+    mmu.write_byte(0xFF40, 0x91);
+    set_addresses(&mut mmu);
+    mmu.write_byte(0xFF55, 0x83);
+    mmu.tick(1);
+    run_stall(&mut mmu);
+    assert_eq!(mmu.read_byte(0xFF55), 0x02);
+
+    // bit 7 clear stops it, the blocks that were left stay readable
+    mmu.write_byte(0xFF55, 0x00);
+    assert_eq!(mmu.read_byte(0xFF55), 0x82);
+    next_hblank(&mut mmu);
+    next_hblank(&mut mmu);
+    assert_eq!(copied(&mmu), 16);
+}
@@ -0,0 +1,100 @@
+use std::process::Command;
+
+use doma_emu::runner::{Runner, RunResult, StopCondition};
+
+// prints `text` over serial, then `jr -2` forever
+fn serial_rom(text: &str) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    // nop, jp 0x0150 over the header
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    let code = [
+        0x21, 0x00, 0x02, // ld hl, 0x0200
+        0x2A,             // ld a, (hl+)
+        0xA7,             // and a
+        0x28, 0x08,       // jr z, +8
+        0xE0, 0x01,       // ldh (0x01), a
+        0x3E, 0x81,       // ld a, 0x81
+        0xE0, 0x02,       // ldh (0x02), a
+        0x18, 0xF4,       // jr -12
+        0x18, 0xFE,       // jr -2
+    ];
+    rom[0x150..0x150 + code.len()].copy_from_slice(&code);
+    rom[0x200..0x200 + text.len()].copy_from_slice(text.as_bytes());
+    rom
+}
+
+#[test]
+fn stops_on_serial_text() {
+    let mut runner = Runner::new(&serial_rom("cpu_instrs\nPassed\n"));
+    let result = runner.run(60, &[StopCondition::Serial("Passed".to_string())]);
+    assert_eq!(result, RunResult::Stopped(StopCondition::Serial("Passed".to_string())));
+    assert!(runner.serial_text().starts_with("cpu_instrs\nPassed"));
+    assert_eq!(runner.frame(), 0);
+}
+
+#[test]
+fn stops_on_pc_and_infinite_loop() {
+    let mut runner = Runner::new(&serial_rom("hi"));
+    assert_eq!(runner.run(60, &[StopCondition::Pc(0x0153)]), RunResult::Stopped(StopCondition::Pc(0x0153)));
+
+    assert_eq!(runner.run(60, &[StopCondition::InfiniteLoop]), RunResult::Stopped(StopCondition::InfiniteLoop));
+    assert_eq!(runner.cpu.get_pc(), 0x015F);
+    assert_eq!(runner.serial_text(), "hi");
+}
+
+#[test]
+fn runs_out_of_frames() {
+    let mut runner = Runner::new(&serial_rom("Failed"));
+    assert_eq!(runner.run(5, &[StopCondition::Serial("Passed".to_string())]), RunResult::OutOfFrames);
+    assert_eq!(runner.frame(), 5);
+}
+
+#[test]
+fn binary_exit_status() {
+    let dir = std::env::temp_dir();
+    let rom = dir.join("doma_emu_headless_test.gb");
+    std::fs::write(&rom, serial_rom("Passed\n")).unwrap();
+    let screen = dir.join("doma_emu_headless_test.png");
+
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_headless"))
+            .arg(&rom)
+            .args(args)
+            .status()
+            .unwrap()
+            .code()
+    };
+    assert_eq!(run(&["--frames", "10", "--until-serial", "Passed", "--screen", screen.to_str().unwrap()]), Some(0));
+    assert!(screen.exists());
+    assert_eq!(run(&["--frames", "10", "--fail-serial", "Pass"]), Some(1));
+    assert_eq!(run(&["--frames", "10", "--until-pc", "0x4000"]), Some(1));
+    assert_eq!(run(&["--frames", "2"]), Some(0));
+    assert_eq!(run(&["--bogus"]), Some(2));
+
+    std::fs::remove_file(&rom).unwrap();
+    std::fs::remove_file(&screen).unwrap();
+}
+
+#[test]
+fn stops_on_ld_b_b() {
+    let mut rom = serial_rom("");
+    // ld b,3 / ld c,5 / ld d,8 / ld e,13 / ld h,21 / ld l,34 / ld b,b
+    let code = [0x06, 3, 0x0E, 5, 0x16, 8, 0x1E, 13, 0x26, 21, 0x2E, 34, 0x40, 0x18, 0xFE];
+    rom[0x150..0x150 + code.len()].copy_from_slice(&code);
+
+    let mut runner = Runner::new(&rom);
+    assert_eq!(runner.run(10, &[StopCondition::Breakpoint]), RunResult::Stopped(StopCondition::Breakpoint));
+    assert_eq!(runner.cpu.get_pc(), 0x015D);
+    assert!(runner.fibonacci_registers());
+}
+
+#[test]
+fn stops_on_lockup() {
+    let mut rom = serial_rom("");
+    // ld a,1 / an illegal opcode
+    rom[0x150..0x153].copy_from_slice(&[0x3E, 1, 0xDD]);
+
+    let mut runner = Runner::new(&rom);
+    assert_eq!(runner.run(10, &[StopCondition::Lockup]), RunResult::Stopped(StopCondition::Lockup));
+    assert_eq!(runner.cpu.get_pc(), 0x0152);
+}
@@ -0,0 +1,134 @@
+// HALT and interrupt dispatch on a TestBus, where IE and IF are just the
+// bytes at 0xFFFF and 0xFF0F
+
+mod common;
+
+use common::cpu_at;
+use doma_emu::bus::{Bus, BusAccess};
+
+const VBLANK: u8 = 1 << 0;
+const TIMER: u8 = 1 << 2;
+
+#[test]
+fn halt_bug_reads_the_next_byte_twice() {
+    // HALT, LD A,0x14 with IME off and an interrupt waiting
+    let (mut cpu, mut bus) = cpu_at(&[0x76, 0x3E, 0x14], 0x00);
+    bus.write(0xFFFF, TIMER);
+    bus.write(0xFF0F, TIMER);
+
+    cpu.step(&mut bus);
+    assert!(!cpu.get_halted());
+    assert_eq!(cpu.get_pc(), 0x101);
+
+    // the LD reads its own opcode as the operand, then 0x14 runs as INC D
+    cpu.step(&mut bus);
+    assert_eq!(cpu.get_a(), 0x3E);
+    assert_eq!(cpu.get_pc(), 0x102);
+    cpu.step(&mut bus);
+    assert_eq!(cpu.get_d(), 0x01);
+    assert_eq!(cpu.get_pc(), 0x103);
+    // nothing got served
+    assert_eq!(bus.interrupt_flags(), TIMER);
+}
+
+#[test]
+fn halt_without_ime_wakes_without_dispatch() {
+    // HALT, NOP
+    let (mut cpu, mut bus) = cpu_at(&[0x76, 0x00], 0x00);
+    bus.write(0xFFFF, TIMER);
+
+    cpu.step(&mut bus);
+    assert!(cpu.get_halted());
+    for _ in 0..10 {
+        cpu.step(&mut bus);
+    }
+    assert!(cpu.get_halted());
+    assert_eq!(bus.cycles(), 11);
+
+    // requested but not enabled doesn't wake it
+    bus.write(0xFF0F, VBLANK);
+    cpu.step(&mut bus);
+    assert!(cpu.get_halted());
+
+    bus.write(0xFF0F, VBLANK | TIMER);
+    cpu.step(&mut bus);
+    assert!(!cpu.get_halted());
+    assert_eq!(cpu.get_pc(), 0x101);
+    assert_eq!(cpu.get_sp(), 0xD000);
+    assert_eq!(bus.interrupt_flags(), VBLANK | TIMER);
+
+    cpu.step(&mut bus);
+    assert_eq!(cpu.get_pc(), 0x102);
+}
+
+#[test]
+fn halt_with_ime_dispatches_on_wake() {
+    let (mut cpu, mut bus) = cpu_at(&[0x76, 0x00], 0x00);
+    cpu.set_ime(true);
+    bus.write(0xFFFF, TIMER);
+
+    cpu.step(&mut bus);
+    cpu.step(&mut bus);
+    assert!(cpu.get_halted());
+
+    bus.write(0xFF0F, TIMER);
+    cpu.step(&mut bus);
+    assert!(!cpu.get_halted());
+    assert_eq!(cpu.get_pc(), 0x50);
+    // returns to the instruction after HALT
+    assert_eq!([bus.read(0xCFFE), bus.read(0xCFFF)], [0x01, 0x01]);
+    assert_eq!(bus.interrupt_flags(), 0);
+}
+
+#[test]
+fn dispatch_takes_five_cycles() {
+    // NOP
+    let (mut cpu, mut bus) = cpu_at(&[0x00], 0x00);
+    cpu.set_ime(true);
+    bus.write(0xFFFF, TIMER);
+    bus.write(0xFF0F, TIMER);
+
+    bus.start_log();
+    cpu.step(&mut bus);
+    assert_eq!(bus.cycles(), 1 + 5);
+    assert_eq!(bus.take_log(), [
+        BusAccess {cycle: 0, address: 0x100, value: 0x00, write: false},
+        BusAccess {cycle: 3, address: 0xCFFF, value: 0x01, write: true},
+        BusAccess {cycle: 4, address: 0xCFFE, value: 0x01, write: true},
+    ]);
+    assert_eq!(cpu.get_pc(), 0x50);
+    assert!(!cpu.get_ime());
+}
+
+#[test]
+fn ie_write_during_push_cancels_dispatch() {
+    // SP at 0x0000, so the high byte of PC (0x01) goes to IE and only
+    // leaves VBlank enabled, which isn't requested
+    let (mut cpu, mut bus) = cpu_at(&[0x00], 0x00);
+    cpu.set_sp(0x0000);
+    cpu.set_ime(true);
+    bus.write(0xFFFF, TIMER);
+    bus.write(0xFF0F, TIMER);
+
+    cpu.step(&mut bus);
+    assert_eq!(cpu.get_pc(), 0x0000);
+    assert!(!cpu.get_ime());
+    assert_eq!(bus.interrupt_enable(), VBLANK);
+    // the timer is still waiting
+    assert_eq!(bus.interrupt_flags(), TIMER);
+    assert_eq!(bus.cycles(), 1 + 5);
+}
+
+#[test]
+fn ie_write_during_push_switches_interrupt() {
+    // same push, but VBlank is requested too so it gets served instead
+    let (mut cpu, mut bus) = cpu_at(&[0x00], 0x00);
+    cpu.set_sp(0x0000);
+    cpu.set_ime(true);
+    bus.write(0xFFFF, TIMER);
+    bus.write(0xFF0F, VBLANK | TIMER);
+
+    cpu.step(&mut bus);
+    assert_eq!(cpu.get_pc(), 0x40);
+    assert_eq!(bus.interrupt_flags(), TIMER);
+}
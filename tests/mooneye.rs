@@ -0,0 +1,86 @@
+// Mooneye's acceptance tests finish with a LD B,B and leave 3/5/8/13/21/34
+// in B/C/D/E/H/L when they pass. Expects the ROMs under
+// <roms>/mooneye/acceptance/, see common::rom_path.
+//
+// Tests marked `false` aren't known to pass. Some haven't been run since
+// what they test went in (ie_push, and the timer ones now that memory
+// accesses are timed per M-cycle). They don't fail the run, but one that
+// passes is reported so it can be flipped to `true`.
+
+mod common;
+
+use doma_emu::runner::{Runner, RunResult, StopCondition};
+
+const MAX_FRAMES: u32 = 60 * 20;
+
+fn run_group(group: &str, roms: &[(&str, bool)]) {
+    let mut failed = Vec::new();
+    for &(rom, expect_pass) in roms {
+        let Some(path) = common::rom_path(&format!("mooneye/acceptance/{}/{}.gb", group, rom)) else {
+            continue;
+        };
+        let mut runner = Runner::from_file(path.to_str().unwrap()).unwrap();
+        let result = runner.run(MAX_FRAMES, &[StopCondition::Breakpoint]);
+        let passed = result == RunResult::Stopped(StopCondition::Breakpoint) && runner.fibonacci_registers();
+
+        match (passed, expect_pass) {
+            (false, true) => failed.push(format!("{}/{} failed: {}", group, rom, runner.registers())),
+            (true, false) => eprintln!("{}/{} passes now, mark it as expected to pass", group, rom),
+            _ => {}
+        }
+    }
+    assert!(failed.is_empty(), "\n{}", failed.join("\n"));
+}
+
+#[test]
+fn timer() {
+    run_group("timer", &[
+        ("div_write", true),
+        ("rapid_toggle", false),
+        ("tim00", true),
+        ("tim00_div_trigger", true),
+        ("tim01", true),
+        ("tim01_div_trigger", true),
+        ("tim10", true),
+        ("tim10_div_trigger", true),
+        ("tim11", true),
+        ("tim11_div_trigger", true),
+        ("tima_reload", false),
+        ("tima_write_reloading", false),
+        ("tma_write_reloading", false),
+    ]);
+}
+
+#[test]
+fn interrupts() {
+    run_group("interrupts", &[
+        ("ie_push", false),
+    ]);
+}
+
+#[test]
+fn oam_dma() {
+    run_group("oam_dma", &[
+        ("basic", true),
+        ("reg_read", false),
+        ("sources-GS", false),
+    ]);
+}
+
+#[test]
+fn ppu() {
+    run_group("ppu", &[
+        ("hblank_ly_scx_timing-GS", false),
+        ("intr_1_2_timing-GS", false),
+        ("intr_2_0_timing", false),
+        ("intr_2_mode0_timing", false),
+        ("intr_2_mode0_timing_sprites", false),
+        ("intr_2_mode3_timing", false),
+        ("intr_2_oam_ok_timing", false),
+        ("lcdon_timing-GS", false),
+        ("lcdon_write_timing-GS", false),
+        ("stat_irq_blocking", false),
+        ("stat_lyc_onoff", false),
+        ("vblank_stat_intr-GS", true),
+    ]);
+}
@@ -0,0 +1,69 @@
+// The DMG palette config file and picking palettes per layer
+
+use doma_emu::ppu::palette::{PaletteConfig, PaletteLayer, CLASSIC_GREEN, GREYSCALE, POCKET_GREY};
+
+#[test]
+fn valid_file() {
+    let config = PaletteConfig::parse("
+        # a user palette, selected before and after it's defined
+        obj0 = gbc_brown
+        gbc_brown = FFFFFF FFAD63 843100 000000   # lightest first
+        bg = classic_green
+
+        obj1 = pocket_grey
+    ").unwrap();
+
+    let colors = config.colors();
+    assert_eq!(colors.bg, CLASSIC_GREEN);
+    assert_eq!(colors.obj0, [0xFFFFFF, 0xFFAD63, 0x843100, 0x000000]);
+    assert_eq!(colors.obj1, POCKET_GREY);
+    assert_eq!(config.palettes().len(), 5);
+}
+
+#[test]
+fn user_palette_replaces_a_builtin_one() {
+    let config = PaletteConfig::parse("greyscale = 0x111111 222222 333333 444444").unwrap();
+    assert_eq!(config.palettes().len(), 4);
+    assert_eq!(config.colors().bg, [0x111111, 0x222222, 0x333333, 0x444444]);
+}
+
+#[test]
+fn malformed_colours() {
+    let cases = [
+        ("mine = FFFFFF GGGGGG 000000 000000", "line 1: `GGGGGG` is not an RGB hex colour"),
+        ("mine = FFFFFF FFF 000000 000000", "line 1: `FFF` is not an RGB hex colour"),
+        ("\nmine = FFFFFF 000000 000000", "line 2: a palette has exactly 4 colours"),
+        ("mine = FFFFFF 000000 000000 000000 000000", "line 1: a palette has exactly 4 colours"),
+        ("just a line", "line 1: expected `name = value`"),
+    ];
+    for (text, error) in cases {
+        assert_eq!(PaletteConfig::parse(text).unwrap_err(), error, "{:?}", text);
+    }
+}
+
+#[test]
+fn unknown_palette_names() {
+    for layer in ["bg", "obj0", "obj1"] {
+        let text = format!("# nothing\n{} = no_such_palette", layer);
+        assert_eq!(PaletteConfig::parse(&text).unwrap_err(), "line 2: unknown palette `no_such_palette`");
+    }
+}
+
+#[test]
+fn select_and_cycle() {
+    let mut config = PaletteConfig::default();
+    assert!(!config.select(PaletteLayer::Obj1, "nope"));
+    assert!(config.select(PaletteLayer::Obj1, "high_contrast"));
+
+    // the last preset wraps around to the first, the other layers stay put
+    assert_eq!(config.cycle(PaletteLayer::Obj1).colors, GREYSCALE);
+    assert_eq!(config.cycle(PaletteLayer::Obj1).colors, CLASSIC_GREEN);
+    assert_eq!(config.selected(PaletteLayer::Bg).name, "greyscale");
+    assert_eq!(config.selected(PaletteLayer::Obj0).name, "greyscale");
+
+    let count = config.palettes().len();
+    for _ in 0..count {
+        config.cycle(PaletteLayer::Bg);
+    }
+    assert_eq!(config.selected(PaletteLayer::Bg).name, "greyscale");
+}
@@ -0,0 +1,38 @@
+use std::fs;
+
+use doma_emu::mmu::MMU;
+use doma_emu::recorder::{Recorder, rgb_to_yuv};
+
+#[test]
+fn records_every_finished_frame() {
+    let path = std::env::temp_dir().join("doma_emu_recorder_test.y4m");
+    let path = path.to_str().unwrap();
+
+    let mut mmu = MMU::new();
+    let mut recorder = Recorder::new(path, 160, 144).unwrap();
+    let mut prev_frame = mmu.ppu.current_frame;
+    while mmu.ppu.current_frame < 3 {
+        mmu.ppu.ppu_ticks(&mut mmu.interrupts);
+        if prev_frame != mmu.ppu.current_frame {
+            recorder.push_frame(mmu.ppu.video_buffer()).unwrap();
+            prev_frame = mmu.ppu.current_frame;
+        }
+    }
+    assert_eq!(recorder.finish().unwrap(), 3);
+
+    let data = fs::read(path).unwrap();
+    fs::remove_file(path).unwrap();
+    let header = b"YUV4MPEG2 W160 H144 F4194304:70224 Ip A1:1 C444\n";
+    assert!(data.starts_with(header));
+    let frame = b"FRAME\n".len() + 160 * 144 * 3;
+    assert_eq!(data.len(), header.len() + 3 * frame);
+    assert!(data[header.len() + 2 * frame..].starts_with(b"FRAME\n"));
+}
+
+#[test]
+fn studio_range_colors() {
+    assert_eq!(rgb_to_yuv(0x000000), (16, 128, 128));
+    assert_eq!(rgb_to_yuv(0xFFFFFF), (235, 128, 128));
+    let (_, u, v) = rgb_to_yuv(0xFF0000);
+    assert!(u < 128 && v > 200);
+}
@@ -0,0 +1,232 @@
+// The scanline renderer has to draw static frames exactly like the pixel FIFO.
+
+use doma_emu::mmu::MMU;
+use doma_emu::ppu::RenderMode;
+
+const FRAMES: u32 = 3;
+
+fn render(mode: RenderMode, setup: &dyn Fn(&mut MMU)) -> Vec<u32> {
+    let mut mmu = MMU::new();
+    mmu.ppu.set_render_mode(mode);
+    setup(&mut mmu);
+
+    while mmu.ppu.current_frame < FRAMES {
+        mmu.ppu.ppu_ticks(&mut mmu.interrupts);
+    }
+    mmu.ppu.video_buffer().to_vec()
+}
+
+fn compare(setup: impl Fn(&mut MMU)) {
+    let accurate = render(RenderMode::Accurate, &setup);
+    let fast = render(RenderMode::Fast, &setup);
+
+    assert!(accurate.iter().any(|&color| color != accurate[0]), "the scene drew nothing");
+    for (i, (a, f)) in accurate.iter().zip(fast.iter()).enumerate() {
+        assert_eq!(a, f, "pixel x:{} y:{} differs", i % 160, i / 160);
+    }
+}
+
+// tile data, both maps, and palettes that use every shade
+fn fill_vram(mmu: &mut MMU) {
+    let mut seed: u32 = 0x1234_5678;
+    for address in 0x8000..=0x9FFF {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        mmu.write_byte(address, (seed >> 16) as u8);
+    }
+    mmu.write_byte(0xFF47, 0xE4);
+    mmu.write_byte(0xFF48, 0xD2);
+    mmu.write_byte(0xFF49, 0x1B);
+}
+
+fn place_sprites(mmu: &mut MMU) {
+    // y, x, tile, flags: overlapping, partly off screen, flipped, behind BG, OBP1
+    let sprites: [[u8; 4]; 12] = [
+        [16, 8, 0x01, 0x00],
+        [20, 12, 0x02, 0x20],
+        [30, 4, 0x03, 0x40],
+        [40, 60, 0x04, 0x80],
+        [40, 64, 0x05, 0x10],
+        [41, 66, 0x06, 0x60],
+        [80, 100, 0x07, 0x00],
+        [80, 163, 0x08, 0x00],
+        [100, 30, 0x09, 0x90],
+        [150, 80, 0x0A, 0x20],
+        [60, 0, 0x0B, 0x00],
+        [60, 120, 0x0C, 0xF0],
+    ];
+    for (i, sprite) in sprites.iter().enumerate() {
+        for (offset, value) in sprite.iter().enumerate() {
+            mmu.write_byte(0xFE00 + (i * 4 + offset) as u16, *value);
+        }
+    }
+    // the rest are moved off screen
+    for i in sprites.len()..40 {
+        mmu.write_byte(0xFE00 + (i * 4) as u16, 0);
+    }
+}
+
+#[test]
+fn background_with_scroll() {
+    compare(|mmu| {
+        fill_vram(mmu);
+        mmu.write_byte(0xFF42, 7);
+        mmu.write_byte(0xFF43, 13);
+        mmu.write_byte(0xFF40, 0x91);
+    });
+}
+
+#[test]
+fn background_signed_tile_data() {
+    compare(|mmu| {
+        fill_vram(mmu);
+        mmu.write_byte(0xFF42, 200);
+        mmu.write_byte(0xFF43, 250);
+        mmu.write_byte(0xFF40, 0x89);
+    });
+}
+
+#[test]
+fn window() {
+    compare(|mmu| {
+        fill_vram(mmu);
+        mmu.write_byte(0xFF43, 5);
+        mmu.write_byte(0xFF4A, 30);
+        mmu.write_byte(0xFF4B, 55);
+        mmu.write_byte(0xFF40, 0xF1);
+    });
+}
+
+#[test]
+fn window_at_left_edge() {
+    // WX=0 is shifted by SCX % 8, 1-6 are cut off on the left
+    for wx in [0, 3, 7] {
+        compare(|mmu| {
+            fill_vram(mmu);
+            mmu.write_byte(0xFF43, 3);
+            mmu.write_byte(0xFF4B, wx);
+            mmu.write_byte(0xFF40, 0xE1);
+        });
+    }
+}
+
+#[test]
+fn sprites() {
+    compare(|mmu| {
+        fill_vram(mmu);
+        place_sprites(mmu);
+        mmu.write_byte(0xFF43, 6);
+        mmu.write_byte(0xFF40, 0x93);
+    });
+}
+
+#[test]
+fn tall_sprites_over_window() {
+    compare(|mmu| {
+        fill_vram(mmu);
+        place_sprites(mmu);
+        mmu.write_byte(0xFF43, 2);
+        mmu.write_byte(0xFF4A, 50);
+        mmu.write_byte(0xFF4B, 40);
+        mmu.write_byte(0xFF40, 0xF7);
+    });
+}
+
+#[test]
+fn cgb_attributes_and_palettes() {
+    compare(|mmu| {
+        mmu.set_cgb_mode(true);
+        fill_vram(mmu);
+        // bank 1 gets random attributes as well
+        mmu.write_byte(0xFF4F, 1);
+        fill_vram(mmu);
+        mmu.write_byte(0xFF4F, 0);
+        place_sprites(mmu);
+
+        mmu.write_byte(0xFF68, 0x80);
+        mmu.write_byte(0xFF6A, 0x80);
+        for i in 0..64u8 {
+            mmu.write_byte(0xFF69, i.wrapping_mul(37));
+            mmu.write_byte(0xFF6B, i.wrapping_mul(91));
+        }
+        mmu.write_byte(0xFF43, 4);
+        mmu.write_byte(0xFF4A, 70);
+        mmu.write_byte(0xFF4B, 90);
+        mmu.write_byte(0xFF40, 0xF3);
+    });
+}
+
+#[test]
+fn hidden_layers_and_highlight() {
+    compare(|mmu| {
+        fill_vram(mmu);
+        place_sprites(mmu);
+        mmu.write_byte(0xFF4A, 50);
+        mmu.write_byte(0xFF4B, 40);
+        mmu.write_byte(0xFF40, 0xF3);
+        let layers = mmu.ppu.layers_mut();
+        layers.background = false;
+        layers.highlight = true;
+    });
+}
+
+// two solid sprites on line 20: `first` is OAM 0 and uses colour 1,
+// `second` is OAM 1 and uses colour 3. Returns the line from both renderers.
+fn overlapping_sprites(cgb: bool, first_x: u8, second_x: u8) -> [Vec<u32>; 2] {
+    let setup = |mmu: &mut MMU| {
+        mmu.set_cgb_mode(cgb);
+        // tile 1 all colour 1, tile 2 all colour 3
+        for row in 0..8 {
+            mmu.write_byte(0x8010 + row * 2, 0xFF);
+            mmu.write_byte(0x8011 + row * 2, 0x00);
+            mmu.write_byte(0x8020 + row * 2, 0xFF);
+            mmu.write_byte(0x8021 + row * 2, 0xFF);
+        }
+        mmu.write_byte(0xFF48, 0xE4);
+        // CGB object palette 0: colour 1 red, colour 3 blue
+        mmu.write_byte(0xFF6A, 0x80);
+        for value in [0xFF, 0x7F, 0x1F, 0x00, 0xE0, 0x03, 0x00, 0x7C] {
+            mmu.write_byte(0xFF6B, value);
+        }
+        for (i, sprite) in [[32, first_x, 0x01, 0x00], [32, second_x, 0x02, 0x00]].iter().enumerate() {
+            for (offset, value) in sprite.iter().enumerate() {
+                mmu.write_byte(0xFE00 + (i * 4 + offset) as u16, *value);
+            }
+        }
+        for i in 2..40 {
+            mmu.write_byte(0xFE00 + (i * 4) as u16, 0);
+        }
+        mmu.write_byte(0xFF40, 0x83);
+    };
+    let line = |mode| render(mode, &setup)[20 * 160..21 * 160].to_vec();
+    [line(RenderMode::Accurate), line(RenderMode::Fast)]
+}
+
+#[test]
+fn dmg_sprite_priority_by_x() {
+    // the second one is further left, so it wins where they overlap even
+    // though it comes later in OAM
+    for line in overlapping_sprites(false, 20, 16) {
+        assert_ne!(line[9], line[17], "the two sprites look the same");
+        assert_eq!(line[13], line[9]);
+    }
+}
+
+#[test]
+fn dmg_sprite_priority_on_equal_x() {
+    // apart, the first one covers 12-19 and the second 52-59
+    let [apart, _] = overlapping_sprites(false, 20, 60);
+    assert_ne!(apart[12], apart[52], "the two sprites look the same");
+    // same X, the lower OAM index wins
+    for line in overlapping_sprites(false, 20, 20) {
+        assert_eq!(line[12..20], [apart[12]; 8]);
+    }
+}
+
+#[test]
+fn cgb_sprite_priority_by_oam_index() {
+    // on CGB X doesn't matter, OAM 0 wins over OAM 1 further left
+    for line in overlapping_sprites(true, 20, 16) {
+        assert_ne!(line[9], line[17], "the two sprites look the same");
+        assert_eq!(line[13], line[17]);
+    }
+}
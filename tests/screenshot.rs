@@ -0,0 +1,138 @@
+use doma_emu::mmu::MMU;
+use doma_emu::png;
+
+// pulls the chunks back out and undoes the stored zlib blocks
+fn decode(data: &[u8]) -> (u32, u32, Vec<u8>) {
+    assert_eq!(&data[..8], &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]);
+    let (mut width, mut height, mut idat) = (0, 0, Vec::new());
+    let mut pos = 8;
+    while pos < data.len() {
+        let len = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        let kind = &data[pos + 4..pos + 8];
+        let body = &data[pos + 8..pos + 8 + len];
+        match kind {
+            b"IHDR" => {
+                width = u32::from_be_bytes(body[0..4].try_into().unwrap());
+                height = u32::from_be_bytes(body[4..8].try_into().unwrap());
+                assert_eq!(&body[8..], &[8, 2, 0, 0, 0]);
+            }
+            b"IDAT" => idat.extend_from_slice(body),
+            _ => {}
+        }
+        pos += 12 + len;
+    }
+
+    let mut raw = Vec::new();
+    let mut pos = 2;
+    loop {
+        let last = idat[pos] & 1 == 1;
+        let len = u16::from_le_bytes([idat[pos + 1], idat[pos + 2]]) as usize;
+        let nlen = u16::from_le_bytes([idat[pos + 3], idat[pos + 4]]);
+        assert_eq!(!nlen as usize, len);
+        raw.extend_from_slice(&idat[pos + 5..pos + 5 + len]);
+        pos += 5 + len;
+        if last {
+            break;
+        }
+    }
+    (width, height, raw)
+}
+
+#[test]
+fn screenshot_is_160x144() {
+    let mmu = MMU::new();
+    let (width, height, raw) = decode(&mmu.ppu.screenshot(1));
+    assert_eq!((width, height), (160, 144));
+    assert_eq!(raw.len(), 144 * (1 + 160 * 3));
+}
+
+#[test]
+fn upscaled_pixels() {
+    let pixels = [0x112233, 0x445566, 0x778899, 0xAABBCC];
+    let (width, height, raw) = decode(&png::encode(2, 2, &pixels, 3));
+    assert_eq!((width, height), (6, 6));
+
+    let row = 1 + 6 * 3;
+    // bottom right pixel of the top left square, and the first of the next one
+    assert_eq!(&raw[2 * row + 1 + 2 * 3..][..3], &[0x11, 0x22, 0x33]);
+    assert_eq!(&raw[2 * row + 1 + 3 * 3..][..3], &[0x44, 0x55, 0x66]);
+    assert_eq!(&raw[5 * row + 1 + 5 * 3..][..3], &[0xAA, 0xBB, 0xCC]);
+}
+// 24x20 RGB, pixel (x, y) = (x * 10, y * 12, x * y), filter type y % 5, dynamic Huffman codes
+const GRADIENT_PNG: &[u8] = &[
+    0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x14, 0x08, 0x02, 0x00, 0x00, 0x00, 0x18, 0xD7, 0x6A,
+    0xD4, 0x00, 0x00, 0x01, 0xBB, 0x49, 0x44, 0x41, 0x54, 0x78, 0xDA, 0xAD, 0xD4, 0xAB, 0x8F, 0x13,
+    0x51, 0x14, 0x80, 0xF1, 0xAF, 0x8F, 0x99, 0xB6, 0xD3, 0xDB, 0xF7, 0x2C, 0x0F, 0xC1, 0x18, 0xCC,
+    0x08, 0xD6, 0x8C, 0x00, 0xC1, 0x31, 0x98, 0x86, 0xB0, 0xA6, 0x02, 0x04, 0x63, 0x30, 0x35, 0x6B,
+    0x46, 0x80, 0x39, 0xBA, 0x21, 0xC1, 0xD4, 0x60, 0xAA, 0x6B, 0x30, 0xD5, 0x47, 0x37, 0x9B, 0x60,
+    0xAA, 0x47, 0xD7, 0x60, 0xAA, 0xF9, 0x0F, 0x60, 0xF6, 0x22, 0x20, 0x59, 0x41, 0xBA, 0x6D, 0xF2,
+    0x13, 0x47, 0x4C, 0xCE, 0xBD, 0xF3, 0x89, 0x0B, 0x10, 0x41, 0x0C, 0x09, 0xA4, 0x90, 0x81, 0xC0,
+    0x14, 0x66, 0x90, 0xC3, 0x1C, 0x0A, 0x50, 0x58, 0xC0, 0x12, 0x56, 0xB0, 0x86, 0x0D, 0x18, 0x6C,
+    0x61, 0x07, 0x25, 0xEC, 0xE1, 0x00, 0x35, 0x5C, 0xB5, 0xA8, 0x76, 0xBA, 0x7A, 0xB5, 0x08, 0x57,
+    0xAD, 0xAB, 0x86, 0x06, 0xAE, 0x89, 0x0B, 0x70, 0x21, 0xAE, 0x85, 0x6B, 0xE3, 0x3A, 0xB8, 0x08,
+    0xD7, 0xC5, 0x39, 0x5C, 0x0F, 0xD7, 0xC7, 0x0D, 0x70, 0x43, 0xDC, 0x08, 0x37, 0xC6, 0x4D, 0x70,
+    0x31, 0xEE, 0x02, 0xF7, 0x00, 0xF7, 0xB0, 0xC1, 0x23, 0x82, 0xB0, 0x1E, 0x84, 0x0D, 0xAF, 0xE9,
+    0x05, 0x5E, 0xE8, 0xB5, 0xBC, 0xB6, 0xD7, 0xF1, 0x22, 0xAF, 0xEB, 0x39, 0xAF, 0x57, 0x69, 0xDE,
+    0xDE, 0xA8, 0xFA, 0x41, 0xEA, 0xD0, 0x80, 0xE6, 0x09, 0x84, 0x48, 0x82, 0x58, 0xA2, 0x44, 0x06,
+    0xA9, 0xC4, 0x99, 0x3C, 0x16, 0x49, 0xA6, 0xF2, 0x74, 0x26, 0x69, 0x2E, 0x97, 0x73, 0xC9, 0x0A,
+    0x79, 0xA1, 0x22, 0x0B, 0x79, 0xB5, 0x94, 0xE9, 0x4A, 0xAE, 0xD6, 0x32, 0xDB, 0xC8, 0x3B, 0x93,
+    0x7C, 0x2B, 0x1F, 0x76, 0x32, 0x2F, 0xE5, 0x7A, 0x2F, 0xC5, 0x41, 0x3E, 0xD5, 0x78, 0x5D, 0xC5,
+    0x0E, 0x4F, 0x77, 0xC6, 0xD8, 0xCF, 0xB9, 0x5F, 0xDD, 0x20, 0xEC, 0x7B, 0x03, 0x6F, 0x78, 0x37,
+    0x76, 0x00, 0x21, 0xB4, 0xA0, 0x0D, 0x9D, 0x63, 0x28, 0x91, 0x46, 0xB1, 0xC6, 0x89, 0x26, 0xA9,
+    0xA6, 0x99, 0x66, 0xA2, 0x32, 0xD5, 0xE9, 0x4C, 0x67, 0xB9, 0xE6, 0x73, 0x9D, 0x17, 0x5A, 0xA8,
+    0xEA, 0x42, 0x17, 0x4B, 0x5D, 0xAE, 0x74, 0xB5, 0xD6, 0xF5, 0x46, 0x37, 0xA6, 0xB6, 0xD5, 0xED,
+    0x4E, 0x77, 0xA5, 0x96, 0x7B, 0xDD, 0x1F, 0xF4, 0x50, 0xE3, 0x73, 0x15, 0xBB, 0x7B, 0xBA, 0x33,
+    0xC6, 0x7E, 0xCB, 0xFD, 0xEA, 0x7A, 0xA3, 0x20, 0xFC, 0x1A, 0x84, 0xE3, 0xCA, 0x7F, 0x63, 0x57,
+    0x4F, 0x43, 0x97, 0xDB, 0x8F, 0x7A, 0xD0, 0xBF, 0x23, 0xFE, 0x67, 0x36, 0x22, 0x1B, 0xC4, 0x96,
+    0x24, 0x76, 0x99, 0x9A, 0x64, 0x76, 0x25, 0x96, 0x4F, 0xED, 0x7A, 0x66, 0x9A, 0xDB, 0x97, 0xB9,
+    0xAD, 0x0A, 0xFB, 0xA6, 0x66, 0x0B, 0xFB, 0xBE, 0xB4, 0x72, 0x65, 0x3F, 0xD6, 0xF6, 0x73, 0x63,
+    0xBF, 0xCC, 0xFA, 0x5B, 0x7B, 0xB2, 0xB3, 0x67, 0xA5, 0xBD, 0xDC, 0xDB, 0x9B, 0x83, 0xBD, 0xAF,
+    0x71, 0x53, 0x1D, 0x3A, 0x3C, 0xDD, 0x19, 0x63, 0x7F, 0xE4, 0xC8, 0xBA, 0xA3, 0x3F, 0x75, 0x83,
+    0x70, 0xE2, 0xC5, 0xDE, 0xC5, 0xB1, 0xB1, 0x27, 0x30, 0x84, 0x11, 0x8C, 0xFD, 0xFC, 0xD7, 0x6F,
+    0x78, 0x55, 0x85, 0x45, 0xC7, 0x2A, 0xD0, 0x21, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44,
+    0xAE, 0x42, 0x60, 0x82,
+];
+
+// 7x3 with a 2 bit palette of black, red, green, blue, index (x + y) % 4, fixed Huffman codes
+const PALETTE_PNG: &[u8] = &[
+    0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x03, 0x02, 0x03, 0x00, 0x00, 0x00, 0x22, 0xAD, 0xFD,
+    0x56, 0x00, 0x00, 0x00, 0x0C, 0x50, 0x4C, 0x54, 0x45, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00,
+    0xFF, 0x00, 0x00, 0x00, 0xFF, 0x9B, 0xC0, 0x13, 0xDC, 0x00, 0x00, 0x00, 0x11, 0x49, 0x44, 0x41,
+    0x54, 0x78, 0x01, 0x63, 0x90, 0x96, 0x60, 0xC8, 0xC9, 0x61, 0xD8, 0xB8, 0x01, 0x00, 0x07, 0x67,
+    0x02, 0x6D, 0x7F, 0xBC, 0x5A, 0xAA, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42,
+    0x60, 0x82,
+];
+
+#[test]
+fn decodes_compressed_filtered_rgb() {
+    let image = png::decode(GRADIENT_PNG).unwrap();
+    assert_eq!((image.width, image.height), (24, 20));
+    for y in 0..20 {
+        for x in 0..24 {
+            let want = ((x * 10) & 0xFF) << 16 | ((y * 12) & 0xFF) << 8 | ((x * y) & 0xFF);
+            assert_eq!(image.pixels[y as usize * 24 + x as usize], want, "x:{} y:{}", x, y);
+        }
+    }
+}
+
+#[test]
+fn decodes_small_palette() {
+    let image = png::decode(PALETTE_PNG).unwrap();
+    assert_eq!((image.width, image.height), (7, 3));
+    let colors = [0x000000, 0xFF0000, 0x00FF00, 0x0000FF];
+    for y in 0..3 {
+        for x in 0..7 {
+            assert_eq!(image.pixels[y * 7 + x], colors[(x + y) % 4]);
+        }
+    }
+}
+
+#[test]
+fn reads_back_its_own_screenshots() {
+    let pixels: Vec<u32> = (0..160 * 144u32).map(|i| i.wrapping_mul(2_654_435_761) & 0xFFFFFF).collect();
+    let image = png::decode(&png::encode(160, 144, &pixels, 1)).unwrap();
+    assert_eq!((image.width, image.height), (160, 144));
+    assert_eq!(image.pixels, pixels);
+}
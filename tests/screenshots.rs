@@ -0,0 +1,83 @@
+// Screen regression tests: every ROM in <roms>/screenshots/ (see
+// common::rom_path) is run and its screen compared with the PNG of the same
+// name, e.g. dmg-acid2.gb with dmg-acid2.png. A <name>.frames file holding a
+// number runs it for exactly that many frames, otherwise it runs until the
+// LD B,B the acid tests end with. Mismatches leave <name>-actual.png and
+// <name>-diff.png in the target dir's tmp directory.
+
+mod common;
+
+use std::fs;
+
+use doma_emu::runner::{Runner, StopCondition};
+
+const MAX_FRAMES: u32 = 60 * 10;
+
+#[test]
+fn screenshots() {
+    let Some(dir) = common::rom_path("screenshots") else {
+        return;
+    };
+    let failures = format!("{}/screenshots", env!("CARGO_TARGET_TMPDIR"));
+
+    let mut roms: Vec<_> = fs::read_dir(&dir).unwrap()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "gb" || ext == "gbc"))
+        .collect();
+    roms.sort();
+
+    let mut failed = Vec::new();
+    for rom in roms {
+        let reference = rom.with_extension("png");
+        if !reference.exists() {
+            eprintln!("skipping {}, no {}", rom.display(), reference.display());
+            continue;
+        }
+
+        let mut runner = Runner::from_file(rom.to_str().unwrap()).unwrap();
+        match fs::read_to_string(rom.with_extension("frames")) {
+            Ok(frames) => {
+                runner.run(frames.trim().parse().expect("bad .frames file"), &[]);
+            }
+            Err(_) => {
+                runner.run(MAX_FRAMES, &[StopCondition::Breakpoint]);
+            }
+        }
+
+        if let Err(error) = runner.compare_screen(reference.to_str().unwrap(), &failures) {
+            failed.push(error);
+        }
+    }
+    assert!(failed.is_empty(), "\n{}", failed.join("\n"));
+}
+
+// the comparison itself, against a screen saved from the same run and one
+// with a pixel changed
+#[test]
+fn compare_screen() {
+    let mut rom = vec![0; 0x8000];
+    // JR -2 at the entry point
+    rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+    let mut runner = Runner::new(&rom);
+    runner.run(2, &[]);
+
+    let dir = format!("{}/compare_screen", env!("CARGO_TARGET_TMPDIR"));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let reference = format!("{}/reference.png", dir);
+    runner.save_screen(&reference).unwrap();
+    assert_eq!(runner.compare_screen(&reference, &dir), Ok(()));
+    assert!(!std::path::Path::new(&format!("{}/reference-diff.png", dir)).exists());
+
+    let mut pixels = doma_emu::png::load(&reference).unwrap().pixels;
+    pixels[0] ^= 0xFFFFFF;
+    doma_emu::png::save(&reference, 160, 144, &pixels, 1).unwrap();
+    let error = runner.compare_screen(&reference, &dir).unwrap_err();
+    assert!(error.starts_with("1 pixels differ"), "{}", error);
+
+    let diff = doma_emu::png::load(&format!("{}/reference-diff.png", dir)).unwrap();
+    assert_eq!(diff.pixels[0], 0xFF0000);
+    assert!(diff.pixels[1..].iter().all(|&pixel| pixel != 0xFF0000));
+    let actual = doma_emu::png::load(&format!("{}/reference-actual.png", dir)).unwrap();
+    assert_eq!(actual.pixels, runner.mmu.ppu.video_buffer());
+}
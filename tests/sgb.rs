@@ -0,0 +1,256 @@
+// Super Game Boy packets, attributes, masking and the border
+
+use doma_emu::joypad::JoyPad;
+use doma_emu::mmu::MMU;
+use doma_emu::ppu::palette::rgb555_to_rgb888;
+use doma_emu::ppu::{XRES, YRES};
+use doma_emu::sgb::{Sgb, SGB_HEIGHT, SGB_WIDTH};
+
+// colour 3 of each palette after `sgb()`
+const COLOR3: [u16; 4] = [0x001F, 0x03E0, 0x7C00, 0x7FFF];
+const BACKDROP: u16 = 0x1234;
+
+// a command of `packets` packets, `data` goes after the header byte
+fn command(code: u8, packets: u8, data: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0; packets as usize * 16];
+    bytes[0] = code << 3 | packets;
+    bytes[1..=data.len()].copy_from_slice(data);
+    bytes
+}
+
+fn send(sgb: &mut Sgb, bytes: &[u8]) {
+    let mut joypad = JoyPad::default();
+    for packet in bytes.chunks_exact(16) {
+        sgb.handle_packet(packet.try_into().unwrap(), &mut joypad, 0);
+    }
+}
+
+// PAL01 and PAL23, so every palette has its own colour 3
+fn sgb() -> Sgb {
+    let mut sgb = Sgb::default();
+    sgb.set_enabled(true);
+    for (code, first) in [(0x00, 0), (0x01, 2)] {
+        let mut colors = [0; 14];
+        colors[..2].copy_from_slice(&BACKDROP.to_le_bytes());
+        colors[6..8].copy_from_slice(&COLOR3[first].to_le_bytes());
+        colors[12..14].copy_from_slice(&COLOR3[first + 1].to_le_bytes());
+        send(&mut sgb, &command(code, 1, &colors));
+    }
+    sgb
+}
+
+fn screen(shade: u8) -> Vec<u8> {
+    vec![shade; XRES * YRES]
+}
+
+// the Game Boy pixel at the top left of a cell
+fn cell(frame: &[u32], x: usize, y: usize) -> u32 {
+    frame[(40 + y * 8) * SGB_WIDTH + 48 + x * 8]
+}
+
+// which palette each cell got, rendering colour 3 everywhere
+fn cell_palettes(sgb: &mut Sgb) -> Vec<Vec<usize>> {
+    let frame = sgb.render(&screen(3));
+    (0..18).map(|y| (0..20).map(|x| {
+        let color = cell(&frame, x, y);
+        COLOR3.iter().position(|&c| rgb555_to_rgb888(c) == color).unwrap()
+    }).collect()).collect()
+}
+
+#[test]
+fn packets_over_the_joypad_lines() {
+    let mut joypad = JoyPad::default();
+    let packet: [u8; 16] = std::array::from_fn(|i| (i * 17) as u8);
+
+    // idle, then reset
+    joypad.write_joypad(0x30);
+    joypad.write_joypad(0x00);
+    joypad.write_joypad(0x30);
+    for bit in 0..128 {
+        let one = packet[bit / 8] >> (bit % 8) & 1 != 0;
+        joypad.write_joypad(if one { 0x10 } else { 0x20 });
+        joypad.write_joypad(0x30);
+    }
+    assert_eq!(joypad.take_sgb_packet(), None);
+    // the 0 stop bit
+    joypad.write_joypad(0x20);
+    joypad.write_joypad(0x30);
+    assert_eq!(joypad.take_sgb_packet(), Some(packet));
+    assert_eq!(joypad.take_sgb_packet(), None);
+}
+
+#[test]
+fn garbled_stop_bit_drops_the_packet() {
+    let mut joypad = JoyPad::default();
+    joypad.write_joypad(0x30);
+    joypad.write_joypad(0x00);
+    joypad.write_joypad(0x30);
+    for _ in 0..129 {
+        joypad.write_joypad(0x10);
+        joypad.write_joypad(0x30);
+    }
+    assert_eq!(joypad.take_sgb_packet(), None);
+}
+
+#[test]
+fn palettes() {
+    let mut sgb = sgb();
+    // shade 0 is the shared colour 0, everything starts on palette 0
+    let frame = sgb.render(&screen(0));
+    assert_eq!(frame[0], rgb555_to_rgb888(BACKDROP));
+    assert_eq!(cell(&frame, 5, 5), rgb555_to_rgb888(BACKDROP));
+    assert!(cell_palettes(&mut sgb).iter().flatten().all(|&palette| palette == 0));
+}
+
+#[test]
+fn attr_blk() {
+    let mut sgb = sgb();
+    // inside 1, border 2, outside 3, box from (2, 3) to (6, 8)
+    send(&mut sgb, &command(0x04, 1, &[1, 0b111, 0b11_10_01, 2, 3, 6, 8]));
+    let cells = cell_palettes(&mut sgb);
+    assert_eq!(cells[5][4], 1);
+    assert_eq!(cells[3][4], 2);
+    assert_eq!(cells[5][6], 2);
+    assert_eq!(cells[0][0], 3);
+    assert_eq!(cells[9][4], 3);
+
+    // only inside also colours the border, outside stays as it was
+    send(&mut sgb, &command(0x04, 1, &[1, 0b001, 0b00_00_00, 10, 10, 12, 12]));
+    let cells = cell_palettes(&mut sgb);
+    assert_eq!(cells[10][10], 0);
+    assert_eq!(cells[11][11], 0);
+    assert_eq!(cells[5][4], 1);
+    assert_eq!(cells[0][0], 3);
+}
+
+#[test]
+fn attr_lin() {
+    let mut sgb = sgb();
+    // row 4 palette 1, then column 7 palette 2 across it
+    send(&mut sgb, &command(0x05, 1, &[2, 0x80 | 1 << 5 | 4, 2 << 5 | 7]));
+    let cells = cell_palettes(&mut sgb);
+    assert_eq!(cells[4][0], 1);
+    assert_eq!(cells[4][19], 1);
+    assert_eq!(cells[4][7], 2);
+    assert_eq!(cells[17][7], 2);
+    assert_eq!(cells[3][0], 0);
+}
+
+#[test]
+fn attr_div() {
+    let mut sgb = sgb();
+    // split above/below row 6: above 1, on it 2, below 3
+    send(&mut sgb, &command(0x06, 1, &[0x40 | 2 << 4 | 1 << 2 | 3, 6]));
+    let cells = cell_palettes(&mut sgb);
+    assert_eq!(cells[5][10], 1);
+    assert_eq!(cells[6][10], 2);
+    assert_eq!(cells[7][10], 3);
+
+    // left/right of column 3
+    send(&mut sgb, &command(0x06, 1, &[2 << 4 | 1 << 2 | 3, 3]));
+    let cells = cell_palettes(&mut sgb);
+    assert_eq!(cells[0][2], 1);
+    assert_eq!(cells[0][3], 2);
+    assert_eq!(cells[17][4], 3);
+}
+
+#[test]
+fn attr_chr() {
+    let mut sgb = sgb();
+    // 5 cells left to right from (18, 2), wrapping onto the next row
+    send(&mut sgb, &command(0x07, 1, &[18, 2, 5, 0, 0, 0b01_10_11_01, 0b10_00_00_00]));
+    let cells = cell_palettes(&mut sgb);
+    assert_eq!(&cells[2][18..], &[1, 2]);
+    assert_eq!(&cells[3][..4], &[3, 1, 2, 0]);
+
+    // top to bottom from (0, 16), wrapping onto the next column
+    send(&mut sgb, &command(0x07, 1, &[0, 16, 3, 0, 1, 0b11_11_11_00]));
+    let cells = cell_palettes(&mut sgb);
+    assert_eq!(cells[16][0], 3);
+    assert_eq!(cells[17][0], 3);
+    assert_eq!(cells[0][1], 3);
+    assert_eq!(cells[1][1], 0);
+}
+
+#[test]
+fn mask_en() {
+    let mut sgb = sgb();
+    let shade = |frame: &[u32]| cell(frame, 0, 0);
+
+    // freeze keeps the screen from when it was set
+    send(&mut sgb, &command(0x17, 1, &[1]));
+    assert_eq!(shade(&sgb.render(&screen(3))), rgb555_to_rgb888(COLOR3[0]));
+    assert_eq!(shade(&sgb.render(&screen(0))), rgb555_to_rgb888(COLOR3[0]));
+
+    send(&mut sgb, &command(0x17, 1, &[2]));
+    assert_eq!(shade(&sgb.render(&screen(3))), 0x000000);
+
+    send(&mut sgb, &command(0x17, 1, &[3]));
+    assert_eq!(shade(&sgb.render(&screen(3))), rgb555_to_rgb888(BACKDROP));
+
+    // and back to the live screen
+    send(&mut sgb, &command(0x17, 1, &[0]));
+    assert_eq!(shade(&sgb.render(&screen(0))), rgb555_to_rgb888(BACKDROP));
+    assert_eq!(shade(&sgb.render(&screen(3))), rgb555_to_rgb888(COLOR3[0]));
+}
+
+#[test]
+fn border_with_a_full_map() {
+    let mut sgb = sgb();
+    const BLUE: u16 = 0x7C00;
+
+    // CHR_TRN: tile 1 is colour 1 all over
+    send(&mut sgb, &command(0x13, 1, &[0]));
+    assert!(sgb.transfer_pending(1));
+    let mut tiles = [0; 0x1000];
+    for row in 0..8 {
+        tiles[32 + row * 2] = 0xFF;
+    }
+    sgb.finish_transfer(&tiles);
+
+    // PCT_TRN: all 32 rows use tile 1 and palette 4
+    send(&mut sgb, &command(0x14, 1, &[]));
+    assert!(sgb.transfer_pending(1));
+    let mut map = [0; 0x1000];
+    for entry in map[..0x800].chunks_exact_mut(2) {
+        entry[0] = 1;
+    }
+    map[0x802..0x804].copy_from_slice(&BLUE.to_le_bytes());
+    sgb.finish_transfer(&map);
+
+    let frame = sgb.render(&screen(0));
+    assert_eq!(frame.len(), SGB_WIDTH * SGB_HEIGHT);
+    assert!(frame.iter().all(|&pixel| pixel == rgb555_to_rgb888(BLUE)));
+}
+
+// the PPU keeps drawing colours, the shades the SGB uses go to their own buffer
+#[test]
+fn ppu_writes_shades_beside_colours() {
+    let frame = |bgp: u8| {
+        let mut mmu = MMU::new();
+        mmu.set_sgb_mode(true);
+        // tile 0 is colour 1 all over, and the map is all tile 0
+        for address in (0x8000..0x8010).step_by(2) {
+            mmu.write_byte(address, 0xFF);
+        }
+        mmu.write_byte(0xFF40, 0x91);
+        mmu.write_byte(0xFF47, bgp);
+        while mmu.ppu.current_frame < 2 {
+            mmu.ppu.ppu_ticks(&mut mmu.interrupts);
+        }
+        (mmu.ppu.video_buffer()[0], mmu.ppu.shade_buffer()[0])
+    };
+
+    assert_eq!(frame(0xE4), (0xAAAAAA, 1));
+    assert_eq!(frame(0x1B), (0x555555, 2));
+}
+
+#[test]
+fn sgb_mode_leaves_the_dmg_colours_alone() {
+    let mut mmu = MMU::new();
+    let colors = mmu.ppu.dmg_colors();
+    mmu.set_sgb_mode(true);
+    assert_eq!(mmu.ppu.dmg_colors(), colors);
+    mmu.set_sgb_mode(false);
+    assert_eq!(mmu.ppu.dmg_colors(), colors);
+}
@@ -0,0 +1,173 @@
+// The SM83 single step tests: one JSON file per opcode (`00.json` to
+// `ff.json`, `cb 00.json` to `cb ff.json`), each a list of cases with the
+// state before and after one instruction and the bus activity of every
+// M-cycle. Expects them under <roms>/sm83/v1/, see common::rom_path.
+
+mod common;
+
+use std::fs;
+
+use serde_json::Value;
+
+use doma_emu::bus::{Bus, BusAccess, TestBus};
+use doma_emu::cpu::{CPU, Registers};
+
+fn registers(state: &Value) -> Registers {
+    let byte = |name: &str| state[name].as_u64().unwrap() as u8;
+    let word = |name: &str| state[name].as_u64().unwrap() as u16;
+    Registers {
+        a: byte("a"),
+        f: byte("f"),
+        b: byte("b"),
+        c: byte("c"),
+        d: byte("d"),
+        e: byte("e"),
+        h: byte("h"),
+        l: byte("l"),
+        sp: word("sp"),
+        pc: word("pc"),
+        ime: state["ime"].as_u64() == Some(1),
+        ei: state["ei"].as_u64() == Some(1),
+    }
+}
+
+fn ram(state: &Value) -> Vec<(u16, u8)> {
+    state["ram"].as_array().unwrap().iter()
+        .map(|entry| (entry[0].as_u64().unwrap() as u16, entry[1].as_u64().unwrap() as u8))
+        .collect()
+}
+
+// null or "---" cycles don't touch the bus
+fn bus_accesses(cycles: &Value) -> Vec<BusAccess> {
+    cycles.as_array().unwrap().iter().enumerate().filter_map(|(i, cycle)| {
+        let kind = cycle.get(2)?.as_str()?;
+        let write = kind.contains('w');
+        if !write && !kind.contains('r') {
+            return None;
+        }
+        Some(BusAccess {
+            cycle: i,
+            address: cycle[0].as_u64()? as u16,
+            value: cycle[1].as_u64()? as u8,
+            write,
+        })
+    }).collect()
+}
+
+// runs one case, Err says what went wrong
+fn run_case(case: &Value) -> Result<(), String> {
+    let initial = &case["initial"];
+    let expected = &case["final"];
+
+    let mut bus = TestBus::new();
+    for (address, value) in ram(initial) {
+        bus.write(address, value);
+    }
+    let mut cpu = CPU::from_registers(registers(initial));
+
+    bus.start_log();
+    let m_cycles = cpu.run_instruction(&mut bus);
+    let log = bus.take_log();
+
+    let mut got = cpu.registers();
+    let mut want = registers(expected);
+    // IME after EI is only checked as far as it's going to be set
+    if want.ime && got.ei {
+        got.ime = true;
+    }
+    got.ei = false;
+    want.ei = false;
+    if got != want {
+        return Err(format!("registers\n  got  {:?}\n  want {:?}", got, want));
+    }
+
+    for (address, value) in ram(expected) {
+        let actual = bus.read(address);
+        if actual != value {
+            return Err(format!("ram {:#06X} is {:#04X}, want {:#04X}", address, actual, value));
+        }
+    }
+
+    let accesses = bus_accesses(&case["cycles"]);
+    if log != accesses {
+        return Err(format!("bus\n  got  {:?}\n  want {:?}", log, accesses));
+    }
+    let cycles = case["cycles"].as_array().unwrap().len();
+    if m_cycles as usize != cycles || bus.cycles() != cycles {
+        return Err(format!("took {} M-cycles ({} ticked), want {}", m_cycles, bus.cycles(), cycles));
+    }
+    Ok(())
+}
+
+// first failure of every case list
+fn run_cases(name: &str, cases: &Value, failed: &mut Vec<String>) {
+    for case in cases.as_array().unwrap() {
+        if let Err(error) = run_case(case) {
+            failed.push(format!("{} {}: {}", name, case["name"], error));
+            return;
+        }
+    }
+}
+
+#[test]
+fn sm83_opcodes() {
+    let Some(dir) = common::rom_path("sm83/v1") else {
+        return;
+    };
+
+    let mut failed = Vec::new();
+    let mut files = 0;
+    for opcode in 0..0x200u16 {
+        let name = if opcode < 0x100 {
+            format!("{:02x}", opcode)
+        } else {
+            format!("cb {:02x}", opcode & 0xFF)
+        };
+        // the unused opcodes don't have tests
+        let Ok(json) = fs::read_to_string(dir.join(format!("{}.json", name))) else {
+            continue;
+        };
+        files += 1;
+        let cases: Value = serde_json::from_str(&json).unwrap();
+        run_cases(&name, &cases, &mut failed);
+    }
+    eprintln!("{} of {} opcodes pass", files - failed.len(), files);
+    assert!(failed.is_empty(), "\n{}", failed.join("\n"));
+}
+
+// a couple of cases in the same format, so the harness itself gets checked
+// even without the test files around
+#[test]
+fn harness_cases() {
+    let cases = r#"[
+        {
+            "name": "77 ld (hl),a",
+            "initial": {"a": 171, "b": 0, "c": 0, "d": 0, "e": 0, "f": 0, "h": 192, "l": 16,
+                "pc": 256, "sp": 65534, "ime": 0, "ram": [[256, 119], [49168, 0]]},
+            "final": {"a": 171, "b": 0, "c": 0, "d": 0, "e": 0, "f": 0, "h": 192, "l": 16,
+                "pc": 257, "sp": 65534, "ime": 0, "ram": [[256, 119], [49168, 171]]},
+            "cycles": [[256, 119, "r-m"], [49168, 171, "-wm"]]
+        },
+        {
+            "name": "c5 push bc",
+            "initial": {"a": 0, "b": 18, "c": 52, "d": 0, "e": 0, "f": 0, "h": 0, "l": 0,
+                "pc": 40960, "sp": 65280, "ime": 0, "ram": [[40960, 197]]},
+            "final": {"a": 0, "b": 18, "c": 52, "d": 0, "e": 0, "f": 0, "h": 0, "l": 0,
+                "pc": 40961, "sp": 65278, "ime": 0, "ram": [[65279, 18], [65278, 52]]},
+            "cycles": [[40960, 197, "r-m"], null, [65279, 18, "-wm"], [65278, 52, "-wm"]]
+        },
+        {
+            "name": "cd call a16",
+            "initial": {"a": 0, "b": 0, "c": 0, "d": 0, "e": 0, "f": 0, "h": 0, "l": 0,
+                "pc": 256, "sp": 53248, "ime": 0, "ram": [[256, 205], [257, 0], [258, 2]]},
+            "final": {"a": 0, "b": 0, "c": 0, "d": 0, "e": 0, "f": 0, "h": 0, "l": 0,
+                "pc": 512, "sp": 53246, "ime": 0, "ram": [[53247, 1], [53246, 3]]},
+            "cycles": [[256, 205, "r-m"], [257, 0, "r-m"], [258, 2, "r-m"], null,
+                [53247, 1, "-wm"], [53246, 3, "-wm"]]
+        }
+    ]"#;
+    let cases: Value = serde_json::from_str(cases).unwrap();
+    let mut failed = Vec::new();
+    run_cases("harness", &cases, &mut failed);
+    assert!(failed.is_empty(), "\n{}", failed.join("\n"));
+}
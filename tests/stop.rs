@@ -0,0 +1,109 @@
+// STOP on the whole machine: the clock stops until a button is pressed, or
+// a CGB switches speed
+
+use doma_emu::joypad::JoypadButtons;
+use doma_emu::runner::Runner;
+
+// selects `p1` on the joypad, STOPs, then INC B forever
+fn stop_rom(p1: u8, cgb: bool) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x143] = if cgb {0x80} else {0x00};
+    let code = [
+        0x3E, p1,   // LD A,p1
+        0xE0, 0x00, // LDH (0x00),A
+        0x10, 0x00, // STOP
+        0x04,       // INC B
+        0x18, 0xFE, // JR -2
+    ];
+    rom[0x100..0x100 + code.len()].copy_from_slice(&code);
+    rom
+}
+
+const AFTER_STOP: u16 = 0x106;
+
+fn run_to(runner: &mut Runner, pc: u16) {
+    for _ in 0..100 {
+        if runner.cpu.get_pc() == pc {
+            return;
+        }
+        runner.step();
+    }
+    panic!("never got to {:#06X}", pc);
+}
+
+#[test]
+fn waits_for_a_selected_button() {
+    // buttons selected, directions not
+    let mut runner = Runner::new(&stop_rom(0x10, false));
+    run_to(&mut runner, AFTER_STOP);
+    assert!(runner.mmu.stopped());
+    assert_eq!(runner.mmu.read_byte(0xFF04), 0);
+
+    let frame = runner.frame();
+    let dots = runner.mmu.read_byte(0xFF44);
+    for _ in 0..100_000 {
+        runner.step();
+    }
+    assert_eq!(runner.cpu.get_pc(), AFTER_STOP);
+    assert_eq!((runner.frame(), runner.mmu.read_byte(0xFF44)), (frame, dots));
+    assert_eq!(runner.mmu.read_byte(0xFF04), 0);
+
+    // not selected, so it doesn't pull a line low
+    runner.mmu.joypad.press_joypad(JoypadButtons::Right);
+    runner.step();
+    assert!(runner.mmu.stopped());
+
+    runner.mmu.joypad.press_joypad(JoypadButtons::A);
+    runner.step();
+    runner.step();
+    assert!(!runner.mmu.stopped());
+    assert_eq!(runner.cpu.get_b(), 1);
+    // the joypad interrupt got requested on the way
+    assert_eq!(runner.mmu.interrupts.read_requested() & 0x10, 0x10);
+}
+
+#[test]
+fn held_button_halts_instead() {
+    let mut runner = Runner::new(&stop_rom(0x10, false));
+    runner.mmu.joypad.press_joypad(JoypadButtons::Start);
+    run_to(&mut runner, AFTER_STOP);
+    assert!(!runner.mmu.stopped());
+    assert!(runner.cpu.get_halted());
+}
+
+#[test]
+fn held_button_with_interrupt_pending_is_one_byte() {
+    let mut runner = Runner::new(&stop_rom(0x10, false));
+    runner.mmu.joypad.press_joypad(JoypadButtons::Start);
+    // selecting the buttons requests the joypad interrupt, IME stays off
+    runner.mmu.interrupts.write_enabled(0x10);
+    run_to(&mut runner, AFTER_STOP - 1);
+    assert!(!runner.mmu.stopped());
+    assert!(!runner.cpu.get_halted());
+
+    // the STOP operand runs as a NOP
+    runner.step();
+    assert_eq!(runner.cpu.get_pc(), AFTER_STOP);
+    runner.step();
+    assert_eq!(runner.cpu.get_b(), 1);
+}
+
+#[test]
+fn cgb_speed_switch_stalls() {
+    let mut rom = stop_rom(0x30, true);
+    // arm KEY1 instead of touching the joypad
+    rom[0x101] = 0x01;
+    rom[0x103] = 0x4D;
+    let mut runner = Runner::new(&rom);
+    run_to(&mut runner, AFTER_STOP);
+    assert!(!runner.mmu.stopped());
+    assert_eq!(runner.mmu.read_byte(0xFF4D) & 0x81, 0x80);
+
+    let mut stalled = 0;
+    while runner.cpu.get_b() == 0 {
+        runner.step();
+        stalled += 1;
+        assert!(stalled < 3000, "never came back from the speed switch");
+    }
+    assert!((2050..2055).contains(&stalled), "stalled for {} M-cycles", stalled);
+}
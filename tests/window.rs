@@ -0,0 +1,190 @@
+// Window edge cases: WX below 7 and at 166, turning the window on mid-frame,
+// and the internal line counter. Both renderers have to agree with hardware.
+
+use doma_emu::mmu::MMU;
+use doma_emu::ppu::RenderMode;
+
+const FRAMES: u32 = 3;
+const MODES: [RenderMode; 2] = [RenderMode::Accurate, RenderMode::Fast];
+
+// LCD on, window map at 0x9C00, tile data at 0x8000, BG on. BG map is all tile 0
+// (blank) and the window map is all tile 1, so any shade 3 pixel is the window.
+const LCDC: u8 = 0xD1;
+const LCDC_WINDOW: u8 = LCDC | 0x20;
+
+// only the leftmost column of each tile, marks every 8th window pixel
+const STRIPE: [u8; 8] = [0x80; 8];
+// only the top row of each tile, marks every 8th window line
+const TOP_ROW: [u8; 8] = [0xFF, 0, 0, 0, 0, 0, 0, 0];
+const SOLID: [u8; 8] = [0xFF; 8];
+
+// `on_line` runs as each line starts, for mid-frame register writes.
+// Returns the shades of the last frame.
+fn render(mode: RenderMode, tile: [u8; 8], setup: &dyn Fn(&mut MMU), on_line: &dyn Fn(&mut MMU, u8)) -> Vec<u8> {
+    let mut mmu = MMU::new();
+    mmu.ppu.set_render_mode(mode);
+    for (row, value) in tile.iter().enumerate() {
+        mmu.write_byte(0x8010 + row as u16 * 2, *value);
+        mmu.write_byte(0x8011 + row as u16 * 2, *value);
+    }
+    for address in 0x9C00..=0x9FFF {
+        mmu.write_byte(address, 0x01);
+    }
+    mmu.write_byte(0xFF47, 0xE4);
+    setup(&mut mmu);
+
+    let mut line = None;
+    while mmu.ppu.current_frame < FRAMES {
+        let ly = mmu.read_byte(0xFF44);
+        if line != Some(ly) {
+            line = Some(ly);
+            on_line(&mut mmu, ly);
+        }
+        mmu.ppu.ppu_ticks(&mut mmu.interrupts);
+    }
+    mmu.ppu.shade_buffer().to_vec()
+}
+
+fn row(shades: &[u8], y: usize) -> &[u8] {
+    &shades[y * 160..(y + 1) * 160]
+}
+
+// the x positions on line 0 that show the window's stripe
+fn stripe_columns(scroll_x: u8, window_x: u8) -> Vec<Vec<usize>> {
+    let setup = |mmu: &mut MMU| {
+        mmu.write_byte(0xFF43, scroll_x);
+        mmu.write_byte(0xFF4A, 0);
+        mmu.write_byte(0xFF4B, window_x);
+        mmu.write_byte(0xFF40, LCDC_WINDOW);
+    };
+    MODES.iter().map(|&mode| {
+        let shades = render(mode, STRIPE, &setup, &|_, _| ());
+        (0..160).filter(|&x| row(&shades, 0)[x] == 3).collect()
+    }).collect()
+}
+
+fn every_8th(first: usize) -> Vec<usize> {
+    (first..160).step_by(8).collect()
+}
+
+#[test]
+fn window_x_7_starts_at_the_left_edge() {
+    for columns in stripe_columns(5, 7) {
+        assert_eq!(columns, every_8th(0));
+    }
+}
+
+#[test]
+fn window_x_below_7_cuts_off_the_left() {
+    // WX=3 starts 4 pixels off screen, SCX doesn't matter
+    for scroll_x in [0, 5] {
+        for columns in stripe_columns(scroll_x, 3) {
+            assert_eq!(columns, every_8th(4), "scx {}", scroll_x);
+        }
+    }
+}
+
+#[test]
+fn window_x_0_is_shifted_by_fine_scroll() {
+    // WX=0 cuts off 7 pixels plus the SCX % 8 ones
+    for columns in stripe_columns(0, 0) {
+        assert_eq!(columns, every_8th(1));
+    }
+    for columns in stripe_columns(3, 0) {
+        assert_eq!(columns, every_8th(6));
+    }
+    for columns in stripe_columns(11, 0) {
+        assert_eq!(columns, every_8th(6));
+    }
+}
+
+#[test]
+fn window_x_166_shows_one_column() {
+    let setup = |window_x: u8| move |mmu: &mut MMU| {
+        mmu.write_byte(0xFF4A, 0);
+        mmu.write_byte(0xFF4B, window_x);
+        mmu.write_byte(0xFF40, LCDC_WINDOW);
+    };
+    for mode in MODES {
+        let shades = render(mode, SOLID, &setup(166), &|_, _| ());
+        for y in 0..144 {
+            let line = row(&shades, y);
+            assert!(line[..159].iter().all(|&shade| shade == 0), "{:?} line {}", mode, y);
+            assert_eq!(line[159], 3, "{:?} line {}", mode, y);
+        }
+
+        let shades = render(mode, SOLID, &setup(167), &|_, _| ());
+        assert!(shades.iter().all(|&shade| shade == 0), "{:?} WX=167 drew the window", mode);
+    }
+}
+
+#[test]
+fn window_enabled_mid_frame() {
+    // WY=10 is passed while the window is off, it still triggers and the
+    // window starts from its first line once it's turned on at line 53
+    let setup = |mmu: &mut MMU| {
+        mmu.write_byte(0xFF4A, 10);
+        mmu.write_byte(0xFF4B, 7);
+    };
+    let lcdc = |mmu: &mut MMU, ly: u8| mmu.write_byte(0xFF40, if ly >= 53 {LCDC_WINDOW} else {LCDC});
+    for mode in MODES {
+        let shades = render(mode, TOP_ROW, &setup, &lcdc);
+        for y in 0..144 {
+            let expected = if y >= 53 && (y - 53) % 8 == 0 {3} else {0};
+            assert!(row(&shades, y).iter().all(|&shade| shade == expected), "{:?} line {}", mode, y);
+        }
+    }
+}
+
+#[test]
+fn window_y_below_the_screen_never_triggers() {
+    let setup = |mmu: &mut MMU| {
+        mmu.write_byte(0xFF4A, 200);
+        mmu.write_byte(0xFF4B, 7);
+        mmu.write_byte(0xFF40, LCDC_WINDOW);
+    };
+    for mode in MODES {
+        let shades = render(mode, SOLID, &setup, &|_, _| ());
+        assert!(shades.iter().all(|&shade| shade == 0), "{:?} drew the window", mode);
+    }
+}
+
+#[test]
+fn window_line_counter_skips_hidden_lines() {
+    // the window is off for lines 20-29, so line 30 picks up at window line 20
+    // instead of jumping to 30
+    let setup = |mmu: &mut MMU| {
+        mmu.write_byte(0xFF4A, 0);
+        mmu.write_byte(0xFF4B, 7);
+    };
+    let lcdc = |mmu: &mut MMU, ly: u8| mmu.write_byte(0xFF40, if (20..30).contains(&ly) {LCDC} else {LCDC_WINDOW});
+    for mode in MODES {
+        let shades = render(mode, TOP_ROW, &setup, &lcdc);
+        for y in 0..144 {
+            let window_line = match y {
+                0..=19 => Some(y),
+                20..=29 => None,
+                _ => Some(y - 10),
+            };
+            let expected = if window_line.is_some_and(|line| line % 8 == 0) {3} else {0};
+            assert!(row(&shades, y).iter().all(|&shade| shade == expected), "{:?} line {}", mode, y);
+        }
+    }
+}
+
+#[test]
+fn window_pushed_off_screen_keeps_its_line() {
+    // moving WX past 166 for lines 20-29 hides the window the same way
+    // turning it off does
+    let setup = |mmu: &mut MMU| {
+        mmu.write_byte(0xFF4A, 0);
+        mmu.write_byte(0xFF40, LCDC_WINDOW);
+    };
+    let window_x = |mmu: &mut MMU, ly: u8| mmu.write_byte(0xFF4B, if (20..30).contains(&ly) {200} else {7});
+    for mode in MODES {
+        let shades = render(mode, TOP_ROW, &setup, &window_x);
+        assert!(row(&shades, 25).iter().all(|&shade| shade == 0), "{:?} line 25", mode);
+        assert!(row(&shades, 32).iter().all(|&shade| shade == 0), "{:?} line 32", mode);
+        assert!(row(&shades, 34).iter().all(|&shade| shade == 3), "{:?} line 34", mode);
+    }
+}